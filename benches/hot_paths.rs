@@ -0,0 +1,78 @@
+//! Criterion benchmarks for the framing and statistics hot paths
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use websocket_benchmark::stats::RttStats;
+use websocket_benchmark::websocket::{
+    parse_frame_borrowed, ThreadRngSource, WebSocketFrame, DEFAULT_MAX_PAYLOAD, SMALL_PAYLOAD_MAX,
+};
+
+const FRAME_SIZES: [usize; 4] = [16, 1024, 64 * 1024, 1024 * 1024];
+const SMALL_SIZES: [usize; 3] = [4, 16, SMALL_PAYLOAD_MAX];
+const RTT_COUNTS: [usize; 3] = [10_000, 100_000, 1_000_000];
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    StdRng::seed_from_u64(1).fill(data.as_mut_slice());
+    data
+}
+
+fn frame_creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_frame");
+    for size in FRAME_SIZES {
+        let payload = random_bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &payload, |b, payload| {
+            b.iter(|| WebSocketFrame::create_binary_frame(black_box(payload)))
+        });
+    }
+    group.finish();
+}
+
+fn small_frame_creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_small_frame");
+    for size in SMALL_SIZES {
+        let payload = random_bytes(size);
+        group.bench_with_input(BenchmarkId::new("heap", size), &payload, |b, payload| {
+            b.iter(|| WebSocketFrame::create_frame_with(0x81, black_box(payload), &mut ThreadRngSource))
+        });
+        group.bench_with_input(BenchmarkId::new("stack", size), &payload, |b, payload| {
+            b.iter(|| WebSocketFrame::create_small_frame(0x81, black_box(payload), &mut ThreadRngSource))
+        });
+    }
+    group.finish();
+}
+
+fn frame_parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_frame");
+    for size in FRAME_SIZES {
+        let frame = WebSocketFrame::create_binary_frame(&random_bytes(size));
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("owned", size), &frame, |b, frame| {
+            b.iter(|| WebSocketFrame::parse_frame(black_box(frame), DEFAULT_MAX_PAYLOAD).unwrap())
+        });
+        // Unmasking in place flips the payload back and forth, so the buffer stays a valid frame
+        let mut input = frame.clone();
+        group.bench_function(BenchmarkId::new("borrowed", size), |b| {
+            b.iter(|| parse_frame_borrowed(black_box(&mut input), DEFAULT_MAX_PAYLOAD).unwrap().payload_len)
+        });
+    }
+    group.finish();
+}
+
+fn rtt_stats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rtt_stats");
+    let mut rng = StdRng::seed_from_u64(2);
+    for count in RTT_COUNTS {
+        let rtts: Vec<f64> = (0..count).map(|_| rng.gen_range(0.1..50.0)).collect();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &rtts, |b, rtts| {
+            b.iter_batched(|| rtts.clone(), RttStats::new, BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, frame_creation, small_frame_creation, frame_parsing, rtt_stats);
+criterion_main!(benches);
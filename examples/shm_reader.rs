@@ -0,0 +1,44 @@
+//! Companion reader for --shm: polls the ring and prints each new RTT
+//!
+//! ```text
+//! cargo run --release --example shm_reader --features shm -- wsb-rtt
+//! ```
+
+#[allow(dead_code)]
+#[path = "../src/shm.rs"]
+mod shm;
+
+use shm::ShmRing;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+fn main() -> std::io::Result<()> {
+    let name = std::env::args().nth(1).unwrap_or_else(|| "wsb-rtt".to_string());
+    let ring = ShmRing::open(&name)?;
+    eprintln!("reading {} ({} slots)", name, ring.slots());
+
+    let mut next = ring.written() + 1;
+    loop {
+        let written = ring.written();
+        if written < next {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        // Skip what has already been overwritten
+        let oldest = written.saturating_sub(ring.slots() as u64) + 1;
+        if next < oldest {
+            eprintln!("lost {} samples", oldest - next);
+            next = oldest;
+        }
+
+        while next <= written {
+            match ring.read(next) {
+                Some(rtt_ms) => println!("{}\t{:.3}", next, rtt_ms),
+                None => eprintln!("lost sample {}", next),
+            }
+            next += 1;
+        }
+    }
+}
@@ -1,380 +1,3298 @@
-use crate::stats::{CpuTime, RttStats};
-use crate::utils::generate_websocket_key;
-use crate::websocket::WebSocketFrame;
+use crate::compression::{
+    negotiate_legacy, DeflateParams, Inflater, PERMESSAGE_DEFLATE, X_WEBKIT_DEFLATE,
+};
+use crate::mix::{MixEntry, PayloadMix};
+use crate::output::{self, StatusThread};
+use crate::pacing::{make_rng, Arrival, Pacer};
+use crate::report::{BenchmarkReport, OutputCompression, OutputFormat, TransferSummary};
+use crate::stats::{
+    co_corrected, CpuTime, DeliveryTracker, MemoryTracker, Phase, PhaseTimes, ReconnectStats,
+    MeasureWindow, OnlineStats, ResponseCadence, RttStats, TcpInfo,
+};
+use crate::trace::{TraceEntry, TraceReplay};
+use crate::utils::{
+    current_cpu, ensure_fd_limit, find_header, generate_websocket_key, interface_socket,
+    parse_extensions, parse_headers, parse_ws_url, plaintext_url, resolve_override, response_status,
+    validate_accept_key, set_dscp, set_quickack, stream_from_fd, validate_upgrade_response, AsTcpStream,
+    RetryIo, TlsRecordWatcher,
+};
+use crate::websocket::{
+    check_text_payload, parse_frame_borrowed, parse_frames, read_frame, FrameBuffer, FrameView, MaskSource,
+    MessageAssembler, PayloadHeader, SeededSource, SmallFrame, ThreadRngSource, WebSocketFrame,
+    CLOSE_NORMAL, DEFAULT_MAX_PAYLOAD, PAYLOAD_HEADER_LEN,
+};
 use crate::QUIET_MODE;
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use native_tls::TlsConnector;
+use rand::{rngs::StdRng, Rng};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::sync::atomic::Ordering;
-use std::time::Instant;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream as TokioTcpStream;
+use tokio::sync::Semaphore;
 use futures_util::{sink::SinkExt, stream::StreamExt};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tokio_tungstenite::{
+    client_async_tls,
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame, Message},
+        client::IntoClientRequest,
+        http::{header::ORIGIN, HeaderValue, StatusCode},
+        Error as WsError,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
 
 const PING_MESSAGE: &[u8] = b"PING";
 const BUFFER_SIZE: usize = 4096;
+const MAX_BACKOFF_SHIFT: u32 = 16;
+/// Upper bound of the full-jitter retry backoff window
+const MAX_BACKOFF_MS: u64 = 30_000;
+const MEMORY_SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+/// Default UDP response timeout when --timeout-ms isn't given
+const UDP_RECV_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the full-duplex receiver waits for an outstanding response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the server's reply during a closing handshake
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Smallest and largest payloads tried by --find-max-frame
+const MIN_PROBE_SIZE: usize = 64;
+/// Stays under tungstenite's default 64 MiB limit on the echoed response
+const MAX_PROBE_SIZE: usize = 32 * 1024 * 1024;
+/// Default wait for a probe's response when --timeout-ms isn't given
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Close code for "message too big" (RFC 6455 §7.4.1)
+const CLOSE_TOO_BIG: u16 = 1009;
+/// Outlier iterations listed individually in the report
+const MAX_OUTLIERS_SHOWN: usize = 20;
+/// Descriptors reserved for stdio, the runtime and output files
+const FD_OVERHEAD: u64 = 64;
+/// Payload sizes of the masking micro-benchmark, 64 B to 1 MiB
+const MASKING_SIZES: [usize; 8] = [64, 256, 1024, 4096, 16384, 65536, 262144, 1048576];
+/// Payload bytes framed per size, enough to average out timer and allocator noise
+const MASKING_BYTES_PER_SIZE: usize = 128 * 1024 * 1024;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TokioTcpStream>>;
 
 /// Benchmark configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BenchmarkConfig {
+    /// Primary target; single-connection benchmarks connect here
     pub host: String,
+    /// All targets; the multi-connection benchmark round-robins across them
+    pub hosts: Vec<String>,
     pub port: u16,
     pub ping_pong_count: u32,
     pub quiet: bool,
+    pub reconnect: bool,
+    pub connect_retries: u32,
+    pub retry_backoff_ms: u64,
+    pub strict: bool,
+    pub track_memory: bool,
+    pub memory_sample_every: u32,
+    pub output: Option<PathBuf>,
+    /// Append to an existing CSV/JSON-lines output
+    pub append_output: bool,
+    pub ramp_secs: f64,
+    pub abort_above_ms: Option<f64>,
+    pub abort_after: u32,
+    pub compress: bool,
+    pub clients: u32,
+    pub timeout_ms: Option<u64>,
+    pub interval_ms: Option<f64>,
+    pub arrival: Arrival,
+    pub seed: Option<u64>,
+    pub report_interval_secs: Option<f64>,
+    pub cpu_affinity: Vec<usize>,
+    /// Size of the generated ping payload (defaults to "PING")
+    pub payload_size: Option<usize>,
+    /// Exact ping payload from --payload-file / --payload-stdin
+    #[serde(skip)]
+    pub payload: Option<Vec<u8>>,
+    pub baseline: Option<PathBuf>,
+    pub regression_threshold_pct: f64,
+    /// Run benchmark 1 over HTTP/2 extended CONNECT
+    pub h2: bool,
+    /// Network interface to bind sockets to (SO_BINDTODEVICE)
+    pub interface: Option<String>,
+    /// WebSocket request path
+    pub path: String,
+    /// Use wss:// (false for plain ws://)
+    pub tls: bool,
+    /// Ping-pongs per client in the multi-connection benchmark (defaults to ping_pong_count)
+    pub per_connection: Option<u32>,
+    /// Send binary (0x2) instead of text (0x1) data frames
+    pub binary: bool,
+    /// Retries of a failed ping on the same connection before it counts as a failure
+    pub ping_retries: u32,
+    /// Also offer x-webkit-deflate with --compress
+    pub legacy_deflate: bool,
+    /// permessage-deflate parameters offered with --compress
+    pub deflate: DeflateParams,
+    /// Payload sizes (bytes) for the full-duplex throughput sweep
+    pub payload_sweep: Vec<usize>,
+    /// Retry over ws:// when the wss:// TLS handshake fails
+    pub auto_scheme: bool,
+    /// Write per-phase timings of the sync benchmark as folded stacks
+    pub folded_output: Option<PathBuf>,
+    /// Interval of protocol keepalive pings in the tungstenite benchmarks
+    pub keepalive_ping_ms: Option<u64>,
+    /// Abort "run all" at the first failing benchmark
+    pub fail_fast: bool,
+    /// Weighted payload size classes picked per iteration (--mix)
+    pub mix: Vec<MixEntry>,
+    /// Messages replayed in order by the tungstenite benchmarks (--trace)
+    #[serde(skip)]
+    pub trace: Vec<TraceEntry>,
+    /// Honor the trace's recorded inter-arrival gaps
+    pub trace_timing: bool,
+    /// DSCP value (0-63) marked on outgoing packets
+    pub dscp: Option<u8>,
+    /// Tokio worker threads (defaults to one per CPU, or per --cpu-affinity core)
+    pub runtime_threads: Option<usize>,
+    /// Single-threaded tokio runtime
+    pub current_thread: bool,
+    /// Fail the TCP/UDP benchmarks on responses that don't match the sent payload's length
+    pub strict_response: bool,
+    /// Multi-connection clients allowed to connect and handshake at the same time
+    pub connect_concurrency: u32,
+    /// Status code of the close frame ending each connection
+    pub close_code: u16,
+    /// Reason text of the close frame ending each connection
+    pub close_reason: String,
+    /// Idle time before TCP keepalive probing starts
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Gap between unanswered TCP keepalive probes
+    pub tcp_keepalive_interval_secs: Option<u64>,
+    /// Unanswered TCP keepalive probes before the connection is dropped
+    pub tcp_keepalive_retries: Option<u32>,
+    /// Box-drawing text, or a Markdown summary table only
+    pub output_format: OutputFormat,
+    /// Full-duplex benchmark sends on the --interval-ms schedule regardless of responses
+    pub open_loop: bool,
+    /// Print the kernel's TCP_INFO for the connection after the sync TLS and TCP benchmarks
+    pub tcp_info: bool,
+    /// Stay idle this long after the handshake, then report the first RTT as cold
+    pub idle_before_secs: Option<f64>,
+    /// Compress the --output file (also inferred from a .gz / .zst extension)
+    pub compress_output: Option<OutputCompression>,
+    /// Only samples completed inside this part of the run go into the final stats
+    pub measure_window: Option<MeasureWindow>,
+    /// Origin header sent with the opening handshake; none by default
+    pub origin: Option<String>,
+    /// Report the spread of gaps between consecutive response arrivals
+    pub response_cadence: bool,
+    /// Limit on TCP connect plus TLS/WebSocket handshake, separate from the per-ping timeout
+    pub connect_timeout_ms: Option<u64>,
+    /// Content of generated payloads (--payload-size or the default PING)
+    pub payload_kind: PayloadKind,
+    /// Keep TCP_QUICKACK armed on the TCP and sync TLS benchmark sockets (Linux)
+    pub quickack: bool,
+    /// Track only running avg/stddev/min/max instead of keeping every RTT sample
+    pub no_percentiles: bool,
+    /// Mask the ping frame once and resend the same bytes every iteration
+    pub static_frame: bool,
+    /// Inclusive source ports, one per multi-connection client
+    pub source_port_range: Option<(u16, u16)>,
+    /// Already-connected TCP socket to run over instead of connecting (Unix)
+    pub fd: Option<i32>,
+    /// Send frames right after connecting, skipping the HTTP upgrade
+    pub no_handshake: bool,
+    /// Largest payload a received frame may declare (or a fragmented message add up to) before it is rejected
+    pub max_frame_size: u64,
 }
 
 impl Default for BenchmarkConfig {
     fn default() -> Self {
         BenchmarkConfig {
             host: "10.25.96.5".to_string(),
+            hosts: vec!["10.25.96.5".to_string()],
             port: 8443,
             ping_pong_count: 30,
             quiet: false,
+            reconnect: false,
+            connect_retries: 0,
+            retry_backoff_ms: 500,
+            strict: false,
+            track_memory: false,
+            memory_sample_every: 100,
+            output: None,
+            append_output: false,
+            ramp_secs: 0.0,
+            abort_above_ms: None,
+            abort_after: 5,
+            compress: false,
+            clients: 50,
+            timeout_ms: None,
+            interval_ms: None,
+            arrival: Arrival::Constant,
+            seed: None,
+            report_interval_secs: None,
+            cpu_affinity: Vec::new(),
+            payload_size: None,
+            payload: None,
+            baseline: None,
+            regression_threshold_pct: 10.0,
+            h2: false,
+            interface: None,
+            path: "/ws".to_string(),
+            tls: true,
+            per_connection: None,
+            binary: false,
+            ping_retries: 0,
+            legacy_deflate: true,
+            deflate: DeflateParams::default(),
+            payload_sweep: Vec::new(),
+            auto_scheme: false,
+            folded_output: None,
+            keepalive_ping_ms: None,
+            fail_fast: false,
+            mix: Vec::new(),
+            trace: Vec::new(),
+            trace_timing: false,
+            dscp: None,
+            runtime_threads: None,
+            current_thread: false,
+            strict_response: false,
+            connect_concurrency: 100,
+            close_code: CLOSE_NORMAL,
+            close_reason: String::new(),
+            tcp_keepalive_secs: None,
+            tcp_keepalive_interval_secs: None,
+            tcp_keepalive_retries: None,
+            output_format: OutputFormat::Text,
+            open_loop: false,
+            tcp_info: false,
+            idle_before_secs: None,
+            compress_output: None,
+            measure_window: None,
+            origin: None,
+            response_cadence: false,
+            connect_timeout_ms: None,
+            payload_kind: PayloadKind::Text,
+            quickack: false,
+            no_percentiles: false,
+            static_frame: false,
+            source_port_range: None,
+            fd: None,
+            no_handshake: false,
+            max_frame_size: DEFAULT_MAX_PAYLOAD,
+        }
+    }
+}
+
+impl BenchmarkConfig {
+    /// Build a ping frame with the configured opcode (text, or binary with --binary)
+    pub fn ping_frame(&self, payload: &[u8]) -> Vec<u8> {
+        self.ping_frame_with(payload, &mut ThreadRngSource)
+    }
+
+    /// Build a ping frame masked with a key from `masks`
+    pub fn ping_frame_with(&self, payload: &[u8], masks: &mut dyn MaskSource) -> Vec<u8> {
+        WebSocketFrame::create_frame_with(self.ping_opcode(), payload, masks)
+    }
+
+    /// Build a ping frame into a reused send buffer
+    pub fn ping_frame_into(&self, frame: &mut Vec<u8>, payload: &[u8], masks: &mut dyn MaskSource) {
+        WebSocketFrame::create_frame_into(frame, self.ping_opcode(), payload, masks);
+    }
+
+    /// Build a small ping frame on the stack; None if the payload is too large for it
+    pub fn small_ping_frame(&self, payload: &[u8], masks: &mut dyn MaskSource) -> Option<SmallFrame> {
+        WebSocketFrame::create_small_frame(self.ping_opcode(), payload, masks)
+    }
+
+    /// Text, or binary with --binary
+    fn ping_opcode(&self) -> u8 {
+        if self.binary {
+            0x82
+        } else {
+            0x81
+        }
+    }
+
+    /// Masking keys for the hand-rolled clients: reproducible with --seed, random otherwise.
+    ///
+    /// `stream` gives each multi-connection client its own key sequence under --seed.
+    pub fn mask_source(&self, stream: u64) -> Box<dyn MaskSource + Send> {
+        match self.seed {
+            // Offset below the pacing (seed), --mix (seed + 1) and backoff (seed + 2 + n) streams
+            Some(seed) => Box::new(SeededSource::new(seed.wrapping_sub(1).wrapping_sub(stream))),
+            None => Box::new(ThreadRngSource),
+        }
+    }
+
+    /// Close frame ending tungstenite connections (--close-code, --close-reason)
+    pub fn close_frame(&self) -> CloseFrame<'static> {
+        CloseFrame {
+            code: CloseCode::from(self.close_code),
+            reason: self.close_reason.clone().into(),
+        }
+    }
+
+    /// Close frame payload for the hand-rolled connections: code, then reason
+    fn close_payload(&self) -> Vec<u8> {
+        let mut payload = self.close_code.to_be_bytes().to_vec();
+        payload.extend_from_slice(self.close_reason.as_bytes());
+        payload
+    }
+
+    /// Worker threads of the multi-thread runtime; None for the current-thread runtime
+    pub fn runtime_workers(&self) -> Option<usize> {
+        if self.current_thread {
+            return None;
+        }
+        let default = match self.cpu_affinity.len() {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            cores => cores,
+        };
+        Some(self.runtime_threads.unwrap_or(default))
+    }
+
+    /// Human-readable runtime configuration for reports
+    pub fn runtime_description(&self) -> String {
+        match self.runtime_workers() {
+            Some(workers) => format!("tokio multi-thread, {} workers", workers),
+            None => "tokio current-thread".to_string(),
+        }
+    }
+
+    /// Ping-pongs each multi-connection client performs
+    pub fn per_connection(&self) -> u32 {
+        self.per_connection.unwrap_or(self.ping_pong_count)
+    }
+
+    /// WebSocket URL for the given host using the configured scheme, port and path
+    pub fn ws_url(&self, host: &str) -> String {
+        let scheme = if self.tls { "wss" } else { "ws" };
+        if host.contains(':') {
+            format!("{}://[{}]:{}{}", scheme, host, self.port, self.path)
+        } else {
+            format!("{}://{}:{}{}", scheme, host, self.port, self.path)
+        }
+    }
+
+    /// Payload sent with every ping
+    pub fn ping_payload(&self) -> Vec<u8> {
+        // Offset past the --mix (seed + 1) and retry backoff (seed + 2 + client) streams
+        let seed = self.seed.map(|seed| seed.wrapping_sub(2));
+        match (&self.payload, self.payload_size) {
+            (Some(payload), _) => payload.clone(),
+            (None, size) => self.payload_kind.generate(size.unwrap_or(PING_MESSAGE.len()), seed),
+        }
+    }
+}
+
+/// Content of generated payloads, which decides how well permessage-deflate compresses them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadKind {
+    /// Repeated "PING" text (compresses well)
+    #[default]
+    Text,
+    /// Zero bytes (best case for compression)
+    Zeros,
+    /// Random bytes (incompressible); needs --binary
+    Random,
+}
+
+impl PayloadKind {
+    /// Generate `size` bytes of this kind; random payloads repeat under the same seed
+    pub fn generate(self, size: usize, seed: Option<u64>) -> Vec<u8> {
+        match self {
+            PayloadKind::Text => PING_MESSAGE.iter().copied().cycle().take(size).collect(),
+            PayloadKind::Zeros => vec![0; size],
+            PayloadKind::Random => {
+                let mut payload = vec![0; size];
+                make_rng(seed).fill(payload.as_mut_slice());
+                payload
+            }
         }
     }
 }
 
 /// Print benchmark header
 pub fn print_benchmark_header(name: &str) {
-    println!("┌──────────────────────────────────────────────────────────────┐");
-    println!("│ Benchmark: {:50}│", name);
-    println!("├──────────────────────────────────────────────────────────────┤");
+    outln!("┌──────────────────────────────────────────────────────────────┐");
+    outln!("│ Benchmark: {:50}│", name);
+    outln!("├──────────────────────────────────────────────────────────────┤");
+}
+
+/// Print which data frame opcode the pings use
+fn print_frame_opcode(config: &BenchmarkConfig) {
+    if config.binary {
+        outln!("│ Frames: binary (opcode 0x2)                                  │");
+    } else {
+        outln!("│ Frames: text (opcode 0x1)                                    │");
+    }
 }
 
 /// Print benchmark result (only if logging is enabled)
 pub fn print_benchmark_result(iteration: u32, rtt_ms: f64) {
     if !QUIET_MODE.load(Ordering::SeqCst) {
-        println!("│ Ping-Pong {:>6} RTT: {:>10.3} ms                          │", iteration, rtt_ms);
+        outln!("│ Ping-Pong {:>6} RTT: {:>10.3} ms                          │", iteration, rtt_ms);
     }
 }
 
-/// Benchmark 1: Basic OpenSSL TLS 1.3 with blocking I/O
-pub async fn run_openssl_benchmark(config: &BenchmarkConfig) -> Result<()> {
-    print_benchmark_header("Rust socket + Native TLS (blocking I/O)");
+/// Print how much of the requested run produced samples, so partial runs aren't mistaken for full ones
+fn print_completed(completed: u64, requested: u64, failed: u64, timed_out: u64) {
+    outln!(
+        "│ {:61}│",
+        format!(
+            "Completed: {}/{} iterations ({} failed, {} timed out)",
+            completed, requested, failed, timed_out
+        )
+    );
+}
 
-    let mut cpu = CpuTime::new();
-    cpu.start();
+/// Whether an error came from a tokio timeout, possibly under added context
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<tokio::time::error::Elapsed>().is_some()
+}
 
-    let url = format!("wss://{}:{}/ws", config.host, config.port);
-    let (ws_stream, _) = connect_async(&url).await.context("Failed to connect")?;
+/// Whether a connect_ws attempt ran out of its --connect-timeout
+fn is_connect_timeout(e: &WsError) -> bool {
+    matches!(e, WsError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut)
+}
 
-    let mut ws_stream = ws_stream;
+/// Per-iteration bookkeeping shared by the single-connection benchmark loops
+struct RunMonitor {
+    requested: u32,
+    completed: u32,
+    failed: u32,
+    timed_out: u32,
+    memory: Option<MemoryTracker>,
+    memory_sample_every: u32,
+    abort_above_ms: Option<f64>,
+    abort_after: u32,
+    consecutive_slow: u32,
+    aborted: bool,
+    pacer: Option<Pacer>,
+    status: Option<StatusThread>,
+    send_lateness: Option<Duration>,
+    paced_rtts: Vec<f64>,
+    paced_lateness: Vec<f64>,
+    measure_window: Option<MeasureWindow>,
+    first_send: Option<Instant>,
+    sample_offsets: Vec<f64>,
+    window_summary: Option<String>,
+    cadence: Option<ResponseCadence>,
+    interval_ms: Option<f64>,
+    online: Option<OnlineStats>,
+}
 
-    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
+impl RunMonitor {
+    fn new(config: &BenchmarkConfig) -> Self {
+        output::start_progress(config.ping_pong_count as u64);
+
+        RunMonitor {
+            requested: config.ping_pong_count,
+            completed: 0,
+            failed: 0,
+            timed_out: 0,
+            memory: config.track_memory.then(MemoryTracker::start),
+            memory_sample_every: config.memory_sample_every.max(1),
+            abort_above_ms: config.abort_above_ms,
+            abort_after: config.abort_after.max(1),
+            consecutive_slow: 0,
+            aborted: false,
+            pacer: config
+                .interval_ms
+                .map(|interval| Pacer::new(config.arrival, interval, config.seed)),
+            status: config
+                .report_interval_secs
+                .map(|secs| StatusThread::start(Duration::from_secs_f64(secs))),
+            send_lateness: None,
+            paced_rtts: Vec::new(),
+            paced_lateness: Vec::new(),
+            measure_window: config.measure_window,
+            first_send: None,
+            sample_offsets: Vec::new(),
+            window_summary: None,
+            cadence: config.response_cadence.then(ResponseCadence::default),
+            interval_ms: config.interval_ms,
+            online: config.no_percentiles.then(OnlineStats::default),
+        }
+    }
 
-    for i in 0..config.ping_pong_count {
-        let start = Instant::now();
+    /// Buffer for the run's RTT samples; stays empty when only running moments are kept
+    fn sample_buffer(&self) -> Vec<f64> {
+        match self.online {
+            Some(_) => Vec::new(),
+            None => Vec::with_capacity(self.requested as usize),
+        }
+    }
+
+    /// Keep an RTT sample, or just fold it into the running moments with --no-percentiles
+    fn record_rtt(&mut self, rtts: &mut Vec<f64>, rtt_ms: f64) {
+        match self.online.as_mut() {
+            Some(online) => online.record(rtt_ms),
+            None => rtts.push(rtt_ms),
+        }
+    }
+
+    /// Final statistics of the run from the kept samples, or from the running moments
+    fn rtt_stats(&mut self, rtts: Vec<f64>) -> RttStats {
+        match &self.online {
+            Some(online) => RttStats::from_online(online),
+            None => RttStats::new(self.in_window(rtts)),
+        }
+    }
+
+    /// Delay before the next send when pacing is enabled
+    fn next_send_delay(&mut self) -> Option<Duration> {
+        let pacer = self.pacer.as_mut()?;
+        let delay = pacer.next_delay();
+        // How far behind schedule this send is, e.g. after waiting out a stalled response
+        self.send_lateness = pacer
+            .scheduled()
+            .map(|scheduled| Instant::now().saturating_duration_since(scheduled));
+        Some(delay)
+    }
+
+    /// Called after every completed iteration (1-based); returns true if the run should stop
+    fn on_iteration(&mut self, iteration: u32, rtt_ms: f64) -> bool {
+        output::advance_progress();
+        self.completed += 1;
+
+        if let Some(cadence) = self.cadence.as_mut() {
+            cadence.record(Instant::now());
+        }
+
+        if self.measure_window.is_some() {
+            // The run starts with the first send, not when the monitor was created
+            let now = Instant::now();
+            let first_send = *self.first_send.get_or_insert_with(|| {
+                now.checked_sub(Duration::from_secs_f64(rtt_ms / 1000.0)).unwrap_or(now)
+            });
+            self.sample_offsets.push((now - first_send).as_secs_f64());
+        }
+
+        // The corrected percentiles need every sample
+        if let Some(lateness) = self.send_lateness.take().filter(|_| self.online.is_none()) {
+            self.paced_rtts.push(rtt_ms);
+            self.paced_lateness.push(lateness.as_secs_f64() * 1000.0);
+        }
+
+        if let Some(memory) = self.memory.as_mut() {
+            if iteration.is_multiple_of(self.memory_sample_every) {
+                memory.sample();
+            }
+        }
+
+        if let Some(threshold) = self.abort_above_ms {
+            if rtt_ms > threshold {
+                self.consecutive_slow += 1;
+            } else {
+                self.consecutive_slow = 0;
+            }
+
+            if self.consecutive_slow >= self.abort_after {
+                tracing::error!(
+                    iteration,
+                    consecutive = self.consecutive_slow,
+                    threshold_ms = threshold,
+                    "aborting: sustained high latency"
+                );
+                self.aborted = true;
+            }
+        }
+
+        if self.status.is_some() {
+            output::record_iteration(rtt_ms);
+        }
+
+        #[cfg(all(feature = "shm", unix))]
+        crate::shm::publish(rtt_ms);
+
+        self.aborted
+    }
+
+    /// Called for an iteration that produced no RTT sample and didn't end the run
+    fn on_failure(&mut self, timed_out: bool) {
+        output::advance_progress();
+        self.send_lateness = None;
+        if timed_out {
+            self.timed_out += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    /// Keep the samples that completed inside --measure-window, in iteration order.
+    ///
+    /// `rtts` must hold one sample per `on_iteration` call; without a window it's returned as is.
+    fn in_window(&mut self, rtts: Vec<f64>) -> Vec<f64> {
+        let Some(window) = self.measure_window else {
+            return rtts;
+        };
+
+        let total = self.sample_offsets.last().copied().unwrap_or_default();
+        let (start, end) = window.resolve(total);
+        let kept: Vec<f64> = rtts
+            .iter()
+            .zip(&self.sample_offsets)
+            .filter(|(_, &offset)| offset >= start && offset <= end)
+            .map(|(&rtt, _)| rtt)
+            .collect();
+
+        self.window_summary = Some(format!(
+            "Measure window {:.1}s-{:.1}s of {:.1}s: {}/{} samples",
+            start,
+            end,
+            total,
+            kept.len(),
+            rtts.len()
+        ));
+        kept
+    }
+
+    /// Print the optional summary sections collected during the run
+    fn print_summary(&mut self) {
+        output::finish_progress();
+        // Stop the status thread so its lines don't land inside the summary
+        self.status = None;
+
+        print_completed(
+            self.completed as u64,
+            self.requested as u64,
+            self.failed as u64,
+            self.timed_out as u64,
+        );
+
+        if let Some(summary) = &self.window_summary {
+            outln!("│ {:61}│", summary);
+        }
+        if let Some(pacer) = &self.pacer {
+            pacer.print();
+        }
+        if let Some(cadence) = &self.cadence {
+            cadence.print(self.interval_ms);
+        }
+        if let Some(online) = &self.online {
+            online.print();
+        }
+        if !self.paced_rtts.is_empty() {
+            let corrected = co_corrected(&self.paced_rtts, &self.paced_lateness);
+            RttStats::print_corrected(
+                &RttStats::new(self.paced_rtts.clone()),
+                &RttStats::new(corrected),
+            );
+        }
+        if let Some(memory) = self.memory.as_mut() {
+            memory.sample();
+            memory.print();
+        }
+    }
+
+    /// Fail the run if the latency circuit breaker tripped
+    fn check_aborted(&self) -> Result<()> {
+        if self.aborted {
+            return Err(anyhow::anyhow!(
+                "Benchmark aborted: {} consecutive RTTs above {:.3} ms",
+                self.consecutive_slow,
+                self.abort_above_ms.unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RunMonitor {
+    // Clear the progress bar even when the run ends early with an error
+    fn drop(&mut self) {
+        output::finish_progress();
+    }
+}
+
+/// Parse a response frame, treating protocol violations as errors in strict mode.
+///
+/// Returns the parsed frame, or None if it couldn't be parsed in lenient mode.
+fn check_response_frame<'a>(
+    data: &'a mut [u8],
+    config: &BenchmarkConfig,
+    compressed: bool,
+) -> Result<Option<FrameView<'a>>> {
+    let frame = parse_frame_borrowed(data, config.max_frame_size)
+        .and_then(|frame| frame.validate_with(compressed).map(|()| frame));
+
+    match frame {
+        Ok(frame) => Ok(Some(frame)),
+        Err(e) if config.strict => Err(anyhow::anyhow!("Protocol violation: {}", e)),
+        Err(e) => {
+            tracing::warn!(error = %e, "protocol violation");
+            Ok(None)
+        }
+    }
+}
+
+/// UTF-8 check for inflated or reassembled text, with the same --strict handling as check_response_frame
+fn check_message_text(opcode: u8, payload: &[u8], config: &BenchmarkConfig) -> Result<()> {
+    match check_text_payload(opcode, payload) {
+        Ok(()) => Ok(()),
+        Err(e) if config.strict => Err(anyhow::anyhow!("Protocol violation: {}", e)),
+        Err(e) => {
+            tracing::warn!(error = %e, "protocol violation");
+            Ok(())
+        }
+    }
+}
+
+/// Validate the server's Sec-WebSocket-Extensions response and set up decompression
+fn negotiate_compression(response: &str, config: &BenchmarkConfig) -> Result<Option<Inflater>> {
+    let extensions = find_header(response, "Sec-WebSocket-Extensions")
+        .map(parse_extensions)
+        .unwrap_or_default();
+
+    let Some(extension) = extensions.first() else {
+        if config.compress {
+            outln!("│ Compression: declined by server                              │");
+        }
+        return Ok(None);
+    };
+
+    let legacy = config.legacy_deflate && extension.name == X_WEBKIT_DEFLATE;
+    if !config.compress || extensions.len() > 1 || (extension.name != PERMESSAGE_DEFLATE && !legacy) {
+        return Err(anyhow::anyhow!(
+            "Server negotiated an extension we didn't offer: {}",
+            extension.name
+        ));
+    }
+
+    // Older servers only accept the pre-standard extension; inflate it the same way
+    if legacy {
+        let no_context_takeover = negotiate_legacy(extension)
+            .map_err(|e| anyhow::anyhow!("Invalid x-webkit-deflate response: {}", e))?;
+        outln!("│ Compression: {:48}│", format!("{} (legacy fallback)", X_WEBKIT_DEFLATE));
+        return Ok(Some(Inflater::new(no_context_takeover)));
+    }
+
+    let negotiated = config
+        .deflate
+        .negotiate(extension)
+        .map_err(|e| anyhow::anyhow!("Invalid permessage-deflate response: {}", e))?;
+    outln!("│ Compression: {:48}│", PERMESSAGE_DEFLATE);
+    outln!("│ Deflate params: {:45}│", negotiated.describe());
+
+    // Pings go out uncompressed, so only the server's parameters shape our side: its
+    // window always fits the inflater's 15-bit one, and no_context_takeover resets it per message
+    Ok(Some(Inflater::new(negotiated.server_no_context_takeover)))
+}
+
+/// TLS connector for benchmark servers (self-signed certificates are accepted)
+fn tls_connector() -> Result<TlsConnector> {
+    TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .context("Failed to create TLS connector")
+}
+
+/// Resolve the first address for a host and port, honoring --resolve overrides
+async fn resolve(host: &str, port: u16) -> std::io::Result<std::net::SocketAddr> {
+    if let Some(ip) = resolve_override(host) {
+        return Ok((ip, port).into());
+    }
+    tokio::net::lookup_host((host, port)).await?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("No address for {}", host))
+    })
+}
+
+/// Blocking counterpart of `resolve`
+fn resolve_sync(host: &str, port: u16) -> std::io::Result<std::net::SocketAddr> {
+    if let Some(ip) = resolve_override(host) {
+        return Ok((ip, port).into());
+    }
+    (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("No address for {}", host))
+    })
+}
+
+/// Socket options applied before connecting (--interface, --dscp) and the connect timeout
+#[derive(Debug, Clone, Default)]
+struct SocketOptions {
+    interface: Option<String>,
+    dscp: Option<u8>,
+    keepalive: Option<socket2::TcpKeepalive>,
+    connect_timeout: Option<Duration>,
+    /// Local port to bind TCP sockets to (--source-port-range)
+    source_port: Option<u16>,
+}
+
+impl SocketOptions {
+    fn from_config(config: &BenchmarkConfig) -> Self {
+        SocketOptions {
+            interface: config.interface.clone(),
+            dscp: config.dscp,
+            keepalive: tcp_keepalive(config),
+            connect_timeout: config.connect_timeout_ms.map(Duration::from_millis),
+            source_port: None,
+        }
+    }
+
+    /// Run a connection-establishment future under the connect timeout, if any
+    async fn within_timeout<T, E, F>(&self, connect: F) -> Result<T, E>
+    where
+        F: std::future::Future<Output = Result<T, E>>,
+        E: From<std::io::Error>,
+    {
+        match self.connect_timeout {
+            Some(limit) => tokio::time::timeout(limit, connect).await.unwrap_or_else(|_| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("connect timed out after {} ms", limit.as_millis()),
+                )
+                .into())
+            }),
+            None => connect.await,
+        }
+    }
+
+    /// Create an unconnected socket for `addr` with these options applied.
+    ///
+    /// DSCP marking is best effort: where the platform rejects it the
+    /// benchmark continues with unmarked traffic.
+    fn socket(&self, addr: std::net::SocketAddr, ty: socket2::Type) -> std::io::Result<socket2::Socket> {
+        let socket = match &self.interface {
+            Some(interface) => interface_socket(addr, interface, ty)?,
+            None => socket2::Socket::new(socket2::Domain::for_address(addr), ty, None)?,
+        };
+        if let Some(dscp) = self.dscp {
+            if let Err(e) = set_dscp(&socket, addr, dscp) {
+                tracing::warn!(dscp, error = %e, "failed to set DSCP, sending unmarked");
+            }
+        }
+        if let (Some(keepalive), socket2::Type::STREAM) = (&self.keepalive, ty) {
+            socket.set_tcp_keepalive(keepalive)?;
+        }
+        if let (Some(port), socket2::Type::STREAM) = (self.source_port, ty) {
+            // A previous run's connection on this port may still be in TIME_WAIT
+            socket.set_reuse_address(true)?;
+            let local: std::net::SocketAddr = if addr.is_ipv4() {
+                ([0, 0, 0, 0], port).into()
+            } else {
+                (std::net::Ipv6Addr::UNSPECIFIED, port).into()
+            };
+            socket.bind(&local.into()).map_err(|e| {
+                std::io::Error::new(e.kind(), format!("Failed to bind source port {}: {}", port, e))
+            })?;
+        }
+        Ok(socket)
+    }
+}
+
+/// TCP keepalive settings from --tcp-keepalive-*, if enabled
+fn tcp_keepalive(config: &BenchmarkConfig) -> Option<socket2::TcpKeepalive> {
+    let keepalive = socket2::TcpKeepalive::new().with_time(Duration::from_secs(config.tcp_keepalive_secs?));
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd", windows))]
+    let keepalive = match config.tcp_keepalive_interval_secs {
+        Some(secs) => keepalive.with_interval(Duration::from_secs(secs)),
+        None => keepalive,
+    };
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd"))]
+    let keepalive = match config.tcp_keepalive_retries {
+        Some(retries) => keepalive.with_retries(retries),
+        None => keepalive,
+    };
+
+    Some(keepalive)
+}
+
+/// Open a TCP connection with the given socket options
+async fn connect_tcp(host: &str, port: u16, options: &SocketOptions) -> std::io::Result<TokioTcpStream> {
+    let addr = resolve(host, port).await?;
+    let socket = options.socket(addr, socket2::Type::STREAM)?;
+    socket.set_nonblocking(true)?;
+    let stream: TcpStream = socket.into();
+    options
+        .within_timeout(tokio::net::TcpSocket::from_std_stream(stream).connect(addr))
+        .await
+}
+
+/// Blocking TCP connect honoring --interface, --dscp and --resolve, or the --fd socket
+fn connect_tcp_sync(config: &BenchmarkConfig) -> std::io::Result<TcpStream> {
+    if let Some(fd) = config.fd {
+        return stream_from_fd(fd);
+    }
+    let addr = resolve_sync(&config.host, config.port)?;
+    let options = SocketOptions::from_config(config);
+    let socket = options.socket(addr, socket2::Type::STREAM)?;
+    match options.connect_timeout {
+        Some(limit) => socket.connect_timeout(&addr.into(), limit)?,
+        None => socket.connect(&addr.into())?,
+    }
+    Ok(socket.into())
+}
+
+/// Open a WebSocket connection.
+///
+/// TCP is connected here (honoring the socket options and --resolve) so the
+/// URL host is only used for the Host header and SNI.
+async fn connect_ws(
+    url: &str,
+    host: &str,
+    port: u16,
+    options: &SocketOptions,
+    origin: Option<&str>,
+) -> Result<WsStream, WsError> {
+    let mut request = url.into_client_request()?;
+    if let Some(origin) = origin {
+        request.headers_mut().insert(ORIGIN, HeaderValue::from_str(origin)?);
+    }
+
+    // One limit covers TCP connect plus the TLS and WebSocket handshakes
+    let untimed = SocketOptions {
+        connect_timeout: None,
+        ..options.clone()
+    };
+    options
+        .within_timeout(async {
+            let tcp_stream = connect_tcp(host, port, &untimed).await?;
+            client_async_tls(request, tcp_stream).await.map(|(ws_stream, _)| ws_stream)
+        })
+        .await
+}
+
+/// Whether the server refused the handshake with 403, which retrying won't change
+fn is_forbidden(e: &WsError) -> bool {
+    matches!(e, WsError::Http(response) if response.status() == StatusCode::FORBIDDEN)
+}
+
+/// Handshake error for a 403 response, which servers use to refuse an Origin
+fn forbidden_error(config: &BenchmarkConfig) -> anyhow::Error {
+    match &config.origin {
+        Some(origin) => anyhow::anyhow!(
+            "WebSocket handshake rejected with 403 Forbidden: the server refused Origin \"{}\"",
+            origin
+        ),
+        None => anyhow::anyhow!(
+            "WebSocket handshake rejected with 403 Forbidden; if the server checks origins, pass --origin"
+        ),
+    }
+}
+
+/// Origin header line for a hand-written handshake request, or nothing
+fn origin_header(config: &BenchmarkConfig) -> String {
+    config
+        .origin
+        .as_ref()
+        .map(|origin| format!("Origin: {}\r\n", origin))
+        .unwrap_or_default()
+}
+
+/// How hard connecting was: retries, attempts and time between the first failure and the outcome
+#[derive(Debug, Default, Clone, Copy)]
+struct RetryStats {
+    retries: u32,
+    max_attempts: u32,
+    time: Duration,
+}
+
+impl RetryStats {
+    /// Fold one client's retry stats into the totals
+    fn merge(&mut self, other: RetryStats) {
+        self.retries += other.retries;
+        self.max_attempts = self.max_attempts.max(other.max_attempts);
+        self.time += other.time;
+    }
+}
+
+/// AWS "full jitter" connect-retry backoff: sleep a random time in [0, min(cap, base × 2^attempt)].
+///
+/// Randomizing the whole window keeps many clients failing together from retrying in lockstep.
+struct Backoff {
+    base_ms: u64,
+    max_retries: u32,
+    rng: StdRng,
+    attempt: u32,
+    retrying_since: Option<Instant>,
+}
+
+impl Backoff {
+    /// `stream` gives each client its own RNG sequence under --seed
+    fn new(config: &BenchmarkConfig, stream: u64) -> Self {
+        // Offset past the pacing (seed) and --mix (seed + 1) streams
+        let seed = config.seed.map(|seed| seed.wrapping_add(2).wrapping_add(stream));
+        Backoff {
+            base_ms: config.retry_backoff_ms,
+            max_retries: config.connect_retries,
+            rng: make_rng(seed),
+            attempt: 0,
+            retrying_since: None,
+        }
+    }
+
+    /// Delay before the next attempt, or None once the retries are used up
+    fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt >= self.max_retries {
+            return None;
+        }
+        self.retrying_since.get_or_insert_with(Instant::now);
+
+        let window_ms = self
+            .base_ms
+            .saturating_mul(1u64 << self.attempt.min(MAX_BACKOFF_SHIFT))
+            .min(MAX_BACKOFF_MS);
+        self.attempt += 1;
+        Some(Duration::from_millis(self.rng.gen_range(0..=window_ms)))
+    }
+
+    fn stats(&self) -> RetryStats {
+        RetryStats {
+            retries: self.attempt,
+            max_attempts: self.attempt + 1,
+            time: self.retrying_since.map_or(Duration::ZERO, |since| since.elapsed()),
+        }
+    }
+}
+
+/// Connect to the WebSocket server, retrying with full-jitter backoff
+#[tracing::instrument(name = "connect", skip(config))]
+async fn connect_with_retries(url: &str, config: &BenchmarkConfig) -> Result<WsStream> {
+    let mut backoff = Backoff::new(config, 0);
+    loop {
+        let options = SocketOptions::from_config(config);
+        match connect_ws(url, &config.host, config.port, &options, config.origin.as_deref()).await {
+            Ok(ws_stream) => {
+                tracing::info!(attempts = backoff.stats().max_attempts, "connected");
+                return Ok(ws_stream);
+            }
+            // Retrying won't change the server's mind
+            Err(e) if is_forbidden(&e) => return Err(forbidden_error(config)),
+            // A TLS failure usually means a plaintext server; retrying over TLS won't help
+            Err(WsError::Tls(e)) if url.starts_with("wss://") => {
+                if !config.auto_scheme {
+                    return Err(anyhow::anyhow!(
+                        "TLS handshake failed ({}); if the server is plaintext, retry with --no-tls or --auto-scheme",
+                        e
+                    ));
+                }
+
+                let plain_url = plaintext_url(url).map_err(anyhow::Error::msg)?;
+                outln!("│ Notice: TLS handshake failed, falling back to {}", plain_url);
+                tracing::warn!(error = %e, "TLS handshake failed, falling back to plaintext");
+                return connect_ws(&plain_url, &config.host, config.port, &options, config.origin.as_deref())
+                    .await
+                    .context("Failed to connect over ws:// fallback");
+            }
+            Err(e) => match backoff.next_delay() {
+                Some(delay) => {
+                    let backoff_ms = delay.as_millis() as u64;
+                    tracing::warn!(attempt = backoff.attempt, error = %e, backoff_ms, "connect failed, retrying");
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(e).context("Failed to connect"),
+            },
+        }
+    }
+}
 
-        // Send PING
-        let ping_frame = WebSocketFrame::create_text_frame(PING_MESSAGE);
+/// Send one PING with `payload` over the WebSocket stream and wait for the response
+async fn ping_pong(ws_stream: &mut WsStream, config: &BenchmarkConfig, payload: &[u8]) -> Result<Message> {
+    let exchange = async {
+        let ping_frame = config.ping_frame(payload);
         ws_stream
             .send(Message::Binary(ping_frame))
             .await
             .context("Failed to send PING")?;
 
-        // Receive PONG
-        let msg = ws_stream
-            .next()
-            .await
-            .context("Connection closed")?
-            .context("Failed to receive PONG")?;
+        loop {
+            let msg = ws_stream
+                .next()
+                .await
+                .context("Connection closed")?
+                .context("Failed to receive PONG")?;
+
+            // Control frames (e.g. keepalive pongs) aren't responses to the data ping
+            if !matches!(msg, Message::Ping(_) | Message::Pong(_)) {
+                break Ok(msg);
+            }
+        }
+    };
+
+    match config.timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), exchange)
+            .await
+            .context("Timed out waiting for PONG")?,
+        None => exchange.await,
+    }
+}
+
+/// Sends protocol Ping control frames at a fixed interval, separate from the data pings
+struct Keepalive {
+    interval: Duration,
+    next_due: Instant,
+    sent: u64,
+}
+
+impl Keepalive {
+    fn new(config: &BenchmarkConfig) -> Option<Self> {
+        let interval = Duration::from_millis(config.keepalive_ping_ms?);
+        Some(Keepalive {
+            interval,
+            next_due: Instant::now() + interval,
+            sent: 0,
+        })
+    }
+
+    /// Send a keepalive ping if one is due
+    async fn send_due(&mut self, ws_stream: &mut WsStream) -> Result<()> {
+        if Instant::now() >= self.next_due {
+            ws_stream
+                .send(Message::Ping(Vec::new()))
+                .await
+                .context("Failed to send keepalive ping")?;
+            self.sent += 1;
+            self.next_due = Instant::now() + self.interval;
+        }
+        Ok(())
+    }
+
+    /// Wait out a pacing delay, sending keepalive pings whenever they fall due
+    async fn idle(&mut self, ws_stream: &mut WsStream, delay: Duration) -> Result<()> {
+        let deadline = Instant::now() + delay;
+        loop {
+            self.send_due(ws_stream).await?;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            tokio::time::sleep(deadline.min(self.next_due) - now).await;
+        }
+    }
+}
+
+/// Checks raw TCP/UDP echo responses: a response of the wrong length is never recorded as an RTT
+#[derive(Default)]
+struct ResponseCheck {
+    mismatched: u32,
+}
+
+impl ResponseCheck {
+    /// Whether `received` bytes are a complete echo of `expected` bytes
+    fn accept(&mut self, iteration: u32, received: usize, expected: usize) -> bool {
+        if received == expected {
+            return true;
+        }
+        self.mismatched += 1;
+        tracing::warn!(iteration, received, expected, "response length mismatch");
+        false
+    }
+
+    fn print(&self) {
+        if self.mismatched > 0 {
+            outln!("│ Mismatched responses: {:6} (not counted as RTTs)           │", self.mismatched);
+        }
+    }
+
+    /// With --strict-response, any mismatched response fails the benchmark
+    fn check(&self, config: &BenchmarkConfig) -> Result<()> {
+        if config.strict_response && self.mismatched > 0 {
+            anyhow::bail!(
+                "{} response(s) didn't match the expected length (--strict-response)",
+                self.mismatched
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Perform the WebSocket closing handshake; failures are logged, not fatal.
+///
+/// Returns the status code of the server's Close reply, if it sent one with a code.
+async fn close_ws(ws_stream: &mut WsStream, frame: CloseFrame<'static>) -> Option<u16> {
+    if let Err(e) = ws_stream.close(Some(frame)).await {
+        tracing::warn!(error = %e, "failed to send close frame");
+        return None;
+    }
+
+    // Wait for the server's Close reply so neither side is left in CLOSE_WAIT
+    let drain = async {
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            if let Message::Close(reply) = msg {
+                return reply.map(|reply| u16::from(reply.code));
+            }
+        }
+        None
+    };
+    match tokio::time::timeout(CLOSE_TIMEOUT, drain).await {
+        Ok(code) => code,
+        Err(_) => {
+            tracing::warn!("timed out waiting for close reply");
+            None
+        }
+    }
+}
+
+/// Print the close code sent and the one the server echoed back
+fn print_close_reply(sent: u16, reply: Option<u16>) {
+    let reply = reply.map_or_else(|| "none".to_string(), |code| code.to_string());
+    outln!("│ {:61}│", format!("Close code: sent {}, server replied {}", sent, reply));
+}
+
+/// Write a frame and read the response frame, timing the write, wait and read phases
+fn timed_exchange<S: Read + Write>(
+    stream: &mut S,
+    buffer: &mut FrameBuffer,
+    frame: &[u8],
+    phases: &mut PhaseTimes,
+) -> std::io::Result<Vec<u8>> {
+    let mut stream = RetryIo(stream);
+    let write_start = Instant::now();
+    stream.write_all(frame)?;
+    stream.flush()?;
+    phases.add(Phase::Write, write_start.elapsed());
+
+    // The first bytes only arrive once the server has responded
+    let wait_start = Instant::now();
+    if buffer.pending().is_empty() {
+        buffer.fill(&mut stream)?;
+    }
+    phases.add(Phase::Wait, wait_start.elapsed());
+
+    let read_start = Instant::now();
+    let response = buffer.read_frame(&mut stream)?;
+    phases.add(Phase::Read, read_start.elapsed());
+
+    Ok(response)
+}
+
+/// With --no-handshake the first reply must be a frame; an HTTP status line means
+/// the server still expects the upgrade request
+fn reject_http_reply(received: &[u8]) -> Result<()> {
+    if !received.starts_with(b"HTTP/") {
+        return Ok(());
+    }
+
+    let reply = String::from_utf8_lossy(received);
+    let status_line = reply.lines().next().unwrap_or_default();
+    Err(anyhow::anyhow!(
+        "Server answered the first frame with \"{}\": it expects a WebSocket upgrade, drop --no-handshake",
+        status_line
+    ))
+}
+
+/// List outlier iterations and whether each coincided with a TLS control event.
+///
+/// `tls_events` holds (iteration index, seconds into the run) pairs.
+fn print_outliers(stats: &RttStats, tls_events: &[(usize, f64)]) {
+    let outliers = stats.outliers();
+    if outliers.is_empty() && tls_events.is_empty() {
+        return;
+    }
+
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│ RTT outliers: {:6} | TLS control events: {:6}            │", outliers.len(), tls_events.len());
+    for &index in outliers.iter().take(MAX_OUTLIERS_SHOWN) {
+        let tls_event = tls_events.iter().find(|(event_index, _)| *event_index == index);
+        let note = match tls_event {
+            Some((_, at_secs)) => format!("TLS event at {:.3}s", at_secs),
+            None => "no TLS event".to_string(),
+        };
+        outln!("│   Iteration {:>8}: {:9.3} ms ({})", index + 1, stats.rtts[index], note);
+    }
+    if outliers.len() > MAX_OUTLIERS_SHOWN {
+        outln!("│   ... {} more", outliers.len() - MAX_OUTLIERS_SHOWN);
+    }
+}
+
+/// Compare the first RTT after --idle-before (cold) with the rest of the run (warm)
+fn print_cold_warm(rtts: &[f64], idle_secs: f64) {
+    let Some((&cold, warm)) = rtts.split_first() else {
+        return;
+    };
+
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│ {:61}│", format!("Cold RTT after {:.1}s idle: {:.3} ms", idle_secs, cold));
+    if !warm.is_empty() {
+        let warm_avg = warm.iter().sum::<f64>() / warm.len() as f64;
+        outln!(
+            "│ {:61}│",
+            format!("Warm avg RTT: {:.3} ms (cold {:+.3} ms)", warm_avg, cold - warm_avg)
+        );
+    }
+}
+
+/// Send a Close frame and shut down a blocking TLS connection; failures are logged
+fn close_tls_sync<S: Read + Write + AsTcpStream>(
+    tls_stream: &mut native_tls::TlsStream<S>,
+    config: &BenchmarkConfig,
+) -> Option<u16> {
+    let mut reply_code = None;
+    let close_frame = WebSocketFrame::create_frame(0x88, &config.close_payload());
+    if let Err(e) = tls_stream.write_all(&close_frame).and_then(|()| tls_stream.flush()) {
+        tracing::warn!(error = %e, "failed to send close frame");
+    } else {
+        let _ = tls_stream.get_ref().tcp().set_read_timeout(Some(CLOSE_TIMEOUT));
+        let reply = read_frame(tls_stream, config.max_frame_size)
+            .and_then(|data| WebSocketFrame::parse_frame(&data, config.max_frame_size));
+        match reply {
+            Ok(reply) if reply.opcode == 0x8 && reply.payload.len() >= 2 => {
+                reply_code = Some(u16::from_be_bytes([reply.payload[0], reply.payload[1]]));
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "no close reply from server"),
+        }
+    }
+
+    if let Err(e) = tls_stream.shutdown() {
+        tracing::warn!(error = %e, "TLS shutdown failed");
+    }
+    if let Err(e) = tls_stream.get_ref().tcp().shutdown(Shutdown::Both) {
+        tracing::warn!(error = %e, "socket shutdown failed");
+    }
+    reply_code
+}
+
+/// Run the ping-pong loop over a tungstenite stream, reconnecting on failure if enabled
+async fn run_ws_ping_loop(
+    url: &str,
+    mut ws_stream: WsStream,
+    config: &BenchmarkConfig,
+    monitor: &mut RunMonitor,
+) -> Result<(Vec<f64>, ReconnectStats)> {
+    let mut rtts = monitor.sample_buffer();
+    let mut reconnect_stats = ReconnectStats::default();
+    let mut keepalive = Keepalive::new(config);
+    let mut mix = PayloadMix::new(&config.mix, config.seed);
+    let mut trace = TraceReplay::new(&config.trace, config.trace_timing);
+    let default_payload = config.ping_payload();
+
+    if let Some(secs) = config.idle_before_secs {
+        let idle = Duration::from_secs_f64(secs);
+        match keepalive.as_mut() {
+            Some(keepalive) => keepalive.idle(&mut ws_stream, idle).await?,
+            None => tokio::time::sleep(idle).await,
+        }
+    }
+
+    for i in 0..config.ping_pong_count {
+        let delay = match trace.as_mut() {
+            Some(trace) => trace.next_delay(i as usize),
+            None => monitor.next_send_delay(),
+        };
+        match (delay, keepalive.as_mut()) {
+            (Some(delay), Some(keepalive)) => keepalive.idle(&mut ws_stream, delay).await?,
+            (Some(delay), None) => tokio::time::sleep(delay).await,
+            (None, Some(keepalive)) => keepalive.send_due(&mut ws_stream).await?,
+            (None, None) => {}
+        }
+
+        let (class, payload) = match (mix.as_mut(), &trace) {
+            (Some(mix), _) => {
+                let class = mix.pick();
+                (Some(class), mix.payload(class).to_vec())
+            }
+            (None, Some(trace)) => (None, trace.payload(i as usize)),
+            (None, None) => (None, default_payload.clone()),
+        };
+
+        // Retry a failed ping on the same connection; RTT covers only the successful attempt
+        let mut retries = 0;
+        let result = loop {
+            let start = Instant::now();
+            match ping_pong(&mut ws_stream, config, &payload).await {
+                Ok(msg) => break Ok((msg, start.elapsed())),
+                Err(e) if retries < config.ping_retries => {
+                    retries += 1;
+                    reconnect_stats.ping_retries += 1;
+                    tracing::warn!(iteration = i + 1, retry = retries, error = format!("{:#}", e), "ping failed, retrying");
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        match result {
+            Ok((msg, elapsed)) => {
+                let end = elapsed.as_millis() as f64;
+                monitor.record_rtt(&mut rtts, end);
+                if let (Some(mix), Some(class)) = (mix.as_mut(), class) {
+                    mix.record(class, end);
+                }
+
+                // Parse frame if needed
+                if let Message::Binary(mut data) = msg {
+                    check_response_frame(&mut data, config, false)?;
+                }
+
+                print_benchmark_result(i + 1, end);
+                if monitor.on_iteration(i + 1, end) {
+                    break;
+                }
+            }
+            Err(e) if config.reconnect => {
+                reconnect_stats.failed_iterations += 1;
+                monitor.on_failure(is_timeout(&e));
+                tracing::warn!(iteration = i + 1, error = format!("{:#}", e), "ping failed, reconnecting");
+
+                let down_since = Instant::now();
+                ws_stream = connect_with_retries(url, config).await?;
+                reconnect_stats.reconnections += 1;
+                reconnect_stats.downtime += down_since.elapsed();
+                tracing::info!(downtime_ms = down_since.elapsed().as_millis() as u64, "reconnected");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(keepalive) = &keepalive {
+        outln!("│ Keepalive pings sent: {:8}                                 │", keepalive.sent);
+    }
+    if let Some(mix) = &mix {
+        mix.print();
+    }
+    if let Some(trace) = &trace {
+        trace.print();
+    }
+    if let Some(secs) = config.idle_before_secs {
+        print_cold_warm(&rtts, secs);
+    }
+
+    let reply = close_ws(&mut ws_stream, config.close_frame()).await;
+    print_close_reply(config.close_code, reply);
+
+    Ok((rtts, reconnect_stats))
+}
+
+/// Connect, do a single ping-pong and return its RTT in milliseconds
+pub async fn run_once(config: &BenchmarkConfig) -> Result<f64> {
+    let url = config.ws_url(&config.host);
+
+    let attempt = async {
+        let mut ws_stream = connect_with_retries(&url, config).await?;
+        let start = Instant::now();
+        ping_pong(&mut ws_stream, config, &config.ping_payload()).await?;
+        let rtt_ms = start.elapsed().as_secs_f64() * 1000.0;
+        close_ws(&mut ws_stream, config.close_frame()).await;
+        Ok::<_, anyhow::Error>(rtt_ms)
+    };
+
+    match config.timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), attempt)
+            .await
+            .context("Timed out")?,
+        None => attempt.await,
+    }
+}
+
+/// Short ping-pong run against one --targets endpoint on its own connection
+async fn survey_target(url: &str, config: &BenchmarkConfig) -> Result<RttStats> {
+    let target = parse_ws_url(url).map_err(anyhow::Error::msg)?;
+    let config = BenchmarkConfig {
+        host: target.host.clone(),
+        hosts: vec![target.host],
+        port: target.port,
+        path: target.path,
+        tls: target.tls,
+        ..config.clone()
+    };
+
+    // A blackholed endpoint would otherwise stall its worker for the OS connect timeout
+    let connect_timeout = config.timeout_ms.map_or(RESPONSE_TIMEOUT, Duration::from_millis);
+    let mut ws_stream = tokio::time::timeout(connect_timeout, connect_with_retries(url, &config))
+        .await
+        .context("Timed out connecting")??;
+
+    let payload = config.ping_payload();
+    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
+    for _ in 0..config.ping_pong_count {
+        let start = Instant::now();
+        ping_pong(&mut ws_stream, &config, &payload).await?;
+        rtts.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    close_ws(&mut ws_stream, config.close_frame()).await;
+
+    Ok(RttStats::new(rtts))
+}
+
+/// Survey --targets endpoints, `concurrency` at a time, and print them by P99, worst first
+pub async fn survey_targets(config: &BenchmarkConfig, urls: &[String], concurrency: usize) -> Result<()> {
+    outln!(
+        "Surveying {} endpoints: {} pings each, {} at a time",
+        urls.len(),
+        config.ping_pong_count,
+        concurrency
+    );
+
+    let mut survey = futures_util::stream::iter(urls)
+        .map(|url| async move {
+            let outcome = survey_target(url, config).await.map_err(|e| format!("{:#}", e));
+            (url.as_str(), outcome)
+        })
+        .buffer_unordered(concurrency);
+
+    let mut results = Vec::with_capacity(urls.len());
+    while let Some((url, outcome)) = survey.next().await {
+        if !config.quiet {
+            let status = if outcome.is_ok() { "ok" } else { "FAILED" };
+            outln!("[{}/{}] {} {}", results.len() + 1, urls.len(), url, status);
+        }
+        results.push((url, outcome));
+    }
+
+    // Failed endpoints have no P99 and sink to the bottom
+    let p99 = |outcome: &Result<RttStats, String>| {
+        outcome.as_ref().map_or(f64::NEG_INFINITY, |stats| stats.percentile(99.0))
+    };
+    results.sort_by(|a, b| p99(&b.1).total_cmp(&p99(&a.1)));
+
+    let width = results.iter().map(|(url, _)| url.len()).max().unwrap_or(0).max("Endpoint".len());
+    outln!();
+    outln!("{:<width$}  {:>10}  {:>10}  Result", "Endpoint", "Avg (ms)", "P99 (ms)");
+    for (url, outcome) in &results {
+        match outcome {
+            Ok(stats) => outln!(
+                "{:<width$}  {:>10.3}  {:>10.3}  ok",
+                url,
+                stats.avg,
+                stats.percentile(99.0)
+            ),
+            Err(reason) => outln!("{:<width$}  {:>10}  {:>10}  FAILED: {}", url, "-", "-", reason),
+        }
+    }
+
+    let failed = results.iter().filter(|(_, outcome)| outcome.is_err()).count();
+    outln!();
+    outln!("{} ok, {} failed", results.len() - failed, failed);
+    // Some failures are expected in a survey, but none succeeding means the run measured nothing
+    if failed > 0 && failed == results.len() {
+        anyhow::bail!("All {} endpoints failed", failed);
+    }
+    Ok(())
+}
+
+/// How the server handled one --find-max-frame probe
+enum ProbeOutcome {
+    Accepted,
+    /// No response within the timeout; counted as accepted
+    Slow,
+    Rejected(String),
+}
+
+/// Send one ping with a `size`-byte payload on a fresh connection
+async fn probe_frame_size(url: &str, config: &BenchmarkConfig, size: usize) -> Result<ProbeOutcome> {
+    let mut ws_stream = connect_with_retries(url, config).await?;
+    let payload: Vec<u8> = PING_MESSAGE.iter().copied().cycle().take(size).collect();
+    let timeout = config.timeout_ms.map_or(PROBE_TIMEOUT, Duration::from_millis);
+
+    // The probe applies its own timeout so a slow reply isn't mistaken for a drop
+    let untimed = BenchmarkConfig {
+        timeout_ms: None,
+        ..config.clone()
+    };
+    let outcome = match tokio::time::timeout(timeout, ping_pong(&mut ws_stream, &untimed, &payload)).await {
+        Err(_) => ProbeOutcome::Slow,
+        Ok(Ok(Message::Close(Some(frame)))) if u16::from(frame.code) == CLOSE_TOO_BIG => {
+            ProbeOutcome::Rejected(format!("close {} (message too big)", CLOSE_TOO_BIG))
+        }
+        Ok(Ok(Message::Close(frame))) => ProbeOutcome::Rejected(match frame {
+            Some(frame) => format!("close {} {:?}", u16::from(frame.code), frame.reason),
+            None => "closed".to_string(),
+        }),
+        Ok(Ok(_)) => ProbeOutcome::Accepted,
+        Ok(Err(e)) => ProbeOutcome::Rejected(format!("{:#}", e)),
+    };
+
+    if !matches!(outcome, ProbeOutcome::Rejected(_)) {
+        close_ws(&mut ws_stream, config.close_frame()).await;
+    }
+    Ok(outcome)
+}
+
+/// Find the largest ping payload the server accepts.
+///
+/// Doubles the size from MIN_PROBE_SIZE until a probe is rejected (close 1009
+/// or a dropped connection), then binary-searches between the last accepted
+/// and first rejected sizes. Each probe is one ping-pong on a new connection.
+pub async fn find_max_frame(config: &BenchmarkConfig) -> Result<()> {
+    print_benchmark_header("Rust max frame size probe");
+    let url = config.ws_url(&config.host);
+
+    let probe = |size: usize| {
+        let url = url.clone();
+        async move {
+            let outcome = probe_frame_size(&url, config, size).await?;
+            match &outcome {
+                ProbeOutcome::Accepted => outln!("│ {:>10} B: accepted", size),
+                ProbeOutcome::Slow => outln!("│ {:>10} B: accepted but slow (no reply within timeout)", size),
+                ProbeOutcome::Rejected(reason) => outln!("│ {:>10} B: rejected ({})", size, reason),
+            }
+            Ok::<_, anyhow::Error>(!matches!(outcome, ProbeOutcome::Rejected(_)))
+        }
+    };
+
+    let mut accepted = 0;
+    let mut rejected = None;
+    let mut size = MIN_PROBE_SIZE;
+    while size <= MAX_PROBE_SIZE {
+        if probe(size).await? {
+            accepted = size;
+            size *= 2;
+        } else {
+            rejected = Some(size);
+            break;
+        }
+    }
+
+    let Some(mut rejected) = rejected else {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ No limit found: {} bytes accepted", MAX_PROBE_SIZE);
+        outln!("└──────────────────────────────────────────────────────────────┘");
+        return Ok(());
+    };
+
+    while rejected - accepted > 1 {
+        let mid = accepted + (rejected - accepted) / 2;
+        if probe(mid).await? {
+            accepted = mid;
+        } else {
+            rejected = mid;
+        }
+    }
+
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    if accepted == 0 {
+        outln!("│ Server rejected even {} bytes", rejected);
+    } else {
+        outln!("│ Max accepted payload: {} bytes (rejected from {})", accepted, rejected);
+    }
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    Ok(())
+}
+
+/// Send the opening handshake on `stream` and validate the response, including
+/// Sec-WebSocket-Accept. Returns the raw response.
+fn check_handshake<S: Read + Write>(stream: &mut S, config: &BenchmarkConfig) -> Result<String> {
+    let ws_key = generate_websocket_key();
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}:{}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         {}\
+         \r\n",
+        config.path, config.host, config.port, ws_key, origin_header(config)
+    );
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut response = vec![0u8; BUFFER_SIZE];
+    let bytes_read = stream.read(&mut response).context("Failed to read handshake response")?;
+    if bytes_read == 0 {
+        anyhow::bail!("Connection closed before the handshake response");
+    }
+    let response = String::from_utf8_lossy(&response[..bytes_read]).into_owned();
+
+    if response_status(&response) == Some(403) {
+        return Err(forbidden_error(config));
+    }
+    validate_upgrade_response(&response)
+        .and_then(|()| validate_accept_key(&response, &ws_key))
+        .map_err(|e| anyhow::anyhow!("WebSocket handshake failed: {}", e))?;
+
+    Ok(response)
+}
+
+/// Time client framing (header, mask key and the payload XOR) across payload sizes, without I/O.
+///
+/// Goes through `WebSocketFrame::create_frame`, the same path the benchmarks send with.
+pub fn run_masking_benchmark() -> Result<()> {
+    print_benchmark_header("Frame masking (create_frame, no network)");
+    outln!("│    Payload |     Frames |    ns/frame |        GB/s          │");
+
+    for size in MASKING_SIZES {
+        let payload = vec![0x5a; size];
+        let frames = (MASKING_BYTES_PER_SIZE / size).max(1);
+
+        // Warm the allocator and caches before timing
+        std::hint::black_box(WebSocketFrame::create_binary_frame(&payload));
+
+        let start = Instant::now();
+        for _ in 0..frames {
+            std::hint::black_box(WebSocketFrame::create_binary_frame(std::hint::black_box(&payload)));
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let ns_per_frame = elapsed * 1e9 / frames as f64;
+        let gb_per_sec = (size * frames) as f64 / elapsed / 1e9;
+        outln!("│ {:>8} B | {:>10} | {:>11.1} | {:>11.3}          │", size, frames, ns_per_frame, gb_per_sec);
+    }
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    Ok(())
+}
+
+/// Connect and perform the opening handshake only, printing what was negotiated.
+///
+/// A reachability/correctness check rather than a timing; no data frames are sent.
+pub fn validate_handshake(config: &BenchmarkConfig) -> Result<()> {
+    let url = config.ws_url(&config.host);
+    let tcp_stream = connect_tcp_sync(config).with_context(|| format!("Failed to connect TCP to {}", url))?;
+    tcp_stream.set_read_timeout(config.timeout_ms.map(Duration::from_millis))?;
+    outln!("Target:      {}", url);
+    outln!("Connected:   {}", tcp_stream.peer_addr()?);
+
+    let response = if config.tls {
+        let mut tls_stream = tls_connector()?
+            .connect(&config.host, tcp_stream)
+            .context("TLS handshake failed")?;
+        let certificate = match tls_stream.peer_certificate()? {
+            Some(certificate) => format!("{} bytes DER", certificate.to_der()?.len()),
+            None => "none".to_string(),
+        };
+        outln!("TLS:         established (peer certificate: {})", certificate);
+
+        let response = check_handshake(&mut tls_stream, config)?;
+        close_tls_sync(&mut tls_stream, config);
+        response
+    } else {
+        outln!("TLS:         none (plaintext)");
+        let mut tcp_stream = tcp_stream;
+        let response = check_handshake(&mut tcp_stream, config)?;
+        let close_frame = WebSocketFrame::create_frame(0x88, &config.close_payload());
+        if let Err(e) = tcp_stream.write_all(&close_frame) {
+            tracing::warn!(error = %e, "failed to send close frame");
+        }
+        let _ = tcp_stream.shutdown(Shutdown::Both);
+        response
+    };
+
+    let headers = parse_headers(&response);
+    let negotiated = |name: &str| headers.get(name).cloned().unwrap_or_else(|| "none".to_string());
+    outln!("Handshake:   OK (101, Sec-WebSocket-Accept valid)");
+    outln!("Subprotocol: {}", negotiated("sec-websocket-protocol"));
+    outln!("Extensions:  {}", negotiated("sec-websocket-extensions"));
+
+    Ok(())
+}
+
+/// Benchmark 1: Basic OpenSSL TLS 1.3 with blocking I/O
+pub async fn run_openssl_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust socket + Native TLS (blocking I/O)";
+    print_benchmark_header(name);
+    print_frame_opcode(config);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let url = config.ws_url(&config.host);
+    let ws_stream = connect_with_retries(&url, config).await?;
+
+    let mut monitor = RunMonitor::new(config);
+    let (rtts, reconnect_stats) = run_ws_ping_loop(&url, ws_stream, config, &mut monitor).await?;
+
+    cpu.stop();
+    let stats = monitor.rtt_stats(rtts);
+    stats.print_rtt_stats();
+    RttStats::print_cpu_time(&cpu, stats.count);
+    if config.reconnect || config.ping_retries > 0 {
+        reconnect_stats.print();
+    }
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
+
+/// Benchmark 2: TLS with async wait (tokio-based)
+pub async fn run_async_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust async + Native TLS (tokio)";
+    print_benchmark_header(name);
+    print_frame_opcode(config);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let url = config.ws_url(&config.host);
+    let ws_stream = connect_with_retries(&url, config).await?;
+
+    let mut monitor = RunMonitor::new(config);
+    let (rtts, reconnect_stats) = run_ws_ping_loop(&url, ws_stream, config, &mut monitor).await?;
+
+    cpu.stop();
+    let stats = monitor.rtt_stats(rtts);
+    stats.print_rtt_stats();
+    RttStats::print_cpu_time(&cpu, stats.count);
+    if config.reconnect || config.ping_retries > 0 {
+        reconnect_stats.print();
+    }
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
+
+/// Async benchmark over HTTP/2 extended CONNECT (RFC 8441)
+#[cfg(feature = "h2")]
+pub async fn run_h2_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust async + Native TLS (HTTP/2 CONNECT)";
+    print_benchmark_header(name);
+    print_frame_opcode(config);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let mut ws = crate::http2::H2WebSocket::connect(&config.host, config.port, &config.path, config.max_frame_size)
+        .await?;
+
+    let payload = config.ping_payload();
+    let mut masks = config.mask_source(0);
+    let mut monitor = RunMonitor::new(config);
+    let mut rtts = monitor.sample_buffer();
+
+    for i in 0..config.ping_pong_count {
+        if let Some(delay) = monitor.next_send_delay() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let start = Instant::now();
+
+        ws.send_frame(config.ping_frame_with(&payload, masks.as_mut()))?;
+        let frame = match config.timeout_ms {
+            Some(ms) => tokio::time::timeout(Duration::from_millis(ms), ws.recv_frame())
+                .await
+                .context("Timed out waiting for PONG")??,
+            None => ws.recv_frame().await?,
+        };
+        if let Err(e) = frame.validate() {
+            if config.strict {
+                return Err(anyhow::anyhow!("Protocol violation: {}", e));
+            }
+            tracing::warn!(error = %e, "protocol violation");
+        }
+
+        let end = start.elapsed().as_secs_f64() * 1000.0;
+        monitor.record_rtt(&mut rtts, end);
+
+        print_benchmark_result(i + 1, end);
+        if monitor.on_iteration(i + 1, end) {
+            break;
+        }
+    }
+
+    cpu.stop();
+    let stats = monitor.rtt_stats(rtts);
+    stats.print_rtt_stats();
+    RttStats::print_cpu_time(&cpu, stats.count);
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
+
+#[cfg(not(feature = "h2"))]
+pub async fn run_h2_benchmark(_config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    Err(anyhow::anyhow!("--h2 requires building with `--features h2`"))
+}
+
+/// Send the opening handshake for the hand-framed sync benchmarks and set up decompression.
+///
+/// Frames the server sends right behind its response are left in `buffer`.
+fn upgrade_sync<S: Read + Write>(
+    stream: &mut S,
+    config: &BenchmarkConfig,
+    buffer: &mut FrameBuffer,
+) -> Result<Option<Inflater>> {
+    let ws_key = generate_websocket_key();
+    let extensions_header = if config.compress && config.legacy_deflate {
+        // Listed in preference order; the server accepts at most one
+        format!(
+            "Sec-WebSocket-Extensions: {}, {}\r\n",
+            config.deflate.offer(),
+            X_WEBKIT_DEFLATE
+        )
+    } else if config.compress {
+        format!("Sec-WebSocket-Extensions: {}\r\n", config.deflate.offer())
+    } else {
+        String::new()
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}:{}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         {}\
+         {}\
+         \r\n",
+        config.path, config.host, config.port, ws_key, origin_header(config), extensions_header
+    );
+
+    // Signals (e.g. SIGWINCH) can interrupt blocking I/O on a long run; retry instead of failing
+    let mut io = RetryIo(stream);
+    io.write_all(request.as_bytes())?;
+    io.flush()?;
+
+    // Read handshake response
+    let mut response = vec![0u8; BUFFER_SIZE];
+    let bytes_read = io.read(&mut response)?;
+    let header_end = response[..bytes_read]
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map_or(bytes_read, |position| position + 4);
+    buffer.extend(&response[header_end..bytes_read]);
+    let response = String::from_utf8_lossy(&response[..header_end]);
+
+    if response_status(&response) == Some(403) {
+        return Err(forbidden_error(config));
+    }
+    validate_upgrade_response(&response)
+        .map_err(|e| anyhow::anyhow!("WebSocket handshake failed: {}", e))?;
+
+    let inflater = negotiate_compression(&response, config)?;
+    tracing::info!("handshake complete");
+    Ok(inflater)
+}
+
+/// Benchmark 11: Basic TLS (similar to C's OpenSSL benchmark)
+pub fn run_basic_tls_benchmark_sync(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust sync socket + Native TLS";
+    print_benchmark_header(name);
+    print_frame_opcode(config);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    if !config.tls {
+        anyhow::bail!("{} requires a wss:// target", name);
+    }
+
+    let connect_span = tracing::info_span!("connect", host = %config.host, port = config.port).entered();
+
+    // Connect TCP
+    let tcp_stream = connect_tcp_sync(config).context("Failed to connect TCP")?;
+
+    // Set TCP_NODELAY
+    tcp_stream.set_nodelay(true)?;
+    if config.quickack {
+        set_quickack(&tcp_stream).context("Failed to set TCP_QUICKACK")?;
+    }
+
+    // Create TLS connector
+    let connector = tls_connector()?;
+
+    // Watch the raw records for renegotiation once the initial handshake is done
+    let mut tls_stream = connector
+        .connect(&config.host, TlsRecordWatcher::new(tcp_stream))
+        .context("Failed to connect TLS")?;
+    tls_stream.get_mut().arm();
+
+    // WebSocket handshake, unless the server already speaks frames (--no-handshake)
+    let mut buffer = FrameBuffer::new(config.max_frame_size);
+    let mut inflater = if config.no_handshake {
+        None
+    } else {
+        upgrade_sync(&mut tls_stream, config, &mut buffer)?
+    };
+    drop(connect_span);
+
+    // Run ping-pong
+    let mut monitor = RunMonitor::new(config);
+    let mut rtts = monitor.sample_buffer();
+    let default_payload = config.ping_payload();
+    let mut mix = PayloadMix::new(&config.mix, config.seed);
+    let mut masks = config.mask_source(0);
+    // One mask for the whole run (--static-frame): no framing cost per iteration
+    let static_frame = config
+        .static_frame
+        .then(|| config.ping_frame_with(&default_payload, masks.as_mut()));
+    let mut ping_retries = 0;
+    let mut phases = PhaseTimes::default();
+    let mut desynced_iterations = 0;
+    let mut tls_events = Vec::new();
+    let mut ping_frame = Vec::new();
+    if let Some(secs) = config.idle_before_secs {
+        std::thread::sleep(Duration::from_secs_f64(secs));
+    }
+    let run_start = Instant::now();
+
+    for i in 0..config.ping_pong_count {
+        if let Some(delay) = monitor.next_send_delay() {
+            std::thread::sleep(delay);
+        }
+
+        let control_records = tls_stream.get_ref().control_records();
+        let class = mix.as_mut().map(PayloadMix::pick);
+        let payload = match (&mix, class) {
+            (Some(mix), Some(class)) => mix.payload(class),
+            _ => default_payload.as_slice(),
+        };
+
+        // Retry a failed ping on the same connection; RTT covers only the successful attempt
+        let mut retries = 0;
+        let (start, mut frame_data) = loop {
+            let start = Instant::now();
+
+            // Send PING and receive PONG; small pings are framed on the stack
+            let small_frame;
+            let frame: &[u8] = if let Some(frame) = &static_frame {
+                frame
+            } else if let Some(frame) = config.small_ping_frame(payload, masks.as_mut()) {
+                small_frame = frame;
+                &small_frame
+            } else {
+                config.ping_frame_into(&mut ping_frame, payload, masks.as_mut());
+                &ping_frame
+            };
+            phases.add(Phase::Frame, start.elapsed());
+            let result = timed_exchange(&mut tls_stream, &mut buffer, frame, &mut phases);
+            if config.no_handshake && i == 0 {
+                let received = match &result {
+                    Ok(frame_data) => frame_data.as_slice(),
+                    Err(_) => buffer.pending(),
+                };
+                reject_http_reply(received)?;
+            }
+            // The kernel clears TCP_QUICKACK once data arrives
+            if config.quickack {
+                if let Err(e) = set_quickack(tls_stream.get_ref().tcp()) {
+                    tracing::debug!(error = %e, "failed to re-arm TCP_QUICKACK");
+                }
+            }
+
+            match result {
+                Ok(frame_data) => break (start, frame_data),
+                Err(e) if retries < config.ping_retries => {
+                    retries += 1;
+                    ping_retries += 1;
+                    tracing::warn!(iteration = i + 1, retry = retries, error = %e, "ping failed, retrying");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        let parse_start = Instant::now();
+        let frame = check_response_frame(&mut frame_data, config, inflater.is_some())?;
+
+        // Only frames with RSV1 set carry compressed payloads; inflated text is UTF-8-checked
+        // here since frame validation only sees the compressed bytes
+        if let (Some(frame), Some(inflater)) = (frame, inflater.as_mut()) {
+            let payload = inflater.decode(frame.rsv1, frame.payload).context("Failed to inflate PONG")?;
+            if frame.rsv1 {
+                check_message_text(frame.opcode, &payload, config)?;
+            }
+        }
+        phases.add(Phase::Parse, parse_start.elapsed());
+        phases.finish_iteration();
+
+        if tls_stream.get_ref().control_records() != control_records {
+            let at_secs = run_start.elapsed().as_secs_f64();
+            tracing::warn!(iteration = i + 1, at_secs, "TLS control records (renegotiation) received");
+            tls_events.push((i as usize, at_secs));
+        }
+
+        // One ping should yield exactly one frame; leftovers mean the server sent
+        // extra frames or the reads are out of step with the responses
+        if !buffer.pending().is_empty() {
+            desynced_iterations += 1;
+            let (extra_frames, consumed) = parse_frames(buffer.pending());
+            tracing::warn!(
+                iteration = i + 1,
+                extra_frames = extra_frames.len(),
+                partial_bytes = buffer.pending().len() - consumed,
+                "unconsumed bytes after response"
+            );
+        }
+
+        let end = start.elapsed().as_millis() as f64;
+        monitor.record_rtt(&mut rtts, end);
+        if let (Some(mix), Some(class)) = (mix.as_mut(), class) {
+            mix.record(class, end);
+        }
+
+        print_benchmark_result(i + 1, end);
+        if monitor.on_iteration(i + 1, end) {
+            break;
+        }
+    }
+
+    // Read before closing, while the connection still holds its state
+    let tcp_info = config.tcp_info.then(|| TcpInfo::read(tls_stream.get_ref().tcp()));
+    let close_reply = close_tls_sync(&mut tls_stream, config);
+
+    cpu.stop();
+    // Cold/warm compares against the first sample, which --measure-window may trim
+    if let Some(secs) = config.idle_before_secs {
+        print_cold_warm(&rtts, secs);
+    }
+    let stats = monitor.rtt_stats(rtts);
+    stats.print_rtt_stats();
+    RttStats::print_cpu_time(&cpu, stats.count);
+    if config.ping_retries > 0 {
+        outln!("│ Ping retries: {:8}                                       │", ping_retries);
+    }
+    print_outliers(&stats, &tls_events);
+    if let Some(mix) = &mix {
+        mix.print();
+    }
+    if desynced_iterations > 0 {
+        outln!("│ Desync: {:8} iterations left unconsumed bytes buffered   │", desynced_iterations);
+    }
+    if let Some(inflater) = inflater.as_ref().filter(|inflater| inflater.compressed_bytes > 0) {
+        outln!(
+            "│ {:61}│",
+            format!(
+                "Compression ({:?} payload): {:.2}:1, {} B -> {} B",
+                config.payload_kind, inflater.ratio(), inflater.compressed_bytes, inflater.inflated_bytes
+            )
+        );
+    }
+    phases.print();
+    if let Some(tcp_info) = tcp_info {
+        TcpInfo::report(tcp_info);
+    }
+    print_close_reply(config.close_code, close_reply);
+    if let Some(path) = &config.folded_output {
+        std::fs::write(path, phases.folded("sync_tls"))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        outln!("│ Folded phase stacks written to {}", path.display());
+    }
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
+
+/// Benchmark 6: Multi-connection test (simplified version)
+pub async fn run_multi_connection_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    // Fail upfront instead of with "Too many open files" partway through
+    ensure_fd_limit(config.clients as u64 + FD_OVERHEAD).map_err(anyhow::Error::msg)?;
+
+    outln!("┌──────────────────────────────────────────────────────────────┐");
+    let per_connection = config.per_connection();
+    outln!("│ Multi-Connection Benchmark ({} clients × {} ping-pong)          │", config.clients, per_connection);
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│ Implementation: Rust async + Native TLS                      │");
+    print_frame_opcode(config);
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    // Sample RSS in the background while the clients run
+    let memory = config.track_memory.then(|| Arc::new(Mutex::new(MemoryTracker::start())));
+    let memory_task = memory.clone().map(|memory| {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MEMORY_SAMPLE_PERIOD);
+            loop {
+                interval.tick().await;
+                memory.lock().unwrap().sample();
+            }
+        })
+    });
+
+    let client_count = config.clients;
+    let hosts = if config.hosts.is_empty() {
+        vec![config.host.clone()]
+    } else {
+        config.hosts.clone()
+    };
+
+    let status = config
+        .report_interval_secs
+        .map(|secs| StatusThread::start(Duration::from_secs_f64(secs)));
+    let mut handles = Vec::new();
+    let ramp = Duration::from_secs_f64(config.ramp_secs);
+    // Caps concurrent TCP + TLS + upgrade handshakes so thousands of clients don't flood the accept queue
+    let connect_limit = Arc::new(Semaphore::new(config.connect_concurrency as usize));
+    let connect_phase_start = Instant::now();
+
+    for client in 0..client_count {
+        // Round-robin clients across the target hosts
+        let host_index = client as usize % hosts.len();
+        let url_clone = config.ws_url(&hosts[host_index]);
+        let host = hosts[host_index].clone();
+        let port = config.port;
+        let source_port = config.source_port_range.map(|(start, _)| start + client as u16);
+        let options = SocketOptions {
+            source_port,
+            ..SocketOptions::from_config(config)
+        };
+        let origin = config.origin.clone();
+        let close = config.close_frame();
+        let count = per_connection;
+        let payload = config.ping_payload();
+        let opcode = config.ping_opcode();
+        let mut masks = config.mask_source(client as u64);
+        // --static-frame: framed and masked once per client, before its ping loop
+        let static_frame = config
+            .static_frame
+            .then(|| WebSocketFrame::create_frame_with(opcode, &payload, masks.as_mut()));
+        let response_timeout = config.timeout_ms.map(Duration::from_millis);
+        let count_iterations = status.is_some();
+        let track_client = !ramp.is_zero() || source_port.is_some();
+        let mut backoff = Backoff::new(config, client as u64);
+        let connect_limit = connect_limit.clone();
+        // Spread connection establishment evenly over the ramp window
+        let start_delay = ramp.mul_f64(client as f64 / client_count as f64);
+
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(start_delay).await;
+            let mut local_rtts = Vec::with_capacity(count as usize);
+            let mut failed = 0u32;
+            let mut timed_out = 0u32;
+            let mut forbidden = false;
+
+            let connected = loop {
+                // Released before any backoff sleep so waiting retries don't hold a slot
+                let attempt = {
+                    let _permit = connect_limit.acquire().await.expect("connect semaphore is never closed");
+                    connect_ws(&url_clone, &host, port, &options, origin.as_deref()).await
+                };
+                match attempt {
+                    Ok(ws_stream) => break Ok(ws_stream),
+                    Err(e) if is_forbidden(&e) => break Err(e),
+                    Err(e) => match backoff.next_delay() {
+                        Some(delay) => {
+                            tracing::debug!(client, attempt = backoff.attempt, error = %e, "connect failed, retrying");
+                            tokio::time::sleep(delay).await;
+                        }
+                        None => break Err(e),
+                    },
+                }
+            };
+            let retries = backoff.stats();
+            let connect_done = Instant::now();
+
+            let outcome = match connected {
+                Ok(mut ws_stream) => {
+                    for _ in 0..count {
+                        let start = Instant::now();
+
+                        // Message::Binary owns its bytes, so the prebuilt frame is copied, not rebuilt
+                        let ping_frame = match &static_frame {
+                            Some(frame) => frame.clone(),
+                            None => WebSocketFrame::create_frame_with(opcode, &payload, masks.as_mut()),
+                        };
+                        let exchange = async {
+                            ws_stream.send(Message::Binary(ping_frame)).await.is_ok()
+                                && matches!(ws_stream.next().await, Some(Ok(Message::Binary(_))))
+                        };
+                        let answered = match response_timeout {
+                            Some(limit) => tokio::time::timeout(limit, exchange).await,
+                            None => Ok(exchange.await),
+                        };
+                        match answered {
+                            Ok(true) => {
+                                let rtt = start.elapsed().as_millis() as f64;
+                                if count_iterations {
+                                    output::record_iteration(rtt);
+                                }
+                                local_rtts.push(rtt);
+                            }
+                            Ok(false) => failed += 1,
+                            Err(_) => timed_out += 1,
+                        }
+                    }
+                    close_ws(&mut ws_stream, close).await;
+                    // Summarize here, in parallel across clients, rather than after the run
+                    let client_stats = track_client.then(|| RttStats::new(local_rtts.clone()));
+                    // Tasks can migrate between workers; record where this one finished
+                    Some((local_rtts, client_stats, current_cpu()))
+                }
+                Err(e) if is_connect_timeout(&e) => {
+                    timed_out = count;
+                    None
+                }
+                Err(e) => {
+                    forbidden = is_forbidden(&e);
+                    failed = count;
+                    None
+                }
+            };
+            (outcome, failed, timed_out, forbidden, retries, connect_done)
+        });
+
+        handles.push((client, start_delay, host_index, source_port, handle));
+    }
+
+    let mut all_rtts = Vec::with_capacity(client_count as usize * per_connection as usize);
+    let mut ramp_profile = Vec::new();
+    let mut per_port = Vec::new();
+    // Only reported with several hosts; one host's samples are just all_rtts
+    let mut per_host: Vec<Vec<f64>> = vec![Vec::new(); if hosts.len() > 1 { hosts.len() } else { 0 }];
+    let mut worker_cores: BTreeMap<usize, u32> = BTreeMap::new();
+    let mut retries = RetryStats::default();
+    let mut connect_phase = Duration::ZERO;
+    let mut failed = 0u64;
+    let mut timed_out = 0u64;
+    let mut forbidden = 0u32;
+
+    for (client, start_delay, host_index, source_port, handle) in handles {
+        let Ok((outcome, client_failed, client_timed_out, client_forbidden, client_retries, connect_done)) =
+            handle.await
+        else {
+            failed += per_connection as u64;
+            continue;
+        };
+        failed += client_failed as u64;
+        timed_out += client_timed_out as u64;
+        forbidden += client_forbidden as u32;
+        retries.merge(client_retries);
+        connect_phase = connect_phase.max(connect_done - connect_phase_start);
+        if let Some((rtts, client_stats, core)) = outcome {
+            if let Some(core) = core {
+                *worker_cores.entry(core).or_default() += 1;
+            }
+            if let (Some(port), Some(client_stats)) = (source_port, &client_stats) {
+                per_port.push((port, client_stats.count, client_stats.avg, client_stats.max));
+            }
+            if let Some(client_stats) = client_stats.filter(|_| !ramp.is_zero()) {
+                ramp_profile.push((client, start_delay, client_stats));
+            }
+            if let Some(host_rtts) = per_host.get_mut(host_index) {
+                host_rtts.extend_from_slice(&rtts);
+            }
+            all_rtts.extend(rtts);
+        }
+    }
+
+    cpu.stop();
+    drop(status);
+
+    if let Some(task) = memory_task {
+        task.abort();
+    }
+
+    let stats = RttStats::new(all_rtts);
+    if stats.count == 0 && forbidden > 0 {
+        return Err(forbidden_error(config));
+    }
+    if stats.count > 0 {
+        let throughput = if cpu.wall_time > 0.0 {
+            stats.count as f64 / cpu.wall_time
+        } else {
+            0.0
+        };
+
+        outln!("┌──────────────────────────────────────────────────────────────┐");
+        outln!("│ Results: {} clients × {} ping-pong = {} messages           │",
+            client_count, per_connection, client_count as u64 * per_connection as u64);
+        print_completed(
+            stats.count as u64,
+            client_count as u64 * per_connection as u64,
+            failed,
+            timed_out,
+        );
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Total Time: {:7.2}s | Throughput: {:8.0} msg/sec         │",
+            cpu.wall_time, throughput);
+        outln!("│ Avg RTT: {:7.3} ms | Median: {:7.3} ms                   │",
+            stats.avg, stats.median);
+        outln!("│ Min RTT: {:7.3} ms | Max: {:7.3} ms                      │",
+            stats.min, stats.max);
+        outln!("│ Connect phase: {:8.2}s ({:5} handshakes at a time)        │",
+            connect_phase.as_secs_f64(), config.connect_concurrency.min(client_count));
+        if forbidden > 0 {
+            outln!("│ {:61}│", format!("Refused with 403 Forbidden: {} clients", forbidden));
+        }
+        if config.connect_retries > 0 {
+            outln!("├──────────────────────────────────────────────────────────────┤");
+            outln!("│ Connect retries: {:8} | Max attempts (one client): {:5} │",
+                retries.retries, retries.max_attempts);
+            outln!("│ Time retrying (all clients): {:10.2}s                     │",
+                retries.time.as_secs_f64());
+        }
+        if hosts.len() > 1 {
+            outln!("├──────────────────────────────────────────────────────────────┤");
+            outln!("│ Per-Host Results:                                            │");
+            for (host, rtts) in hosts.iter().zip(per_host) {
+                let host_stats = RttStats::new(rtts);
+                outln!("│   {} | {} msgs | Avg: {:7.3} ms | Median: {:7.3} ms",
+                    host, host_stats.count, host_stats.avg, host_stats.median);
+            }
+        }
+        if !per_port.is_empty() {
+            outln!("├──────────────────────────────────────────────────────────────┤");
+            outln!("│ Per-Source-Port Results:                                     │");
+            for (port, count, avg, max) in &per_port {
+                outln!("│   Port {:>5} | {} msgs | Avg: {:7.3} ms | Max: {:7.3} ms",
+                    port, count, avg, max);
+            }
+        }
+        if !config.cpu_affinity.is_empty() {
+            outln!("├──────────────────────────────────────────────────────────────┤");
+            outln!("│ Worker Cores (pinned to {:?}):", config.cpu_affinity);
+            for (core, clients) in &worker_cores {
+                outln!("│   Core {:>3}: {:>5} clients", core, clients);
+            }
+        }
+        if !ramp_profile.is_empty() {
+            outln!("├──────────────────────────────────────────────────────────────┤");
+            outln!("│ Ramp Profile ({:.1}s):                                        │", ramp.as_secs_f64());
+            // Clients that failed to connect are missing, so label rows by the client's own index
+            for (client, start_delay, client_stats) in &ramp_profile {
+                outln!("│   Client {:>4} @ {:6.2}s | Avg: {:7.3} ms | Max: {:7.3} ms",
+                    client + 1, start_delay.as_secs_f64(), client_stats.avg, client_stats.max);
+            }
+        }
+        if let Some(memory) = &memory {
+            let mut memory = memory.lock().unwrap();
+            memory.sample();
+            memory.print();
+        }
+        outln!("└──────────────────────────────────────────────────────────────┘");
+    }
+
+    Ok(BenchmarkReport::new("Multi-Connection (Rust async + Native TLS)", config, &stats, &cpu))
+}
+
+/// TCP benchmark (no TLS)
+pub async fn run_tcp_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust TCP (no TLS)";
+    print_benchmark_header(name);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let mut socket = connect_tcp(&config.host, config.port, &SocketOptions::from_config(config))
+        .await
+        .context("Failed to connect")?;
+    if config.quickack {
+        set_quickack(&socket).context("Failed to set TCP_QUICKACK")?;
+    }
+
+    let mut monitor = RunMonitor::new(config);
+    let mut rtts = monitor.sample_buffer();
+    let mut responses = ResponseCheck::default();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+
+    for i in 0..config.ping_pong_count {
+        if let Some(delay) = monitor.next_send_delay() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let start = Instant::now();
+
+        socket.write_all(PING_MESSAGE).await?;
+
+        // The echo may arrive split across segments: read until it's complete or the peer closes
+        let mut n = 0;
+        while n < PING_MESSAGE.len() {
+            match socket.read(&mut buf[n..]).await? {
+                0 => break,
+                read => n += read,
+            }
+        }
+        // The kernel clears TCP_QUICKACK once data arrives
+        if config.quickack {
+            if let Err(e) = set_quickack(&socket) {
+                tracing::debug!(error = %e, "failed to re-arm TCP_QUICKACK");
+            }
+        }
+
+        let end = start.elapsed().as_millis() as f64;
+        if !responses.accept(i + 1, n, PING_MESSAGE.len()) {
+            monitor.on_failure(false);
+            continue;
+        }
+        monitor.record_rtt(&mut rtts, end);
+
+        print_benchmark_result(i + 1, end);
+        if monitor.on_iteration(i + 1, end) {
+            break;
+        }
+    }
+
+    let tcp_info = config.tcp_info.then(|| TcpInfo::read(&socket));
+    if let Err(e) = socket.shutdown().await {
+        tracing::warn!(error = %e, "TCP shutdown failed");
+    }
+
+    cpu.stop();
+    let stats = monitor.rtt_stats(rtts);
+    stats.print_rtt_stats();
+    RttStats::print_cpu_time(&cpu, stats.count);
+    responses.print();
+    if let Some(tcp_info) = tcp_info {
+        TcpInfo::report(tcp_info);
+    }
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+    responses.check(config)?;
+
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
+
+/// UDP benchmark
+pub async fn run_udp_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust UDP";
+    print_benchmark_header(name);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let udp_port = config.port + 2; // Use port 8445 for UDP
+    let peer = resolve(&config.host, udp_port).await?;
+    let socket = SocketOptions::from_config(config).socket(peer, socket2::Type::DGRAM)?;
+    let local: std::net::SocketAddr = if peer.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    socket.bind(&local.into())?;
+    socket.set_nonblocking(true)?;
+    let socket = tokio::net::UdpSocket::from_std(socket.into())?;
+
+    // A connected UDP socket reports ICMP port-unreachable as ConnectionRefused
+    socket
+        .connect(peer)
+        .await
+        .context("Failed to set UDP peer address")?;
+    let recv_timeout = config
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(UDP_RECV_TIMEOUT);
+
+    let mut monitor = RunMonitor::new(config);
+    let mut rtts = monitor.sample_buffer();
+    let mut delivery = DeliveryTracker::new(config.ping_pong_count as u64);
+    let mut lost = 0u32;
+    let mut responses = ResponseCheck::default();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+
+    for i in 0..config.ping_pong_count {
+        if let Some(delay) = monitor.next_send_delay() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let start = Instant::now();
+
+        let payload = PayloadHeader::now(i as u64).encode(PING_MESSAGE);
+        let deadline = tokio::time::Instant::from_std(start + recv_timeout);
+        let result = match socket.send(&payload).await {
+            // A reply to an earlier, timed-out ping would otherwise be timed as this one's
+            Ok(_) => loop {
+                match tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                    Ok(Ok(n)) if PayloadHeader::decode(&buf[..n]).is_some_and(|h| h.seq != i as u64) => {
+                        tracing::debug!(iteration = i + 1, "dropping a reply outside the awaited sequence");
+                        delivery.record_late();
+                    }
+                    received => break received,
+                }
+            },
+            Err(e) => Ok(Err(e)),
+        };
+
+        let n = match result {
+            Ok(Ok(n)) => n,
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                return Err(anyhow::anyhow!(
+                    "UDP server not reachable at {}:{} (ICMP port unreachable) - no server is listening",
+                    config.host,
+                    udp_port
+                ));
+            }
+            Ok(Err(e)) => return Err(e).context("UDP I/O failed"),
+            Err(_) => {
+                lost += 1;
+                tracing::debug!(iteration = i + 1, "UDP response timed out");
+                monitor.on_failure(true);
+                continue;
+            }
+        };
 
         let end = start.elapsed().as_millis() as f64;
-        rtts.push(end);
-
-        // Parse frame if needed
-        if let Message::Binary(data) = msg {
-            let _frame = WebSocketFrame::parse_frame(&data);
+        // Stale sequences were dropped above, so a sequenced reply is this ping's
+        if PayloadHeader::decode(&buf[..n]).is_some() {
+            delivery.record(i as u64);
+        }
+        if !responses.accept(i + 1, n, payload.len()) {
+            monitor.on_failure(false);
+            continue;
         }
+        monitor.record_rtt(&mut rtts, end);
 
         print_benchmark_result(i + 1, end);
+        if monitor.on_iteration(i + 1, end) {
+            break;
+        }
     }
 
     cpu.stop();
-    let stats = RttStats::new(rtts.clone());
+    let stats = monitor.rtt_stats(rtts);
     stats.print_rtt_stats();
     RttStats::print_cpu_time(&cpu, stats.count);
-    println!("└──────────────────────────────────────────────────────────────┘");
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│ Lost (timed out): {:6}                                     │", lost);
+    if stats.count == 0 && lost > 0 {
+        outln!("│ No responses at all: server silent or traffic filtered       │");
+    } else if lost > 0 {
+        outln!("│ Partial loss: lossy link or overloaded server                │");
+    }
+    // Only meaningful if the server echoes the sequenced payload
+    if delivery.received > 0 || delivery.late > 0 {
+        delivery.print();
+    }
+    responses.print();
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
+    responses.check(config)?;
 
-    Ok(())
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
 }
 
-/// Benchmark 2: TLS with async wait (tokio-based)
-pub async fn run_async_benchmark(config: &BenchmarkConfig) -> Result<()> {
-    print_benchmark_header("Rust async + Native TLS (tokio)");
+/// Benchmark 9: Full-duplex - one task sends while another drains responses
+pub async fn run_full_duplex_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust full-duplex (split tungstenite stream)";
+    print_benchmark_header(name);
 
     let mut cpu = CpuTime::new();
     cpu.start();
 
-    let url = format!("wss://{}:{}/ws", config.host, config.port);
-    let (mut ws_stream, _) = connect_async(&url).await?;
-
-    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
+    let url = config.ws_url(&config.host);
+    let ws_stream = connect_with_retries(&url, config).await?;
+    let (mut sink, mut stream) = ws_stream.split();
 
-    for i in 0..config.ping_pong_count {
-        let start = Instant::now();
+    let count = config.ping_pong_count;
+    let filler = config.ping_payload();
 
-        // Send PING
-        let ping_frame = WebSocketFrame::create_text_frame(PING_MESSAGE);
-        ws_stream.send(Message::Binary(ping_frame)).await?;
+    // Sender: pings go out back-to-back without waiting for responses
+    let sender = tokio::spawn(async move {
+        for seq in 0..count as u64 {
+            let payload = PayloadHeader::now(seq).encode(&filler);
+            sink.send(Message::Binary(payload))
+                .await
+                .context("Failed to send PING")?;
+        }
+        Ok::<_, anyhow::Error>(sink)
+    });
+
+    // Receiver: RTT comes from the send time embedded in each response,
+    // so it stays correct even if responses arrive out of order
+    let mut monitor = RunMonitor::new(config);
+    let mut rtts = monitor.sample_buffer();
+    let mut delivery = DeliveryTracker::new(count as u64);
+    let mut received = 0;
+
+    while received < count {
+        let msg = match tokio::time::timeout(RESPONSE_TIMEOUT, stream.next()).await {
+            Ok(Some(msg)) => msg.context("Failed to receive PONG")?,
+            Ok(None) => {
+                for _ in received..count {
+                    monitor.on_failure(false);
+                }
+                break;
+            }
+            Err(_) => {
+                tracing::warn!(received, "timed out waiting for responses");
+                for _ in received..count {
+                    monitor.on_failure(true);
+                }
+                break;
+            }
+        };
 
-        // Receive PONG (async wait)
-        let msg = ws_stream.next().await.ok_or_else(|| anyhow::anyhow!("Connection closed"))??;
+        let Message::Binary(data) = msg else {
+            continue;
+        };
+        let Some(header) = PayloadHeader::decode(&data) else {
+            continue;
+        };
+        if !delivery.record(header.seq) {
+            continue;
+        }
 
-        let end = start.elapsed().as_millis() as f64;
-        rtts.push(end);
+        let end = header.elapsed_ms();
+        monitor.record_rtt(&mut rtts, end);
+        received += 1;
 
-        if let Message::Binary(data) = msg {
-            let _frame = WebSocketFrame::parse_frame(&data);
+        print_benchmark_result(header.seq as u32 + 1, end);
+        if monitor.on_iteration(received, end) {
+            break;
         }
+    }
 
-        print_benchmark_result(i + 1, end);
+    let sink = sender.await??;
+    if let Ok(mut ws_stream) = stream.reunite(sink) {
+        close_ws(&mut ws_stream, config.close_frame()).await;
     }
 
     cpu.stop();
-    let stats = RttStats::new(rtts.clone());
+    let stats = monitor.rtt_stats(rtts);
     stats.print_rtt_stats();
     RttStats::print_cpu_time(&cpu, stats.count);
-    println!("└──────────────────────────────────────────────────────────────┘");
+    delivery.print();
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
 
-    Ok(())
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
 }
 
-/// Benchmark 11: Basic TLS (similar to C's OpenSSL benchmark)
-pub fn run_basic_tls_benchmark_sync(config: &BenchmarkConfig) -> Result<()> {
-    print_benchmark_header("Rust sync socket + Native TLS");
+/// Open-loop variant of benchmark 9: pings go out on the --interval-ms schedule no matter
+/// how many responses are outstanding.
+///
+/// Latency is measured from each ping's scheduled send time, so a stall on the sending
+/// side (e.g. backpressure) counts against every ping it delayed instead of being
+/// hidden (coordinated omission). The naive send-to-response latency is shown alongside.
+pub async fn run_open_loop_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let Some(interval_ms) = config.interval_ms else {
+        anyhow::bail!("--open-loop needs a send schedule: pass --interval-ms");
+    };
+
+    let name = "Rust open-loop (scheduled sends, CO-corrected)";
+    print_benchmark_header(name);
 
     let mut cpu = CpuTime::new();
     cpu.start();
 
-    // Connect TCP
-    let tcp_stream = TcpStream::connect((config.host.as_str(), config.port))
-        .context("Failed to connect TCP")?;
+    let url = config.ws_url(&config.host);
+    let ws_stream = connect_with_retries(&url, config).await?;
+    let (mut sink, mut stream) = ws_stream.split();
+
+    let count = config.ping_pong_count;
+    let filler = config.ping_payload();
+    let mut pacer = Pacer::new(config.arrival, interval_ms, config.seed);
+
+    // Sender: follows the schedule and remembers when each ping was meant to go out
+    let sender = tokio::spawn(async move {
+        let mut scheduled = Vec::with_capacity(count as usize);
+        for seq in 0..count as u64 {
+            tokio::time::sleep(pacer.next_delay()).await;
+            scheduled.push(pacer.scheduled().unwrap_or_else(Instant::now));
+
+            let payload = PayloadHeader::now(seq).encode(&filler);
+            sink.send(Message::Binary(payload))
+                .await
+                .context("Failed to send PING")?;
+        }
+        Ok::<_, anyhow::Error>((sink, scheduled, pacer))
+    });
+
+    let mut monitor = RunMonitor::new(config);
+    let mut delivery = DeliveryTracker::new(count as u64);
+    let mut naive = Vec::with_capacity(count as usize);
+    let mut received = Vec::with_capacity(count as usize);
+
+    while received.len() < count as usize {
+        let msg = match tokio::time::timeout(RESPONSE_TIMEOUT, stream.next()).await {
+            Ok(Some(msg)) => msg.context("Failed to receive PONG")?,
+            Ok(None) => break,
+            Err(_) => {
+                tracing::warn!(received = received.len(), "timed out waiting for responses");
+                break;
+            }
+        };
 
-    // Set TCP_NODELAY
-    tcp_stream.set_nodelay(true)?;
+        let Message::Binary(data) = msg else {
+            continue;
+        };
+        let Some(header) = PayloadHeader::decode(&data) else {
+            continue;
+        };
+        if !delivery.record(header.seq) {
+            continue;
+        }
 
-    // Create TLS connector
-    let connector = TlsConnector::builder()
-        .danger_accept_invalid_certs(true)
-        .danger_accept_invalid_hostnames(true)
-        .build()
-        .context("Failed to create TLS connector")?;
+        let end = header.elapsed_ms();
+        naive.push(end);
+        received.push((header.seq as usize, Instant::now()));
 
-    let mut tls_stream = connector
-        .connect(&config.host, tcp_stream)
-        .context("Failed to connect TLS")?;
+        print_benchmark_result(header.seq as u32 + 1, end);
+        if monitor.on_iteration(received.len() as u32, end) {
+            break;
+        }
+    }
+    for _ in received.len()..count as usize {
+        monitor.on_failure(true);
+    }
 
-    // WebSocket handshake
-    let ws_key = generate_websocket_key();
-    let request = format!(
-        "GET /ws HTTP/1.1\r\n\
-         Host: {}:{}\r\n\
-         Upgrade: websocket\r\n\
-         Connection: Upgrade\r\n\
-         Sec-WebSocket-Key: {}\r\n\
-         Sec-WebSocket-Version: 13\r\n\
-         \r\n",
-        config.host, config.port, ws_key
-    );
+    let (sink, scheduled, pacer) = sender.await??;
+    if let Ok(mut ws_stream) = stream.reunite(sink) {
+        close_ws(&mut ws_stream, config.close_frame()).await;
+    }
 
-    tls_stream.write_all(request.as_bytes())?;
-    tls_stream.flush()?;
+    cpu.stop();
+    let corrected = received
+        .iter()
+        .filter_map(|&(seq, at)| Some(at.saturating_duration_since(*scheduled.get(seq)?)))
+        .map(|latency| latency.as_secs_f64() * 1000.0)
+        .collect();
+    let naive = RttStats::new(naive);
+    let stats = RttStats::new(corrected);
+    stats.print_rtt_stats();
+    RttStats::print_corrected(&naive, &stats);
+    RttStats::print_cpu_time(&cpu, stats.count);
+    pacer.print();
+    delivery.print();
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
 
-    // Read handshake response
-    let mut response = vec![0u8; BUFFER_SIZE];
-    let bytes_read = tls_stream.read(&mut response)?;
-    let response = String::from_utf8_lossy(&response[..bytes_read]);
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
 
-    if !response.contains("101") {
-        return Err(anyhow::anyhow!("WebSocket handshake failed"));
-    }
+/// Benchmark 10: TLS handshake only (TCP connect + TLS handshake + close)
+pub fn run_tls_handshake_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust TLS handshake only (no WebSocket)";
+    print_benchmark_header(name);
 
-    // Run ping-pong
-    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
-    let mut recv_buf = vec![0u8; BUFFER_SIZE];
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let connector = tls_connector()?;
+    let mut monitor = RunMonitor::new(config);
+    let mut handshake_times = monitor.sample_buffer();
 
     for i in 0..config.ping_pong_count {
+        if let Some(delay) = monitor.next_send_delay() {
+            std::thread::sleep(delay);
+        }
+
         let start = Instant::now();
 
-        // Send PING
-        let ping_frame = WebSocketFrame::create_text_frame(PING_MESSAGE);
-        tls_stream.write_all(&ping_frame)?;
-        tls_stream.flush()?;
+        let tcp_stream = connect_tcp_sync(config).context("Failed to connect TCP")?;
+        tcp_stream.set_nodelay(true)?;
+        let mut tls_stream = connector
+            .connect(&config.host, tcp_stream)
+            .context("Failed to connect TLS")?;
 
-        // Receive PONG
-        let bytes_read = tls_stream.read(&mut recv_buf)?;
-        let _frame = WebSocketFrame::parse_frame(&recv_buf[..bytes_read]);
+        let end = start.elapsed().as_secs_f64() * 1000.0;
+        monitor.record_rtt(&mut handshake_times, end);
 
-        let end = start.elapsed().as_millis() as f64;
-        rtts.push(end);
+        // Best-effort close_notify and FIN; the handshake is what's being measured
+        if let Err(e) = tls_stream.shutdown() {
+            tracing::debug!(error = %e, "TLS shutdown failed");
+        }
+        let _ = tls_stream.get_ref().shutdown(Shutdown::Both);
 
         print_benchmark_result(i + 1, end);
+        if monitor.on_iteration(i + 1, end) {
+            break;
+        }
     }
 
     cpu.stop();
-    let stats = RttStats::new(rtts.clone());
+    let stats = monitor.rtt_stats(handshake_times);
     stats.print_rtt_stats();
+    if stats.has_samples() {
+        outln!("│   P50: {:7.3} ms | P99:    {:7.3} ms                       │",
+            stats.percentile(50.0), stats.percentile(99.0));
+    }
     RttStats::print_cpu_time(&cpu, stats.count);
-    println!("└──────────────────────────────────────────────────────────────┘");
+    monitor.print_summary();
+    outln!("└──────────────────────────────────────────────────────────────┘");
+    monitor.check_aborted()?;
 
-    Ok(())
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
 }
 
-/// Benchmark 6: Multi-connection test (simplified version)
-pub async fn run_multi_connection_benchmark(config: &BenchmarkConfig) -> Result<()> {
-    println!("┌──────────────────────────────────────────────────────────────┐");
-    println!("│ Multi-Connection Benchmark (50 clients × {} ping-pong)          │", config.ping_pong_count);
-    println!("├──────────────────────────────────────────────────────────────┤");
-    println!("│ Implementation: Rust async + Native TLS                      │");
-    println!("└──────────────────────────────────────────────────────────────┘");
+/// Wait-for-close: ping once, then block until the server closes the connection.
+///
+/// The server shutdown is triggered out of band; this measures how long the
+/// client takes to observe it and with which close code.
+pub async fn run_wait_for_close_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust wait-for-close (server shutdown)";
+    print_benchmark_header(name);
 
     let mut cpu = CpuTime::new();
     cpu.start();
 
-    let client_count = 50;
-    let url = format!("wss://{}:{}/ws", config.host, config.port);
-
-    let mut handles = Vec::new();
-
-    for _ in 0..client_count {
-        let url_clone = url.clone();
-        let count = config.ping_pong_count;
-
-        let handle = tokio::spawn(async move {
-            let mut local_rtts = Vec::with_capacity(count as usize);
-
-                match connect_async(&url_clone).await {
-                    Ok((mut ws_stream, _)) => {
-                        for _ in 0..count {
-                            let start = Instant::now();
-
-                            let ping_frame = WebSocketFrame::create_text_frame(PING_MESSAGE);
-                            if ws_stream.send(Message::Binary(ping_frame)).await.is_ok() {
-                                if let Some(Ok(msg)) = ws_stream.next().await {
-                                    if let Message::Binary(_) = msg {
-                                        local_rtts.push(start.elapsed().as_millis() as f64);
-                                    }
-                                }
-                            }
-                        }
-                        Some(local_rtts)
-                    }
-                    Err(_) => None,
+    let url = config.ws_url(&config.host);
+    let mut ws_stream = connect_with_retries(&url, config).await?;
+
+    let ping_start = Instant::now();
+    ping_pong(&mut ws_stream, config, &config.ping_payload()).await?;
+    outln!("│ Initial ping: {:7.3} ms, waiting for server close...         │",
+        ping_start.elapsed().as_secs_f64() * 1000.0);
+
+    let start = Instant::now();
+    let wait = async {
+        loop {
+            match ws_stream.next().await {
+                Some(Ok(Message::Close(frame))) => break frame,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    tracing::info!(error = %e, "connection ended without close frame");
+                    break None;
                 }
-        });
-
-        handles.push(handle);
-    }
-
-    let mut all_rtts = Vec::new();
-
-    for handle in handles {
-        if let Ok(Some(rtts)) = handle.await {
-            all_rtts.extend(rtts);
+                None => break None,
+            }
         }
-    }
+    };
 
-    cpu.stop();
+    let close_frame = match config.timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), wait)
+            .await
+            .context("Timed out waiting for server close")?,
+        None => wait.await,
+    };
+    let close_ms = start.elapsed().as_secs_f64() * 1000.0;
 
-    if !all_rtts.is_empty() {
-        let stats = RttStats::new(all_rtts);
-        let throughput = if cpu.wall_time > 0.0 {
-            stats.count as f64 / cpu.wall_time
-        } else {
-            0.0
-        };
+    // Complete the closing handshake if the server started one
+    let _ = ws_stream.close(None).await;
+    cpu.stop();
 
-        println!("┌──────────────────────────────────────────────────────────────┐");
-        println!("│ Results: {} clients × {} ping-pong = {} messages           │",
-            client_count, config.ping_pong_count, stats.count);
-        println!("├──────────────────────────────────────────────────────────────┤");
-        println!("│ Total Time: {:7.2}s | Throughput: {:8.0} msg/sec         │",
-            cpu.wall_time, throughput);
-        println!("│ Avg RTT: {:7.3} ms | Median: {:7.3} ms                   │",
-            stats.avg, stats.median);
-        println!("│ Min RTT: {:7.3} ms | Max: {:7.3} ms                      │",
-            stats.min, stats.max);
-        println!("└──────────────────────────────────────────────────────────────┘");
+    outln!("│ Time to close: {:10.3} ms                                  │", close_ms);
+    match &close_frame {
+        Some(frame) => outln!("│ Close code: {} | Reason: {:?}", u16::from(frame.code), frame.reason),
+        None => outln!("│ Closed without a close frame (EOF/reset)                     │"),
     }
+    outln!("└──────────────────────────────────────────────────────────────┘");
 
-    Ok(())
+    let stats = RttStats::new(vec![close_ms]);
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
 }
 
-/// TCP benchmark (no TLS)
-pub async fn run_tcp_benchmark(config: &BenchmarkConfig) -> Result<()> {
-    print_benchmark_header("Rust TCP (no TLS)");
+/// Benchmark 13: half-open connection detection.
+///
+/// Ping once, then block on reads until the connection is declared dead, by TCP
+/// keepalive (--tcp-keepalive-*) or the application read timeout (--timeout-ms,
+/// reset by every received message). Kill the server or drop the link out of
+/// band to measure how long a silent peer goes unnoticed.
+pub async fn run_half_open_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust half-open detection (keepalive / read timeout)";
+    print_benchmark_header(name);
+
+    let keepalive = match config.tcp_keepalive_secs {
+        Some(secs) => format!(
+            "{}s idle, {} interval, {} probes",
+            secs,
+            config.tcp_keepalive_interval_secs.map_or("OS".to_string(), |secs| format!("{}s", secs)),
+            config.tcp_keepalive_retries.map_or("OS".to_string(), |retries| retries.to_string())
+        ),
+        None => "off".to_string(),
+    };
+    let read_timeout = config.timeout_ms.map_or("off".to_string(), |ms| format!("{} ms", ms));
+    outln!("│ {:61}│", format!("TCP keepalive: {}", keepalive));
+    outln!("│ {:61}│", format!("Read timeout: {}", read_timeout));
+    if config.tcp_keepalive_secs.is_none() && config.timeout_ms.is_none() {
+        outln!("│ Warning: neither set, a silently dead peer goes unnoticed    │");
+    }
 
     let mut cpu = CpuTime::new();
     cpu.start();
 
-    let mut socket = tokio::net::TcpStream::connect((config.host.as_str(), config.port))
-        .await
-        .context("Failed to connect")?;
-
-    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
+    let url = config.ws_url(&config.host);
+    let mut ws_stream = connect_with_retries(&url, config).await?;
+
+    let ping_start = Instant::now();
+    ping_pong(&mut ws_stream, config, &config.ping_payload()).await?;
+    outln!("│ Initial ping: {:7.3} ms, waiting for the peer to die...     │",
+        ping_start.elapsed().as_secs_f64() * 1000.0);
+
+    let start = Instant::now();
+    let cause = loop {
+        let next = match config.timeout_ms {
+            Some(ms) => match tokio::time::timeout(Duration::from_millis(ms), ws_stream.next()).await {
+                Ok(next) => next,
+                Err(_) => break format!("read timeout, nothing received for {} ms", ms),
+            },
+            None => ws_stream.next().await,
+        };
+        match next {
+            Some(Ok(Message::Close(frame))) => {
+                break match frame {
+                    Some(frame) => format!("server closed with code {}", u16::from(frame.code)),
+                    None => "server closed".to_string(),
+                };
+            }
+            // Anything else from the server (e.g. its pings) proves the peer is alive
+            Some(Ok(_)) => {}
+            Some(Err(e)) => break e.to_string(),
+            None => break "EOF".to_string(),
+        }
+    };
+    let detect_ms = start.elapsed().as_secs_f64() * 1000.0;
+    cpu.stop();
 
-    for i in 0..config.ping_pong_count {
-        let start = Instant::now();
+    outln!("│ Declared dead after: {:12.3} ms                         │", detect_ms);
+    outln!("│ {:61}│", format!("Cause: {}", cause));
+    outln!("└──────────────────────────────────────────────────────────────┘");
 
-        socket.write_all(PING_MESSAGE).await?;
+    let stats = RttStats::new(vec![detect_ms]);
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
 
-        let mut buf = vec![0u8; BUFFER_SIZE];
-        let n = socket.read(&mut buf).await?;
+/// What arrived for benchmark 14
+struct PushedMessage {
+    opcode: u8,
+    /// Frames the message arrived in
+    frames: usize,
+    /// Payload bytes as sent (compressed, if the message was)
+    payload_bytes: usize,
+    /// Payload bytes after inflating
+    message_bytes: usize,
+    /// Bytes read off the connection after the handshake, headers and control frames included
+    wire_bytes: usize,
+    first_frame_ms: f64,
+    complete_ms: f64,
+}
 
-        let end = start.elapsed().as_millis() as f64;
-        if n > 0 {
-            rtts.push(end);
+/// Read until the first complete data message has arrived, reassembling fragments
+fn receive_pushed_message<S: Read + Write>(stream: &mut S, config: &BenchmarkConfig) -> Result<PushedMessage> {
+    let mut buffer = FrameBuffer::new(config.max_frame_size);
+    let mut inflater = if config.no_handshake {
+        None
+    } else {
+        upgrade_sync(stream, config, &mut buffer)?
+    };
+
+    let start = Instant::now();
+    let mut stream = RetryIo(stream);
+    let mut assembler = MessageAssembler::new(config.max_frame_size);
+    let mut wire_bytes = 0;
+    let mut first_frame_ms = None;
+
+    let message = loop {
+        let mut frame_data = buffer.read_frame(&mut stream).context("Failed to read pushed message")?;
+        first_frame_ms.get_or_insert_with(|| start.elapsed().as_secs_f64() * 1000.0);
+        wire_bytes += frame_data.len();
+
+        let Some(frame) = check_response_frame(&mut frame_data, config, inflater.is_some())? else {
+            continue;
+        };
+        match frame.opcode {
+            0x8 => anyhow::bail!(
+                "Server closed the connection after {} payload bytes, before the message completed",
+                assembler.buffered()
+            ),
+            // Keep the connection healthy while a large message is still streaming in
+            0x9 => {
+                let pong = WebSocketFrame::create_frame(0x8A, frame.payload);
+                stream.write_all(&pong).context("Failed to answer server PING")?;
+            }
+            0xA => {}
+            _ => {
+                if let Some(message) = assembler.push(&frame)? {
+                    break message;
+                }
+            }
+        }
+    };
+    let complete_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    // Fragments can split code points, so text is only checked once the message is whole
+    let message_bytes = match inflater.as_mut() {
+        Some(inflater) => {
+            let payload = inflater
+                .decode(message.rsv1, &message.payload)
+                .context("Failed to inflate pushed message")?;
+            check_message_text(message.opcode, &payload, config)?;
+            payload.len()
         }
+        None => {
+            check_message_text(message.opcode, &message.payload, config)?;
+            message.payload.len()
+        }
+    };
+
+    Ok(PushedMessage {
+        opcode: message.opcode,
+        frames: message.frames,
+        payload_bytes: message.payload.len(),
+        message_bytes,
+        wire_bytes,
+        first_frame_ms: first_frame_ms.unwrap_or(complete_ms),
+        complete_ms,
+    })
+}
 
-        print_benchmark_result(i + 1, end);
+/// Benchmark 14: time to receive a large server-pushed message.
+///
+/// Connect and upgrade, then send nothing and read until the server's first
+/// data message (e.g. a snapshot) has fully arrived, reassembling fragments
+/// and answering pings along the way. The clock starts when the handshake
+/// completes.
+pub fn run_server_push_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust server push (receive first message)";
+    print_benchmark_header(name);
+
+    let mut cpu = CpuTime::new();
+    cpu.start();
+
+    let tcp_stream = connect_tcp_sync(config).context("Failed to connect TCP")?;
+    tcp_stream.set_nodelay(true)?;
+    if let Some(ms) = config.timeout_ms {
+        tcp_stream.set_read_timeout(Some(Duration::from_millis(ms)))?;
     }
 
+    let push = if config.tls {
+        let mut tls_stream = tls_connector()?
+            .connect(&config.host, tcp_stream)
+            .context("Failed to connect TLS")?;
+        receive_pushed_message(&mut tls_stream, config)?
+    } else {
+        let mut tcp_stream = tcp_stream;
+        receive_pushed_message(&mut tcp_stream, config)?
+    };
     cpu.stop();
-    let stats = RttStats::new(rtts.clone());
-    stats.print_rtt_stats();
-    RttStats::print_cpu_time(&cpu, stats.count);
-    println!("└──────────────────────────────────────────────────────────────┘");
 
-    Ok(())
+    let secs = push.complete_ms / 1000.0;
+    let throughput = if secs > 0.0 { push.wire_bytes as f64 / secs } else { 0.0 };
+    let kind = if push.opcode == 0x1 { "text" } else { "binary" };
+
+    outln!("│ {:61}│", format!("Message: {} bytes {} in {} frame(s)", push.payload_bytes, kind, push.frames));
+    if push.message_bytes != push.payload_bytes {
+        outln!("│ {:61}│", format!("Inflated: {} bytes", push.message_bytes));
+    }
+    outln!("│ {:61}│", format!("Received: {} bytes on the wire", push.wire_bytes));
+    outln!("│ First frame: {:12.3} ms                                 │", push.first_frame_ms);
+    outln!("│ Complete:    {:12.3} ms                                 │", push.complete_ms);
+    outln!("│ {:61}│", format!(
+        "Throughput: {:.2} MB/s ({:.1} Mbit/s)",
+        throughput / 1_000_000.0,
+        throughput * 8.0 / 1_000_000.0
+    ));
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    let stats = RttStats::new(vec![push.complete_ms]);
+    let mut report = BenchmarkReport::new(name, config, &stats, &cpu);
+    report.transfer = Some(TransferSummary {
+        bytes: push.wire_bytes as u64,
+        message_bytes: push.message_bytes as u64,
+        frames: push.frames,
+        first_frame_ms: push.first_frame_ms,
+        bytes_per_sec: throughput,
+    });
+    Ok(report)
 }
 
-/// UDP benchmark
-pub async fn run_udp_benchmark(config: &BenchmarkConfig) -> Result<()> {
-    print_benchmark_header("Rust UDP");
+/// Benchmark 12: connection setup rate.
+///
+/// `clients` workers repeatedly connect, complete the handshake and close as
+/// fast as they can until `ping_pong_count` connections have been attempted.
+pub async fn run_connection_rate_benchmark(config: &BenchmarkConfig) -> Result<BenchmarkReport> {
+    let name = "Rust connection rate (connect + handshake + close)";
+    print_benchmark_header(name);
+    outln!("│ Concurrency: {:8} | Connections: {:10}              │",
+        config.clients, config.ping_pong_count);
+
+    ensure_fd_limit(config.clients as u64 + FD_OVERHEAD).map_err(anyhow::Error::msg)?;
 
     let mut cpu = CpuTime::new();
     cpu.start();
 
-    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
-    let udp_port = config.port + 2; // Use port 8445 for UDP
+    let url = config.ws_url(&config.host);
+    let next_attempt = Arc::new(AtomicU32::new(0));
+    let mut handles = Vec::with_capacity(config.clients as usize);
+
+    for _ in 0..config.clients.max(1) {
+        let next_attempt = next_attempt.clone();
+        let url = url.clone();
+        let host = config.host.clone();
+        let port = config.port;
+        let options = SocketOptions::from_config(config);
+        let origin = config.origin.clone();
+        let close = config.close_frame();
+        let total = config.ping_pong_count;
+
+        handles.push(tokio::spawn(async move {
+            let mut handshake_times = Vec::new();
+            let mut failures = 0u32;
+            let mut timed_out = 0u32;
+            let mut forbidden = false;
+            while next_attempt.fetch_add(1, Ordering::Relaxed) < total {
+                let start = Instant::now();
+                match connect_ws(&url, &host, port, &options, origin.as_deref()).await {
+                    Ok(mut ws_stream) => {
+                        handshake_times.push(start.elapsed().as_secs_f64() * 1000.0);
+                        close_ws(&mut ws_stream, close.clone()).await;
+                    }
+                    // Every further attempt would be refused the same way
+                    Err(e) if is_forbidden(&e) => {
+                        failures += 1;
+                        forbidden = true;
+                        break;
+                    }
+                    Err(e) => {
+                        if is_connect_timeout(&e) {
+                            timed_out += 1;
+                        } else {
+                            failures += 1;
+                        }
+                        tracing::debug!(error = %e, "connection attempt failed");
+                    }
+                }
+            }
+            (handshake_times, failures, timed_out, forbidden)
+        }));
+    }
 
-    let mut rtts = Vec::with_capacity(config.ping_pong_count as usize);
+    let mut handshake_times = Vec::with_capacity(config.ping_pong_count as usize);
+    let mut failures = 0;
+    let mut timed_out = 0;
+    let mut forbidden = false;
+    for handle in handles {
+        let (times, failed, worker_timed_out, worker_forbidden) =
+            handle.await.context("Connection worker panicked")?;
+        handshake_times.extend(times);
+        failures += failed;
+        timed_out += worker_timed_out;
+        forbidden |= worker_forbidden;
+    }
 
-    for i in 0..config.ping_pong_count {
-        let start = Instant::now();
+    if forbidden {
+        return Err(forbidden_error(config));
+    }
+
+    cpu.stop();
 
-        socket.send_to(PING_MESSAGE, (config.host.as_str(), udp_port)).await?;
+    let stats = RttStats::new(handshake_times);
+    let rate = if cpu.wall_time > 0.0 {
+        stats.count as f64 / cpu.wall_time
+    } else {
+        0.0
+    };
+    stats.print_rtt_stats();
+    outln!("│   P50: {:7.3} ms | P99:    {:7.3} ms                       │",
+        stats.percentile(50.0), stats.percentile(99.0));
+    outln!("│ Connections/sec: {:10.1}                                  │", rate);
+    print_completed(stats.count as u64, config.ping_pong_count as u64, failures as u64, timed_out as u64);
+    RttStats::print_cpu_time(&cpu, stats.count);
+    outln!("└──────────────────────────────────────────────────────────────┘");
 
-        let mut buf = vec![0u8; BUFFER_SIZE];
-        let n = socket.recv(&mut buf).await?;
+    if stats.count == 0 {
+        anyhow::bail!("All {} connection attempts failed", failures + timed_out);
+    }
 
-        let end = start.elapsed().as_millis() as f64;
-        if n > 0 {
-            rtts.push(end);
-        }
+    Ok(BenchmarkReport::new(name, config, &stats, &cpu))
+}
 
-        print_benchmark_result(i + 1, end);
+/// Run the full-duplex (pipelined) benchmark once per payload size and tabulate throughput
+pub async fn run_payload_sweep(config: &BenchmarkConfig) -> Result<Vec<BenchmarkReport>> {
+    let mut reports = Vec::with_capacity(config.payload_sweep.len());
+
+    for &size in &config.payload_sweep {
+        // The sequence/timestamp header counts toward the swept size
+        let sweep_config = BenchmarkConfig {
+            payload_size: Some(size.saturating_sub(PAYLOAD_HEADER_LEN)),
+            payload: None,
+            ..config.clone()
+        };
+        reports.push(run_full_duplex_benchmark(&sweep_config).await?);
+        outln!();
     }
 
-    cpu.stop();
-    let stats = RttStats::new(rtts.clone());
-    stats.print_rtt_stats();
-    RttStats::print_cpu_time(&cpu, stats.count);
-    println!("└──────────────────────────────────────────────────────────────┘");
+    outln!("┌──────────────────────────────────────────────────────────────┐");
+    outln!("│ Payload Sweep (full-duplex, {} ping-pong per size)            │", config.ping_pong_count);
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│   Payload (B) |     msg/sec |       MB/s | Avg RTT (ms)       │");
+    for (&size, report) in config.payload_sweep.iter().zip(&reports) {
+        let mb_per_sec = report.summary.throughput * size.max(PAYLOAD_HEADER_LEN) as f64 / 1_000_000.0;
+        outln!("│   {:>11} | {:>11.0} | {:>10.2} | {:>12.3}       │",
+            size, report.summary.throughput, mb_per_sec, report.summary.avg);
+    }
+    outln!("└──────────────────────────────────────────────────────────────┘");
 
-    Ok(())
+    Ok(reports)
 }
 
 #[cfg(test)]
@@ -388,4 +3306,293 @@ mod tests {
         assert_eq!(config.port, 8443);
         assert_eq!(config.ping_pong_count, 30);
     }
+
+    #[test]
+    fn test_connect_timeouts_are_told_apart() {
+        let timed_out = WsError::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out"));
+        let refused = WsError::Io(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        assert!(is_connect_timeout(&timed_out));
+        assert!(!is_connect_timeout(&refused));
+        assert!(!is_connect_timeout(&WsError::ConnectionClosed));
+    }
+
+    #[test]
+    fn test_forbidden_handshakes_are_recognised() {
+        let response = |status: u16| {
+            tokio_tungstenite::tungstenite::http::Response::builder()
+                .status(status)
+                .body(None)
+                .unwrap()
+        };
+        assert!(is_forbidden(&WsError::Http(response(403))));
+        assert!(!is_forbidden(&WsError::Http(response(404))));
+        assert!(!is_forbidden(&WsError::ConnectionClosed));
+    }
+
+    #[test]
+    fn test_seeded_mask_sources_differ_per_client() {
+        let config = BenchmarkConfig {
+            seed: Some(7),
+            ..BenchmarkConfig::default()
+        };
+        let masks = |stream| {
+            let mut source = config.mask_source(stream);
+            [source.next_mask(), source.next_mask()]
+        };
+        assert_eq!(masks(0), masks(0));
+        assert_ne!(masks(0), masks(1));
+    }
+
+    #[test]
+    fn test_runtime_workers() {
+        let pinned = BenchmarkConfig {
+            cpu_affinity: vec![2, 3],
+            ..BenchmarkConfig::default()
+        };
+        assert_eq!(pinned.runtime_workers(), Some(2));
+
+        let explicit = BenchmarkConfig {
+            runtime_threads: Some(6),
+            ..pinned.clone()
+        };
+        assert_eq!(explicit.runtime_workers(), Some(6));
+
+        let current = BenchmarkConfig {
+            current_thread: true,
+            ..BenchmarkConfig::default()
+        };
+        assert_eq!(current.runtime_workers(), None);
+        assert_eq!(current.runtime_description(), "tokio current-thread");
+    }
+
+    #[test]
+    fn test_reject_http_reply() {
+        let frame = WebSocketFrame::create_text_frame(b"PONG");
+        assert!(reject_http_reply(&frame).is_ok());
+        assert!(reject_http_reply(b"").is_ok());
+
+        let err = reject_http_reply(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").unwrap_err();
+        assert!(err.to_string().contains("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_bad_frame() {
+        // FIN + reserved opcode 0x3, empty payload
+        let mut bad_frame = [0x83, 0x00];
+
+        let lenient = BenchmarkConfig::default();
+        assert!(check_response_frame(&mut bad_frame, &lenient, false).is_ok());
+
+        let strict = BenchmarkConfig {
+            strict: true,
+            ..BenchmarkConfig::default()
+        };
+        assert!(check_response_frame(&mut bad_frame, &strict, false).is_err());
+    }
+
+    #[test]
+    fn test_payload_kinds() {
+        let config = BenchmarkConfig {
+            payload_size: Some(64),
+            ..BenchmarkConfig::default()
+        };
+        assert_eq!(&config.ping_payload()[..8], b"PINGPING");
+
+        let zeros = BenchmarkConfig {
+            payload_kind: PayloadKind::Zeros,
+            ..config.clone()
+        };
+        assert_eq!(zeros.ping_payload(), vec![0; 64]);
+
+        let random = BenchmarkConfig {
+            payload_kind: PayloadKind::Random,
+            seed: Some(7),
+            ..config
+        };
+        let payload = random.ping_payload();
+        assert_eq!(payload.len(), 64);
+        assert_eq!(payload, random.ping_payload());
+        assert!(payload.iter().any(|&b| b != payload[0]));
+    }
+
+    #[test]
+    fn test_full_jitter_backoff() {
+        let config = BenchmarkConfig {
+            connect_retries: 20,
+            retry_backoff_ms: 100,
+            seed: Some(7),
+            ..BenchmarkConfig::default()
+        };
+
+        let mut backoff = Backoff::new(&config, 3);
+        let delays: Vec<Duration> = std::iter::from_fn(|| backoff.next_delay()).collect();
+        assert_eq!(delays.len(), 20);
+        for (attempt, delay) in delays.iter().enumerate() {
+            let window = (100u64 << attempt.min(16)).min(MAX_BACKOFF_MS);
+            assert!(delay.as_millis() as u64 <= window);
+        }
+        assert_eq!(backoff.stats().retries, 20);
+        assert_eq!(backoff.stats().max_attempts, 21);
+
+        // Same seed and client: same schedule; another client: a different one
+        let mut same = Backoff::new(&config, 3);
+        let mut other = Backoff::new(&config, 4);
+        let same_delays: Vec<Duration> = std::iter::from_fn(|| same.next_delay()).collect();
+        let other_delays: Vec<Duration> = std::iter::from_fn(|| other.next_delay()).collect();
+        assert_eq!(delays, same_delays);
+        assert_ne!(delays, other_delays);
+    }
+
+    #[test]
+    fn test_strict_response_fails_on_truncated_echo() {
+        let mut responses = ResponseCheck::default();
+        assert!(responses.accept(1, 4, 4));
+        assert!(!responses.accept(2, 2, 4));
+        assert_eq!(responses.mismatched, 1);
+
+        assert!(responses.check(&BenchmarkConfig::default()).is_ok());
+        let strict = BenchmarkConfig {
+            strict_response: true,
+            ..BenchmarkConfig::default()
+        };
+        assert!(responses.check(&strict).is_err());
+    }
+
+    #[test]
+    fn test_circuit_breaker_resets_on_fast_iteration() {
+        let config = BenchmarkConfig {
+            abort_above_ms: Some(100.0),
+            abort_after: 3,
+            ..BenchmarkConfig::default()
+        };
+        let mut monitor = RunMonitor::new(&config);
+
+        assert!(!monitor.on_iteration(1, 150.0));
+        assert!(!monitor.on_iteration(2, 150.0));
+        assert!(!monitor.on_iteration(3, 50.0));
+        assert!(!monitor.on_iteration(4, 150.0));
+        assert!(!monitor.on_iteration(5, 150.0));
+        assert!(monitor.check_aborted().is_ok());
+
+        assert!(monitor.on_iteration(6, 150.0));
+        assert!(monitor.check_aborted().is_err());
+        assert_eq!(monitor.completed, 6);
+    }
+
+    #[test]
+    fn test_send_lateness_feeds_corrected_stats() {
+        let config = BenchmarkConfig {
+            interval_ms: Some(5.0),
+            ..BenchmarkConfig::default()
+        };
+        let mut monitor = RunMonitor::new(&config);
+
+        // The first send is on time; its response stalls for 30 ms, past the next slot
+        assert_eq!(monitor.next_send_delay(), Some(Duration::ZERO));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!monitor.on_iteration(1, 30.0));
+
+        // The second send was due 5 ms in, so it leaves about 25 ms late
+        assert_eq!(monitor.next_send_delay(), Some(Duration::ZERO));
+        assert!(!monitor.on_iteration(2, 1.0));
+
+        // A failed iteration's lateness isn't carried over to the next sample
+        monitor.next_send_delay();
+        monitor.on_failure(true);
+        assert!(!monitor.on_iteration(3, 1.0));
+
+        assert_eq!(monitor.paced_rtts, vec![30.0, 1.0]);
+        assert!(monitor.paced_lateness[0] < 5.0);
+        assert!(monitor.paced_lateness[1] >= 25.0);
+
+        let corrected = RttStats::new(co_corrected(&monitor.paced_rtts, &monitor.paced_lateness));
+        assert!(corrected.max >= 30.0);
+        assert!(corrected.min >= 26.0, "the late send's RTT hides the stall: {:?}", corrected.rtts);
+    }
+
+    #[test]
+    fn test_measure_window_trims_ramp_and_drain() {
+        let config = BenchmarkConfig {
+            measure_window: Some(crate::utils::parse_measure_window("0.2,0.8").unwrap()),
+            ..BenchmarkConfig::default()
+        };
+        let mut monitor = RunMonitor::new(&config);
+        monitor.sample_offsets = (0..=10).map(f64::from).collect();
+
+        let rtts: Vec<f64> = (0..=10).map(|i| 100.0 + f64::from(i)).collect();
+        let kept = monitor.in_window(rtts);
+        assert_eq!(kept, vec![102.0, 103.0, 104.0, 105.0, 106.0, 107.0, 108.0]);
+        assert!(monitor.window_summary.as_deref().unwrap().ends_with("7/11 samples"));
+    }
+
+    /// Replays canned server bytes and collects what the client writes
+    struct ScriptedStream {
+        input: std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            std::io::Read::read(&mut self.input, buf)
+        }
+    }
+
+    impl Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const SWITCHING_PROTOCOLS: &[u8] =
+        b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+
+    #[test]
+    fn test_upgrade_keeps_frames_behind_the_response() {
+        // Server frames are unmasked: FIN + binary, 3-byte payload
+        let snapshot = [0x82, 0x03, 1, 2, 3];
+        let mut stream = ScriptedStream {
+            input: std::io::Cursor::new([SWITCHING_PROTOCOLS, &snapshot].concat()),
+            output: Vec::new(),
+        };
+        let config = BenchmarkConfig::default();
+        let mut buffer = FrameBuffer::new(config.max_frame_size);
+
+        assert!(upgrade_sync(&mut stream, &config, &mut buffer).unwrap().is_none());
+        assert!(stream.output.starts_with(b"GET "));
+        assert_eq!(buffer.pending(), snapshot);
+        assert_eq!(buffer.read_frame(&mut stream).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_receive_pushed_message_reassembles_fragments() {
+        let frames: [&[u8]; 3] = [
+            &[0x02, 0x04, b's', b'n', b'a', b'p'],
+            // A ping between fragments gets answered
+            &[0x89, 0x02, b'h', b'i'],
+            &[0x80, 0x04, b's', b'h', b'o', b't'],
+        ];
+        let mut input = SWITCHING_PROTOCOLS.to_vec();
+        for frame in frames {
+            input.extend_from_slice(frame);
+        }
+        let mut stream = ScriptedStream {
+            input: std::io::Cursor::new(input),
+            output: Vec::new(),
+        };
+
+        let push = receive_pushed_message(&mut stream, &BenchmarkConfig::default()).unwrap();
+        assert_eq!(push.opcode, 0x2);
+        assert_eq!(push.frames, 2);
+        assert_eq!(push.payload_bytes, 8);
+        assert_eq!(push.message_bytes, 8);
+        assert_eq!(push.wire_bytes, frames.iter().map(|frame| frame.len()).sum::<usize>());
+
+        let request_end = stream.output.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+        assert_eq!(stream.output[request_end], 0x8A);
+    }
 }
@@ -1,8 +1,22 @@
-use crate::benchmark::{self, BenchmarkConfig};
+use crate::benchmark::{self, BenchmarkConfig, PayloadKind};
+use crate::compression::DeflateParams;
+use crate::mix;
+use crate::pacing::Arrival;
+use crate::profile::Profile;
+use crate::report::{self, BenchmarkReport, OutputCompression, OutputFormat};
+use crate::stats::MeasureWindow;
+use crate::trace;
+use crate::output;
+use crate::utils;
+use crate::websocket::DEFAULT_MAX_PAYLOAD;
 use crate::QUIET_MODE;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
-use std::io::{self, Write};
+use futures_util::FutureExt;
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use tracing::Instrument;
 use std::sync::atomic::Ordering;
 
 /// WebSocket Ping-Pong Benchmark CLI
@@ -11,68 +25,445 @@ use std::sync::atomic::Ordering;
 #[command(author = "Benchmark Team")]
 #[command(version = "1.0")]
 #[command(about = "WebSocket Ping-Pong Benchmark in Rust", long_about = None)]
+// -h is --host, so help is only available as --help
+#[command(disable_help_flag = true)]
 pub struct Args {
-    /// Benchmark number to run (1-19)
+    /// Print help
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+
+    /// Benchmark number to run (1-14)
     #[arg(short, long, value_name = "NUM")]
     pub benchmark: Option<u8>,
 
-    /// Server hostname
+    /// Server hostname (repeat to round-robin multi-connection clients)
     #[arg(short, long, default_value = "10.25.96.5", value_name = "HOST")]
-    pub host: String,
+    pub host: Vec<String>,
+
+    /// Full target URL, e.g. wss://host:8443/ws (overrides --host and --port)
+    #[arg(long, value_name = "URL")]
+    pub url: Option<String>,
+
+    /// Connect over plaintext ws:// instead of wss://
+    #[arg(long)]
+    pub no_tls: bool,
+
+    /// Mark outgoing TCP/UDP packets with this DSCP value (IP_TOS / IPV6_TCLASS)
+    #[arg(long, value_name = "0-63", value_parser = clap::value_parser!(u8).range(0..=63))]
+    pub dscp: Option<u8>,
+
+    /// Connect to IP for HOST instead of resolving it; Host header and SNI keep HOST (repeatable)
+    #[arg(long, value_name = "HOST:IP", value_parser = utils::parse_resolve)]
+    pub resolve: Vec<(String, IpAddr)>,
+
+    /// Publish each RTT to a POSIX shared-memory ring for live readers (needs the shm
+    /// feature; single-connection benchmarks)
+    #[arg(long, value_name = "NAME")]
+    pub shm: Option<String>,
+
+    /// Send this Origin header with the opening handshake, for servers that check it
+    #[arg(long, value_name = "ORIGIN", value_parser = utils::parse_origin)]
+    pub origin: Option<String>,
+
+    /// Fall back to ws:// when the wss:// TLS handshake fails
+    #[arg(long)]
+    pub auto_scheme: bool,
 
     /// Server port
     #[arg(short, long, default_value_t = 8443, value_name = "PORT")]
     pub port: u16,
 
-    /// Number of ping-pong iterations
-    #[arg(short, long, default_value_t = 30, value_name = "COUNT")]
-    pub count: u32,
+    /// Number of ping-pong iterations (at least 1) [default: 30]
+    #[arg(short, long, value_name = "COUNT")]
+    pub count: Option<u32>,
+
+    /// Start from a preset (latency, throughput, soak); explicit flags override it
+    #[arg(long, value_enum, value_name = "NAME")]
+    pub profile: Option<Profile>,
 
     /// Quiet mode (disable per-iteration logging)
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_quiet")]
     pub quiet: bool,
 
+    /// Keep per-iteration logging even if the profile turns on --quiet
+    #[arg(long, overrides_with = "quiet")]
+    pub no_quiet: bool,
+
+    /// Connect, do a single ping-pong and print only the RTT in milliseconds
+    #[arg(long)]
+    pub once: bool,
+
+    /// Only connect and validate the WebSocket handshake (health check), then exit
+    #[arg(long, conflicts_with_all = ["once", "benchmark"])]
+    pub validate_handshake_only: bool,
+
+    /// Probe the largest ping payload the server accepts (doubling, then binary search), then exit
+    #[arg(long, conflicts_with_all = ["once", "benchmark", "validate_handshake_only"])]
+    pub find_max_frame: bool,
+
+    /// Time frame creation and masking for 64 B to 1 MiB payloads without any network I/O, then exit
+    #[arg(long, conflicts_with_all = ["once", "benchmark", "validate_handshake_only", "find_max_frame"])]
+    pub masking_bench: bool,
+
+    /// Survey the ws:// / wss:// URLs listed in FILE (one per line, -c pings each), then exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["once", "benchmark", "validate_handshake_only", "find_max_frame"])]
+    pub targets: Option<PathBuf>,
+
+    /// How many --targets endpoints to benchmark at once
+    #[arg(long, default_value_t = 1, value_name = "N", requires = "targets", value_parser = clap::value_parser!(u32).range(1..))]
+    pub targets_concurrency: u32,
+
+    /// Per-ping response timeout (also bounds the whole --once attempt)
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout_ms: Option<u64>,
+
+    /// Limit on TCP connect plus TLS/WebSocket handshake; a timeout is a failed (retryable) connect
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub connect_timeout_ms: Option<u64>,
+
+    /// Pace pings with this mean gap between sends (milliseconds)
+    #[arg(long, value_name = "MS", value_parser = utils::parse_interval)]
+    pub interval_ms: Option<f64>,
+
+    /// Send protocol Ping control frames at this interval to keep idle paths alive
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub keepalive_ping_ms: Option<u64>,
+
+    /// Inter-arrival distribution used with --interval-ms
+    #[arg(long, value_enum, default_value_t = Arrival::Constant)]
+    pub arrival: Arrival,
+
+    /// Benchmark 9 sends on the --interval-ms schedule without waiting for responses
+    /// and reports latency from the scheduled send time (coordinated-omission-corrected)
+    #[arg(long)]
+    pub open_loop: bool,
+
+    /// Seed for reproducible random schedules, payloads, retry backoff and frame masks
+    #[arg(long, value_name = "SEED")]
+    pub seed: Option<u64>,
+
+    /// Print a progress line (iterations, avg RTT, throughput) every N seconds from a
+    /// background thread reading atomic counters, keeping the ping loop free of printing
+    #[arg(long, value_name = "SECS", value_parser = utils::parse_interval)]
+    pub report_interval: Option<f64>,
+
+    /// Report how evenly responses arrive: min/avg/max/stddev of the gaps between them
+    /// (not benchmarks 6 and 11-14, which don't time individual responses)
+    #[arg(long)]
+    pub response_cadence: bool,
+
+    /// Only count samples completed in START,END of the run, each a fraction (0.1) or
+    /// seconds (10s) from the first send, e.g. 0.1,0.9 drops ramp-up and drain
+    #[arg(long, value_name = "START,END", value_parser = utils::parse_measure_window)]
+    pub measure_window: Option<MeasureWindow>,
+
+    /// Stay idle for SECS after the handshake, then report the first (cold) RTT
+    /// against the steady-state average (benchmarks 1, 2 and async)
+    #[arg(long = "idle-before", value_name = "SECS", value_parser = utils::parse_secs)]
+    pub idle_before_secs: Option<f64>,
+
+    /// Keep only running avg/stddev/min/max instead of every RTT sample, for O(1) memory on
+    /// long runs; median and percentiles aren't reported (benchmarks 1, 2, 5, 7-10 without --open-loop)
+    #[arg(long, conflicts_with_all = ["measure_window", "idle_before_secs"])]
+    pub no_percentiles: bool,
+
+    /// Mask the ping frame once and resend the same bytes every iteration, so RTT excludes
+    /// client framing (benchmarks 2 and 6). RFC 6455 requires a fresh mask per frame: only
+    /// for lenient or internal servers, and not through intermediaries that check masks
+    #[arg(long, conflicts_with_all = ["mix", "trace"])]
+    pub static_frame: bool,
+
+    /// Pin benchmark threads (the main thread and the runtime workers, not the blocking
+    /// pool) to these CPU cores (Linux), e.g. 2,3,4,5
+    #[arg(long, value_delimiter = ',', value_name = "CORES")]
+    pub cpu_affinity: Vec<usize>,
+
+    /// Size in bytes of the generated ping payload
+    #[arg(long, value_name = "BYTES", conflicts_with_all = ["payload_file", "payload_stdin"])]
+    pub payload_size: Option<usize>,
+
+    /// Run the full-duplex benchmark (9) once per payload size, e.g. 64,256,1024
+    #[arg(long, value_delimiter = ',', value_name = "SIZES", conflicts_with_all = ["payload_file", "payload_stdin"])]
+    pub payload_sweep: Vec<usize>,
+
+    /// Content of the generated payload: text and zeros compress well, random doesn't
+    /// (random needs --binary)
+    #[arg(long, value_enum, default_value_t = PayloadKind::Text, conflicts_with_all = ["mix", "trace", "payload_file", "payload_stdin"])]
+    pub payload_kind: PayloadKind,
+
+    /// Weighted mix of payload sizes picked per ping, e.g. "64:70,1024:25,16384:5" (SIZE:WEIGHT;
+    /// benchmarks 1, 2 and 5)
+    #[arg(long, value_name = "SPEC", conflicts_with_all = ["payload_size", "payload_file", "payload_stdin"])]
+    pub mix: Option<String>,
+
+    /// Replay message sizes from a trace file (one SIZE or OFFSET_MS,SIZE per line); sets the count
+    /// (benchmarks 1 and 5)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["mix", "payload_size", "payload_file", "payload_stdin"])]
+    pub trace: Option<PathBuf>,
+
+    /// Sleep to reproduce the trace's recorded inter-arrival gaps
+    #[arg(long, requires = "trace")]
+    pub trace_timing: bool,
+
+    /// Send the raw contents of this file as the ping payload
+    #[arg(long, value_name = "PATH", conflicts_with = "payload_stdin")]
+    pub payload_file: Option<PathBuf>,
+
+    /// Read the raw ping payload from stdin
+    #[arg(long)]
+    pub payload_stdin: bool,
+
+    /// Send binary frames (opcode 0x2) instead of text frames
+    #[arg(long)]
+    pub binary: bool,
+
+    /// Log level filter (overridden by RUST_LOG), e.g. warn, info, debug
+    #[arg(long, default_value = "warn", value_name = "LEVEL")]
+    pub log_level: String,
+
     /// Run multi-connection test
     #[arg(long)]
     pub multi: bool,
+
+    /// Number of clients in the multi-connection benchmark [default: 50]
+    #[arg(long, value_name = "N")]
+    pub clients: Option<u32>,
+
+    /// Status code of the close frame that ends each connection (RFC 6455 sendable codes only)
+    #[arg(long, default_value = "1000", value_name = "CODE", value_parser = utils::parse_close_code)]
+    pub close_code: u16,
+
+    /// Reason text of the close frame (at most 123 bytes)
+    #[arg(long, default_value = "", value_name = "TEXT", value_parser = utils::parse_close_reason)]
+    pub close_reason: String,
+
+    /// Most multi-connection clients connecting (TCP + TLS + upgrade) at the same time
+    #[arg(long, default_value_t = 100, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub connect_concurrency: u32,
+
+    /// Ping-pongs per client in the multi-connection benchmark (defaults to --count)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub per_connection: Option<u32>,
+
+    /// Reconnect and continue when the connection drops mid-run (benchmarks 1 and 5)
+    #[arg(long, overrides_with = "no_reconnect")]
+    pub reconnect: bool,
+
+    /// Don't reconnect even if the profile turns on --reconnect
+    #[arg(long, overrides_with = "reconnect")]
+    pub no_reconnect: bool,
+
+    /// Number of connection retries before giving up (per client in multi-connection mode)
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub connect_retries: u32,
+
+    /// Retries of a failed ping on the same connection before it counts as a failure
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    pub ping_retries: u32,
+
+    /// Base connect-retry backoff: each retry sleeps a random 0..min(30s, base × 2^attempt) ms
+    #[arg(long, default_value_t = 500, value_name = "MS")]
+    pub retry_backoff_ms: u64,
+
+    /// Treat WebSocket protocol violations in responses as hard errors
+    #[arg(long, alias = "min-frame-validation")]
+    pub strict: bool,
+
+    /// Fail the TCP/UDP benchmarks (7, 8) when a response isn't a complete echo
+    #[arg(long)]
+    pub strict_response: bool,
+
+    /// Sample RSS during the run and report memory growth
+    #[arg(long, overrides_with = "no_track_memory")]
+    pub track_memory: bool,
+
+    /// Don't sample RSS even if the profile turns on --track-memory
+    #[arg(long, overrides_with = "track_memory")]
+    pub no_track_memory: bool,
+
+    /// Iterations between RSS samples when tracking memory
+    #[arg(long, default_value_t = 100, value_name = "N")]
+    pub memory_sample_every: u32,
+
+    /// Spread multi-connection client startup evenly over this many seconds
+    #[arg(long, default_value_t = 0.0, value_name = "SECONDS", value_parser = utils::parse_secs)]
+    pub ramp: f64,
+
+    /// Abort when RTT stays above this threshold (milliseconds)
+    #[arg(long, value_name = "MS")]
+    pub abort_above_ms: Option<f64>,
+
+    /// Consecutive iterations above --abort-above-ms before aborting
+    #[arg(long, default_value_t = 5, value_name = "N")]
+    pub abort_after: u32,
+
+    /// Enable TCP keepalive, probing after this many idle seconds
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub tcp_keepalive_secs: Option<u64>,
+
+    /// Seconds between unanswered TCP keepalive probes [default: OS setting]
+    #[arg(long, value_name = "SECS", requires = "tcp_keepalive_secs", value_parser = clap::value_parser!(u64).range(1..))]
+    pub tcp_keepalive_interval_secs: Option<u64>,
+
+    /// Unanswered TCP keepalive probes before the connection is dropped [default: OS setting]
+    #[arg(long, value_name = "N", requires = "tcp_keepalive_secs", value_parser = clap::value_parser!(u32).range(1..))]
+    pub tcp_keepalive_retries: Option<u32>,
+
+    /// Keep TCP_QUICKACK armed on benchmark 2 and 7 sockets to rule out delayed-ACK stalls (Linux)
+    #[arg(long)]
+    pub quickack: bool,
+
+    /// Print the kernel's TCP_INFO (RTT, retransmits, cwnd) after benchmarks 2 and 7 (Linux)
+    #[arg(long)]
+    pub tcp_info: bool,
+
+    /// Bind benchmark sockets to this network interface, e.g. eth1 (Linux, needs CAP_NET_RAW)
+    #[arg(long, value_name = "NAME")]
+    pub interface: Option<String>,
+
+    /// Bind each multi-connection client to its own source port from START-END, so load
+    /// balancers hashing the 5-tuple spread clients across backends; reports per-port stats
+    #[arg(long, value_name = "START-END", value_parser = utils::parse_port_range)]
+    pub source_port_range: Option<(u16, u16)>,
+
+    /// Run benchmark 2 over this already-connected TCP socket instead of connecting, e.g.
+    /// one handed over by a supervising process or fuzzing harness (Unix)
+    #[arg(long, value_name = "N", conflicts_with_all = ["interface", "source_port_range"])]
+    pub fd: Option<i32>,
+
+    /// Skip the HTTP upgrade in benchmark 2 and send frames right after the TLS connect, for
+    /// servers already in WebSocket state or plain framing echo servers
+    #[arg(long, conflicts_with_all = ["compress", "origin"])]
+    pub no_handshake: bool,
+
+    /// Reject received frames declaring a larger payload before allocating for them
+    /// (hand-framed benchmarks) [default: 67108864]
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_frame_size: Option<u64>,
+
+    /// Don't fall back to the legacy x-webkit-deflate extension with --compress
+    #[arg(long)]
+    pub no_legacy_deflate: bool,
+
+    /// Run benchmark 1 as WebSocket over HTTP/2 extended CONNECT (needs the h2 feature)
+    #[arg(long)]
+    pub h2: bool,
+
+    /// Offer permessage-deflate compression (sync TLS benchmark)
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Largest LZ77 window (8-15 bits) the server may assume for our messages
+    #[arg(long, value_name = "BITS", requires = "compress", value_parser = clap::value_parser!(u8).range(8..=15))]
+    pub client_max_window_bits: Option<u8>,
+
+    /// Ask the server to compress with at most this LZ77 window (8-15 bits)
+    #[arg(long, value_name = "BITS", requires = "compress", value_parser = clap::value_parser!(u8).range(8..=15))]
+    pub server_max_window_bits: Option<u8>,
+
+    /// Offer to reset our compression context after every message
+    #[arg(long, requires = "compress")]
+    pub client_no_context_takeover: bool,
+
+    /// Ask the server to reset its compression context after every message
+    #[arg(long, requires = "compress")]
+    pub server_no_context_takeover: bool,
+
+    /// Write results to this file: .csv, .jsonl (JSON lines) or JSON otherwise
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Summary format on stdout: box tables, or a GitHub-flavored Markdown table only
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output_format: OutputFormat,
+
+    /// Compress the --output file; inferred from a .gz / .zst extension, e.g. results.csv.gz
+    #[arg(long, value_enum, value_name = "CODEC", requires = "output")]
+    pub compress_output: Option<OutputCompression>,
+
+    /// Append rows to an existing .csv/.jsonl output instead of overwriting it
+    #[arg(long, requires = "output")]
+    pub append: bool,
+
+    /// Compare results against a previously exported JSON run
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+
+    /// Fail when a metric is worse than --baseline by more than this percentage
+    #[arg(long, default_value_t = 10.0, value_name = "PCT")]
+    pub regression_threshold: f64,
+
+    /// Print nothing on success; on failure or threshold breach dump the full output to stderr
+    #[arg(long)]
+    pub summary_only_on_failure: bool,
+
+    /// Stop "run all" (benchmark 5) at the first failure instead of running the rest
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Tokio worker threads [default: number of CPUs, or of --cpu-affinity cores]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "current_thread")]
+    pub runtime_threads: Option<u64>,
+
+    /// Run everything on a single-threaded tokio runtime (lowest jitter)
+    #[arg(long)]
+    pub current_thread: bool,
+
+    /// Print the JSON schema of the result export and exit
+    #[arg(long)]
+    pub schema: bool,
+
+    /// Write the sync benchmark's per-phase timings as folded stacks (for inferno/flamegraph)
+    #[arg(long, value_name = "PATH")]
+    pub folded: Option<PathBuf>,
+
+    /// Merge exported JSON result files into one combined report and exit
+    #[arg(long, num_args = 1.., value_name = "FILE")]
+    pub merge: Vec<PathBuf>,
 }
 
 fn print_header() {
-    println!("╔══════════════════════════════════════════════════════════════╗");
-    println!("║   Rust WebSocket Ping-Pong Benchmark (TLS/WSS)               ║");
-    println!("╠══════════════════════════════════════════════════════════════╣");
-    println!("║  Connecting to wss://10.25.96.5:8443                        ║");
-    println!("║  Make sure WebSocket server is running!                      ║");
-    println!("╚══════════════════════════════════════════════════════════════╝");
-    println!();
+    outln!("╔══════════════════════════════════════════════════════════════╗");
+    outln!("║   Rust WebSocket Ping-Pong Benchmark (TLS/WSS)               ║");
+    outln!("╠══════════════════════════════════════════════════════════════╣");
+    outln!("║  Connecting to wss://10.25.96.5:8443                        ║");
+    outln!("║  Make sure WebSocket server is running!                      ║");
+    outln!("╚══════════════════════════════════════════════════════════════╝");
+    outln!();
 }
 
 fn show_menu(config: &BenchmarkConfig) {
-    println!("═══════════════════════════════════════════════════════════════");
-    println!("  Rust WebSocket Benchmark (TLS only, wss://{}:{})", config.host, config.port);
-    println!("  {} ping-pong iterations with RTT measurement", config.ping_pong_count);
-    println!("═══════════════════════════════════════════════════════════════");
-    println!();
-    println!("  === Native TLS (userspace TLS) ===");
-    println!("  1. async + Native TLS (tokio)");
-    println!("  2. sync + Native TLS (blocking I/O)");
-    println!();
-    println!("  === Run All Benchmarks ===");
-    println!("  5. Run ALL TLS benchmarks (1-2)");
-    println!();
-    println!("  === Multi-Connection ===");
-    println!("  6. Multi-Connection (50 clients × {} ping-pong)", config.ping_pong_count);
-    println!();
-    println!("  === Other ===");
-    println!("  7. TCP benchmark (no TLS)");
-    println!("  8. UDP benchmark");
-    println!();
-    println!("  97. Toggle ping-pong logging ({})", if QUIET_MODE.load(Ordering::SeqCst) { "OFF" } else { "ON" });
-    println!("  98. Change server address (current: {}:{})", config.host, config.port);
-    println!("  99. Change ping-pong count (current: {})", config.ping_pong_count);
-    println!();
-    println!("  0. Exit");
-    println!("═══════════════════════════════════════════════════════════════");
+    outln!("═══════════════════════════════════════════════════════════════");
+    outln!("  Rust WebSocket Benchmark ({})", config.ws_url(&config.host));
+    outln!("  {} ping-pong iterations with RTT measurement", config.ping_pong_count);
+    outln!("═══════════════════════════════════════════════════════════════");
+    outln!();
+    outln!("  === Native TLS (userspace TLS) ===");
+    outln!("  1. async + Native TLS (tokio)");
+    outln!("  2. sync + Native TLS (blocking I/O)");
+    outln!();
+    outln!("  === Run All Benchmarks ===");
+    outln!("  5. Run ALL TLS benchmarks (1-2)");
+    outln!();
+    outln!("  === Multi-Connection ===");
+    outln!("  6. Multi-Connection ({} clients × {} ping-pong)", config.clients, config.per_connection());
+    outln!();
+    outln!("  === Other ===");
+    outln!("  7. TCP benchmark (no TLS)");
+    outln!("  8. UDP benchmark");
+    outln!("  9. Full-duplex (concurrent send/receive)");
+    outln!("  10. TLS handshake only (connect + handshake + close)");
+    outln!("  11. Wait for server close (graceful shutdown timing)");
+    outln!("  12. Connection rate ({} concurrent connect + handshake + close)", config.clients);
+    outln!("  13. Half-open detection (keepalive / read timeout)");
+    outln!("  14. Server push (time to receive the first message)");
+    outln!();
+    outln!("  97. Toggle ping-pong logging ({})", if QUIET_MODE.load(Ordering::SeqCst) { "OFF" } else { "ON" });
+    outln!("  98. Change server address (current: {}:{})", config.host, config.port);
+    outln!("  99. Change ping-pong count (current: {})", config.ping_pong_count);
+    outln!();
+    outln!("  0. Exit");
+    outln!("═══════════════════════════════════════════════════════════════");
     print!("Enter choice: ");
     io::stdout().flush().unwrap();
 }
@@ -84,15 +475,16 @@ fn read_line() -> String {
 }
 
 fn ask_server_settings(config: &mut BenchmarkConfig) {
-    println!();
-    println!("╔══════════════════════════════════════════════════════════════╗");
-    println!("║  Configure WebSocket Server Address                          ║");
-    println!("╚══════════════════════════════════════════════════════════════╝");
+    outln!();
+    outln!("╔══════════════════════════════════════════════════════════════╗");
+    outln!("║  Configure WebSocket Server Address                          ║");
+    outln!("╚══════════════════════════════════════════════════════════════╝");
 
     print!("Server host [{}]: ", config.host);
     io::stdout().flush().unwrap();
     let input = read_line();
     if !input.is_empty() {
+        config.hosts = vec![input.clone()];
         config.host = input;
     }
 
@@ -105,14 +497,14 @@ fn ask_server_settings(config: &mut BenchmarkConfig) {
         }
     }
 
-    println!("Server set to: wss://{}:{}", config.host, config.port);
+    outln!("Server set to: wss://{}:{}", config.host, config.port);
 }
 
 fn ask_ping_pong_count(config: &mut BenchmarkConfig) {
-    println!();
-    println!("╔══════════════════════════════════════════════════════════════╗");
-    println!("║  Enter number of ping-pong iterations (default: 30):       ║");
-    println!("╚══════════════════════════════════════════════════════════════╝");
+    outln!();
+    outln!("╔══════════════════════════════════════════════════════════════╗");
+    outln!("║  Enter number of ping-pong iterations (default: 30):       ║");
+    outln!("╚══════════════════════════════════════════════════════════════╝");
     print!("Ping-pong count [{}]: ", config.ping_pong_count);
     io::stdout().flush().unwrap();
     let input = read_line();
@@ -120,50 +512,432 @@ fn ask_ping_pong_count(config: &mut BenchmarkConfig) {
         if let Ok(count) = input.parse::<u32>() {
             if count > 0 && count <= 1_000_000 {
                 config.ping_pong_count = count;
-                println!("Ping-pong count set to: {}", config.ping_pong_count);
+                outln!("Ping-pong count set to: {}", config.ping_pong_count);
             } else {
-                println!("Invalid value, using current: {}", config.ping_pong_count);
+                outln!("Invalid value, using current: {}", config.ping_pong_count);
             }
         }
     }
 }
 
-async fn run_benchmark(num: u8, config: &BenchmarkConfig) -> Result<()> {
-    match num {
-        1 => benchmark::run_openssl_benchmark(config).await,
-        2 => {
-            let result = benchmark::run_basic_tls_benchmark_sync(config);
-            // Convert sync result to async result
-            result.map_err(|e| anyhow::anyhow!("{:?}", e))
+/// Reject options that benchmark `num` would silently ignore
+fn check_benchmark_options(num: u8, config: &BenchmarkConfig) -> Result<()> {
+    // Only the shared WebSocket ping loop replays traces
+    let ping_loop = num == 5 || (num == 1 && !config.h2);
+    if !config.trace.is_empty() && !ping_loop {
+        anyhow::bail!("--trace is only supported by benchmarks 1 (without --h2) and 5");
+    }
+    if config.reconnect && !ping_loop {
+        anyhow::bail!(
+            "--reconnect is only supported by benchmarks 1 (without --h2) and 5; \
+             pass --no-reconnect if a profile turned it on"
+        );
+    }
+    if !(config.mix.is_empty() || ping_loop || num == 2) {
+        anyhow::bail!("--mix is only supported by benchmarks 1 (without --h2), 2 and 5");
+    }
+    if config.open_loop && (num != 9 || !config.payload_sweep.is_empty()) {
+        anyhow::bail!("--open-loop is only supported by benchmark 9 without --payload-sweep");
+    }
+    // These run without a RunMonitor, so no response arrival is ever recorded
+    if config.response_cadence && matches!(num, 6 | 11..=14) {
+        anyhow::bail!("--response-cadence is not supported by benchmarks 6 and 11-14");
+    }
+    // These keep every sample themselves; open-loop needs them to correct for the schedule
+    if config.no_percentiles && (matches!(num, 6 | 11..=14) || config.open_loop) {
+        anyhow::bail!("--no-percentiles is not supported by benchmarks 6, 11-14 and --open-loop");
+    }
+    Ok(())
+}
+
+/// Reports of a run, plus the error of any part of a multi-benchmark set that failed
+/// so the parts that passed can still be exported
+type RunOutcome = (Vec<BenchmarkReport>, Option<anyhow::Error>);
+
+async fn run_benchmark(num: u8, config: &BenchmarkConfig) -> Result<RunOutcome> {
+    check_benchmark_options(num, config)?;
+    let reports = match num {
+        1 if config.h2 => vec![benchmark::run_h2_benchmark(config).await?],
+        1 => vec![benchmark::run_openssl_benchmark(config).await?],
+        2 => vec![benchmark::run_basic_tls_benchmark_sync(config)?],
+        5 => return run_all_tls(config).await,
+        6 => vec![benchmark::run_multi_connection_benchmark(config).await?],
+        7 => vec![benchmark::run_tcp_benchmark(config).await?],
+        8 => vec![benchmark::run_udp_benchmark(config).await?],
+        9 if !config.payload_sweep.is_empty() => benchmark::run_payload_sweep(config).await?,
+        9 if config.open_loop => vec![benchmark::run_open_loop_benchmark(config).await?],
+        9 => vec![benchmark::run_full_duplex_benchmark(config).await?],
+        10 => vec![benchmark::run_tls_handshake_benchmark(config)?],
+        11 => vec![benchmark::run_wait_for_close_benchmark(config).await?],
+        12 => vec![benchmark::run_connection_rate_benchmark(config).await?],
+        13 => vec![benchmark::run_half_open_benchmark(config).await?],
+        14 => vec![benchmark::run_server_push_benchmark(config)?],
+        _ => anyhow::bail!("Unknown benchmark: {}", num),
+    };
+    Ok((reports, None))
+}
+
+/// Benchmark 5: run every TLS benchmark in turn.
+///
+/// Failures are collected and summarized at the end unless --fail-fast is set,
+/// in which case the first failure aborts the set. The summarized failure is
+/// returned next to the reports of the benchmarks that passed.
+async fn run_all_tls(config: &BenchmarkConfig) -> Result<RunOutcome> {
+    let runs = [
+        ("socket + Native TLS (blocking I/O)", benchmark::run_openssl_benchmark(config).boxed_local()),
+        ("async + Native TLS (tokio)", benchmark::run_async_benchmark(config).boxed_local()),
+    ];
+
+    let mut reports = Vec::new();
+    let mut outcomes = Vec::new();
+    for (index, (name, run)) in runs.into_iter().enumerate() {
+        if index > 0 {
+            outln!();
         }
-        5 => {
-            benchmark::run_openssl_benchmark(config).await?;
-            println!();
-            benchmark::run_async_benchmark(config).await
+        match run.await {
+            Ok(report) => {
+                reports.push(report);
+                outcomes.push((name, None));
+            }
+            Err(e) if config.fail_fast => return Err(e.context(format!("{} failed", name))),
+            Err(e) => {
+                tracing::error!(benchmark = name, error = ?e, "benchmark failed");
+                outcomes.push((name, Some(e)));
+            }
         }
-        6 => benchmark::run_multi_connection_benchmark(config).await,
-        7 => benchmark::run_tcp_benchmark(config).await,
-        8 => benchmark::run_udp_benchmark(config).await,
-        _ => Err(anyhow::anyhow!("Unknown benchmark: {}", num)),
     }
+
+    outln!();
+    outln!("┌──────────────────────────────────────────────────────────────┐");
+    outln!("│ Run-all summary                                              │");
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    for (name, error) in &outcomes {
+        let status = if error.is_some() { "FAILED" } else { "passed" };
+        outln!("│ {:<52} {:>7} │", name, status);
+    }
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    let failed: Vec<String> = outcomes
+        .iter()
+        .filter_map(|(name, error)| error.as_ref().map(|e| format!("{}: {:#}", name, e)))
+        .collect();
+    let failure = (!failed.is_empty()).then(|| {
+        anyhow::anyhow!("{} of {} benchmarks failed:\n  {}", failed.len(), outcomes.len(), failed.join("\n  "))
+    });
+
+    Ok((reports, failure))
+}
+
+/// A boolean flag with a --no-* counterpart; the profile's value applies when neither is given
+fn profile_flag(on: bool, off: bool, base: bool) -> bool {
+    if on {
+        true
+    } else if off {
+        false
+    } else {
+        base
+    }
+}
+
+/// Load a custom ping payload from --payload-file or --payload-stdin
+fn load_payload(args: &Args) -> Result<Option<Vec<u8>>> {
+    if let Some(path) = &args.payload_file {
+        let payload = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        return Ok(Some(payload));
+    }
+
+    if args.payload_stdin {
+        let mut payload = Vec::new();
+        io::stdin()
+            .read_to_end(&mut payload)
+            .context("Failed to read payload from stdin")?;
+        return Ok(Some(payload));
+    }
+
+    Ok(None)
+}
+
+/// Run a benchmark and export its results if an output file is configured
+async fn run_and_export(num: u8, config: &BenchmarkConfig) -> Result<Vec<BenchmarkReport>> {
+    let (reports, failure) = run_benchmark(num, config)
+        .instrument(tracing::info_span!("benchmark", num))
+        .await?;
+
+    if let Some(dscp) = config.dscp {
+        outln!("DSCP {} (TOS byte 0x{:02x}) requested on outgoing sockets", dscp, dscp << 2);
+    }
+
+    let metadata = report::Metadata::collect(config);
+    metadata.print();
+
+    if let Some(path) = &config.output {
+        report::write_results(path, &reports, &metadata, config.append_output, config.compress_output)?;
+        outln!("Results written to {}", path.display());
+    }
+
+    // The passing benchmarks are exported above; an incomplete set still exits non-zero
+    // and isn't compared against the baseline
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    if let Some(path) = &config.baseline {
+        report::check_baseline(path, &reports, config.regression_threshold_pct)?;
+    }
+
+    Ok(reports)
 }
 
 pub async fn run_interactive_or_command(args: Args) -> Result<()> {
-    print_header();
+    if args.schema {
+        outln!("{}", serde_json::to_string_pretty(&report::json_schema())?);
+        return Ok(());
+    }
+
+    // Merging exported results doesn't touch the network
+    if !args.merge.is_empty() {
+        return report::merge_files(&args.merge);
+    }
+
+    // Pure CPU: no target or config needed
+    if args.masking_bench {
+        return benchmark::run_masking_benchmark();
+    }
+
+    // An empty run would print an all-zero report that looks like a real result
+    if args.count == Some(0) && !args.once && !args.validate_handshake_only && !args.find_max_frame {
+        anyhow::bail!("count must be at least 1");
+    }
+
+    // Flags left unset fall back to the profile's values, then the built-in defaults
+    let mut base = BenchmarkConfig::default();
+    if let Some(profile) = args.profile {
+        profile.apply(&mut base);
+    }
 
     let mut config = BenchmarkConfig {
-        host: args.host.clone(),
+        host: args.host[0].clone(),
+        hosts: args.host.clone(),
         port: args.port,
-        ping_pong_count: args.count,
-        quiet: args.quiet,
+        ping_pong_count: args.count.unwrap_or(base.ping_pong_count),
+        quiet: profile_flag(args.quiet, args.no_quiet, base.quiet),
+        reconnect: profile_flag(args.reconnect, args.no_reconnect, base.reconnect),
+        connect_retries: args.connect_retries,
+        retry_backoff_ms: args.retry_backoff_ms,
+        strict: args.strict,
+        track_memory: profile_flag(args.track_memory, args.no_track_memory, base.track_memory),
+        memory_sample_every: args.memory_sample_every,
+        output: args.output.clone(),
+        append_output: args.append,
+        ramp_secs: args.ramp,
+        abort_above_ms: args.abort_above_ms,
+        abort_after: args.abort_after,
+        compress: args.compress,
+        clients: args.clients.unwrap_or(base.clients),
+        timeout_ms: args.timeout_ms,
+        interval_ms: args.interval_ms.or(base.interval_ms),
+        arrival: args.arrival,
+        seed: args.seed,
+        report_interval_secs: args.report_interval.or(base.report_interval_secs),
+        cpu_affinity: args.cpu_affinity.clone(),
+        payload_size: args.payload_size.or(base.payload_size),
+        payload: load_payload(&args)?,
+        baseline: args.baseline.clone(),
+        regression_threshold_pct: args.regression_threshold,
+        h2: args.h2,
+        interface: args.interface.clone(),
+        path: "/ws".to_string(),
+        tls: !args.no_tls,
+        per_connection: args.per_connection,
+        binary: args.binary,
+        ping_retries: args.ping_retries,
+        legacy_deflate: !args.no_legacy_deflate,
+        deflate: DeflateParams {
+            client_max_window_bits: args.client_max_window_bits,
+            server_max_window_bits: args.server_max_window_bits,
+            client_no_context_takeover: args.client_no_context_takeover,
+            server_no_context_takeover: args.server_no_context_takeover,
+        },
+        payload_sweep: args.payload_sweep.clone(),
+        auto_scheme: args.auto_scheme,
+        folded_output: args.folded.clone(),
+        keepalive_ping_ms: args.keepalive_ping_ms,
+        fail_fast: args.fail_fast,
+        mix: match &args.mix {
+            Some(spec) => mix::parse_mix(spec).map_err(|e| anyhow::anyhow!("Invalid --mix: {}", e))?,
+            None => Vec::new(),
+        },
+        trace: Vec::new(),
+        trace_timing: false,
+        dscp: args.dscp,
+        runtime_threads: args.runtime_threads.map(|n| n as usize),
+        current_thread: args.current_thread,
+        strict_response: args.strict_response,
+        connect_concurrency: args.connect_concurrency,
+        close_code: args.close_code,
+        close_reason: args.close_reason.clone(),
+        tcp_keepalive_secs: args.tcp_keepalive_secs,
+        tcp_keepalive_interval_secs: args.tcp_keepalive_interval_secs,
+        tcp_keepalive_retries: args.tcp_keepalive_retries,
+        output_format: args.output_format,
+        open_loop: args.open_loop,
+        tcp_info: args.tcp_info,
+        idle_before_secs: args.idle_before_secs,
+        compress_output: args.compress_output,
+        measure_window: args.measure_window,
+        origin: args.origin,
+        response_cadence: args.response_cadence,
+        connect_timeout_ms: args.connect_timeout_ms,
+        payload_kind: args.payload_kind,
+        quickack: args.quickack,
+        no_percentiles: args.no_percentiles,
+        static_frame: args.static_frame,
+        source_port_range: args.source_port_range,
+        fd: args.fd,
+        no_handshake: args.no_handshake,
+        max_frame_size: args.max_frame_size.unwrap_or(DEFAULT_MAX_PAYLOAD),
     };
 
+    if config.quickack && cfg!(not(target_os = "linux")) {
+        tracing::warn!("--quickack is only supported on Linux; ignoring it");
+        config.quickack = false;
+    }
+
+    // One handed-over socket carries exactly one connection
+    if config.fd.is_some() {
+        if cfg!(not(unix)) {
+            anyhow::bail!("--fd is only supported on Unix");
+        }
+        if args.benchmark != Some(2) {
+            anyhow::bail!("--fd needs --benchmark 2");
+        }
+    }
+
+    if let Some((start, end)) = config.source_port_range {
+        let ports = u32::from(end - start) + 1;
+        if ports < config.clients {
+            anyhow::bail!(
+                "--source-port-range {}-{} has {} ports for {} clients",
+                start,
+                end,
+                ports,
+                config.clients
+            );
+        }
+    }
+
+    // Random bytes aren't valid UTF-8, so servers must reject them in text frames
+    if config.payload_kind == PayloadKind::Random && !config.binary {
+        anyhow::bail!("--payload-kind random needs --binary");
+    }
+
+    utils::set_resolve_overrides(args.resolve.iter().cloned().collect());
+
+    if let Some(name) = args.shm.as_deref() {
+        #[cfg(all(feature = "shm", unix))]
+        crate::shm::init(name).with_context(|| format!("Failed to create shared-memory ring {}", name))?;
+        #[cfg(not(all(feature = "shm", unix)))]
+        anyhow::bail!("--shm {} requires a Unix build with --features shm", name);
+    }
+
+    if let Some(path) = &args.trace {
+        config.trace = trace::load_trace(path).map_err(anyhow::Error::msg)?;
+        config.ping_pong_count = config.trace.len() as u32;
+        if args.trace_timing && config.trace[0].offset.is_none() {
+            anyhow::bail!("--trace-timing needs a trace with OFFSET_MS,SIZE lines");
+        }
+        config.trace_timing = args.trace_timing;
+    }
+
+    // Echoed pings come back at full size, so a lower limit would fail every iteration
+    let largest_ping = [
+        config.payload.as_ref().map_or(0, Vec::len),
+        config.payload_size.unwrap_or(0),
+        config.mix.iter().map(|entry| entry.size).max().unwrap_or(0),
+        config.payload_sweep.iter().copied().max().unwrap_or(0),
+        config.trace.iter().map(|entry| entry.size).max().unwrap_or(0),
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or(0);
+    if largest_ping as u64 > config.max_frame_size {
+        anyhow::bail!(
+            "--max-frame-size {} is smaller than the {}-byte pings the server echoes back",
+            config.max_frame_size,
+            largest_ping
+        );
+    }
+
+    if let Some(url) = &args.url {
+        let target = utils::parse_ws_url(url).map_err(anyhow::Error::msg)?;
+        config.host = target.host.clone();
+        config.hosts = vec![target.host];
+        config.port = target.port;
+        config.path = target.path;
+        config.tls = target.tls;
+    }
+
+    // Health check: non-zero exit with the failure reason if the handshake fails
+    if args.validate_handshake_only {
+        return benchmark::validate_handshake(&config);
+    }
+
+    if args.find_max_frame {
+        return benchmark::find_max_frame(&config).await;
+    }
+
+    if let Some(path) = &args.targets {
+        let urls = utils::load_targets(path).map_err(anyhow::Error::msg)?;
+        return benchmark::survey_targets(&config, &urls, args.targets_concurrency as usize).await;
+    }
+
+    // Single ping: print only the RTT so the output can be captured by scripts
+    if args.once {
+        let rtt_ms = benchmark::run_once(&config).await?;
+        outln!("{:.3}", rtt_ms);
+        return Ok(());
+    }
+
+    if args.summary_only_on_failure {
+        if args.benchmark.is_none() {
+            anyhow::bail!("--summary-only-on-failure requires --benchmark");
+        }
+        output::start_buffering();
+    }
+    // The box tables are only kept to explain a failure; on success just the Markdown is printed
+    if config.output_format == OutputFormat::Markdown && args.benchmark.is_some() {
+        output::start_buffering();
+    }
+
+    print_header();
+
     // Set quiet mode globally
-    QUIET_MODE.store(args.quiet, Ordering::SeqCst);
+    QUIET_MODE.store(config.quiet, Ordering::SeqCst);
 
     // If benchmark is specified, run it and exit
     if let Some(benchmark_num) = args.benchmark {
-        return run_benchmark(benchmark_num, &config).await;
+        let result = run_and_export(benchmark_num, &config).await;
+
+        // Buffered output is only shown when the run failed or breached a threshold
+        if let Some(buffer) = output::take_buffer() {
+            if result.is_err() {
+                io::stderr().write_all(&buffer)?;
+            }
+        }
+        let reports = result?;
+        if config.output_format == OutputFormat::Markdown {
+            outln!("{}", report::markdown_summary(&reports, &config));
+        }
+        return Ok(());
+    }
+
+    // Without a terminal the menu would block forever on read_line
+    if !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "stdin is not a terminal and no benchmark was selected; \
+             pass --benchmark <NUM> to run non-interactively (see --help)"
+        );
     }
 
     // Interactive mode
@@ -173,13 +947,13 @@ pub async fn run_interactive_or_command(args: Args) -> Result<()> {
 
         match choice.as_str() {
             "0" | "exit" => {
-                println!("Exiting...");
+                outln!("Exiting...");
                 break;
             }
             "97" => {
                 let current = QUIET_MODE.load(Ordering::SeqCst);
                 QUIET_MODE.store(!current, Ordering::SeqCst);
-                println!("Ping-pong logging: {}", if !QUIET_MODE.load(Ordering::SeqCst) { "ENABLED" } else { "DISABLED" });
+                outln!("Ping-pong logging: {}", if !QUIET_MODE.load(Ordering::SeqCst) { "ENABLED" } else { "DISABLED" });
             }
             "98" => {
                 ask_server_settings(&mut config);
@@ -189,19 +963,120 @@ pub async fn run_interactive_or_command(args: Args) -> Result<()> {
             }
             num => {
                 if let Ok(benchmark_num) = num.parse::<u8>() {
-                    if let Err(e) = run_benchmark(benchmark_num, &config).await {
-                        eprintln!("Error running benchmark {}: {:?}", benchmark_num, e);
+                    match run_and_export(benchmark_num, &config).await {
+                        Ok(reports) if config.output_format == OutputFormat::Markdown => {
+                            outln!("{}", report::markdown_summary(&reports, &config));
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            tracing::error!(benchmark = benchmark_num, error = ?e, "benchmark failed");
+                        }
                     }
-                    println!();
-                    println!("Press Enter to continue...");
+                    outln!();
+                    outln!("Press Enter to continue...");
                     read_line();
                 } else {
-                    println!("Invalid choice. Please try again.");
+                    outln!("Invalid choice. Please try again.");
                 }
             }
         }
-        println!();
+        outln!();
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_flags_override_profile_booleans() {
+        let args = Args::try_parse_from(["websocket_benchmark", "--profile", "soak", "--no-reconnect"]).unwrap();
+        let mut base = BenchmarkConfig::default();
+        args.profile.unwrap().apply(&mut base);
+
+        assert!(!profile_flag(args.reconnect, args.no_reconnect, base.reconnect));
+        assert!(profile_flag(args.track_memory, args.no_track_memory, base.track_memory));
+        assert!(!profile_flag(false, false, BenchmarkConfig::default().quiet));
+
+        // The last of a flag and its --no-* counterpart wins
+        let args = Args::try_parse_from(["websocket_benchmark", "--no-quiet", "-q"]).unwrap();
+        assert!(profile_flag(args.quiet, args.no_quiet, false));
+        let args = Args::try_parse_from(["websocket_benchmark", "-q", "--no-quiet"]).unwrap();
+        assert!(!profile_flag(args.quiet, args.no_quiet, true));
+    }
+
+    #[test]
+    fn test_benchmark_options_match_the_benchmark() {
+        let cadence = BenchmarkConfig {
+            response_cadence: true,
+            ..BenchmarkConfig::default()
+        };
+        assert!(check_benchmark_options(1, &cadence).is_ok());
+        assert!(check_benchmark_options(9, &cadence).is_ok());
+        assert!(check_benchmark_options(6, &cadence).is_err());
+        assert!(check_benchmark_options(14, &cadence).is_err());
+
+        let reconnect = BenchmarkConfig {
+            reconnect: true,
+            ..BenchmarkConfig::default()
+        };
+        assert!(check_benchmark_options(5, &reconnect).is_ok());
+        assert!(check_benchmark_options(2, &reconnect).is_err());
+        let h2 = BenchmarkConfig { h2: true, ..reconnect };
+        assert!(check_benchmark_options(1, &h2).is_err());
+
+        let no_percentiles = BenchmarkConfig {
+            no_percentiles: true,
+            ..BenchmarkConfig::default()
+        };
+        assert!(check_benchmark_options(9, &no_percentiles).is_ok());
+        assert!(check_benchmark_options(10, &no_percentiles).is_ok());
+        assert!(check_benchmark_options(12, &no_percentiles).is_err());
+        let open_loop = BenchmarkConfig { open_loop: true, ..no_percentiles };
+        assert!(check_benchmark_options(9, &open_loop).is_err());
+    }
+
+    #[test]
+    fn test_report_interval_is_validated_by_clap() {
+        let args = Args::try_parse_from(["websocket_benchmark", "--report-interval", "2.5"]).unwrap();
+        assert_eq!(args.report_interval, Some(2.5));
+
+        // Duration::from_secs_f64 would panic on these once the run started
+        for value in ["-1", "NaN", "inf", "0"] {
+            let flag = format!("--report-interval={}", value);
+            assert!(Args::try_parse_from(["websocket_benchmark", flag.as_str()]).is_err(), "{} accepted", value);
+        }
+    }
+
+    #[test]
+    fn test_timeout_ms_must_be_positive() {
+        let args = Args::try_parse_from(["websocket_benchmark", "--timeout-ms", "1"]).unwrap();
+        assert_eq!(args.timeout_ms, Some(1));
+        // A zero read timeout is InvalidInput for std sockets and an instant expiry for tokio
+        assert!(Args::try_parse_from(["websocket_benchmark", "--timeout-ms", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_interval_ms_is_validated_by_clap() {
+        let args = Args::try_parse_from(["websocket_benchmark", "--interval-ms", "0.5"]).unwrap();
+        assert_eq!(args.interval_ms, Some(0.5));
+
+        // The pacer would panic on non-finite gaps and silently clamp negative ones to 0
+        for value in ["-1", "NaN", "inf", "0"] {
+            let flag = format!("--interval-ms={}", value);
+            assert!(Args::try_parse_from(["websocket_benchmark", flag.as_str()]).is_err(), "{} accepted", value);
+        }
+    }
+
+    #[test]
+    fn test_ramp_must_be_finite_and_non_negative() {
+        let args = Args::try_parse_from(["websocket_benchmark", "--ramp", "0"]).unwrap();
+        assert_eq!(args.ramp, 0.0);
+        for value in ["-1", "NaN", "inf"] {
+            let flag = format!("--ramp={}", value);
+            assert!(Args::try_parse_from(["websocket_benchmark", flag.as_str()]).is_err(), "{} accepted", value);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::utils::Extension;
+use flate2::{Decompress, FlushDecompress, Status};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::io;
+
+pub const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+/// Pre-standard extension offered by some older servers; same DEFLATE framing
+pub const X_WEBKIT_DEFLATE: &str = "x-webkit-deflate";
+
+/// Trailer stripped from each compressed message (RFC 7692 section 7.2.1)
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+const INFLATE_CHUNK: usize = 4096;
+const MIN_WINDOW_BITS: u8 = 8;
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// permessage-deflate extension parameters
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DeflateParams {
+    pub client_max_window_bits: Option<u8>,
+    pub server_max_window_bits: Option<u8>,
+    pub client_no_context_takeover: bool,
+    pub server_no_context_takeover: bool,
+}
+
+impl DeflateParams {
+    /// Sec-WebSocket-Extensions value offering these parameters
+    pub fn offer(&self) -> String {
+        let mut offer = PERMESSAGE_DEFLATE.to_string();
+        match self.client_max_window_bits {
+            Some(bits) => offer.push_str(&format!("; client_max_window_bits={}", bits)),
+            // Advertise support so the server may pick a window size
+            None => offer.push_str("; client_max_window_bits"),
+        }
+        if let Some(bits) = self.server_max_window_bits {
+            offer.push_str(&format!("; server_max_window_bits={}", bits));
+        }
+        if self.client_no_context_takeover {
+            offer.push_str("; client_no_context_takeover");
+        }
+        if self.server_no_context_takeover {
+            offer.push_str("; server_no_context_takeover");
+        }
+        offer
+    }
+
+    /// Negotiated parameters for the report, e.g. "server_no_context_takeover, client_max_window_bits=10"
+    pub fn describe(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(bits) = self.client_max_window_bits {
+            params.push(format!("client_max_window_bits={}", bits));
+        }
+        if let Some(bits) = self.server_max_window_bits {
+            params.push(format!("server_max_window_bits={}", bits));
+        }
+        if self.client_no_context_takeover {
+            params.push("client_no_context_takeover".to_string());
+        }
+        if self.server_no_context_takeover {
+            params.push("server_no_context_takeover".to_string());
+        }
+        if params.is_empty() {
+            return "defaults (15-bit windows, context takeover)".to_string();
+        }
+        params.join(", ")
+    }
+
+    /// Validate the server's accepted extension against this offer.
+    ///
+    /// Unknown or duplicate parameters, out-of-range window sizes and a
+    /// client_max_window_bits we didn't advertise are rejected.
+    pub fn negotiate(&self, response: &Extension) -> Result<DeflateParams, String> {
+        if response.name != PERMESSAGE_DEFLATE {
+            return Err(format!("unexpected extension {}", response.name));
+        }
+
+        let mut negotiated = DeflateParams::default();
+        for (index, (key, value)) in response.params.iter().enumerate() {
+            if response.params[..index].iter().any(|(seen, _)| seen == key) {
+                return Err(format!("duplicate parameter {}", key));
+            }
+
+            match key.as_str() {
+                "server_no_context_takeover" => negotiated.server_no_context_takeover = true,
+                "client_no_context_takeover" => negotiated.client_no_context_takeover = true,
+                "server_max_window_bits" => {
+                    negotiated.server_max_window_bits = Some(parse_window_bits(key, value)?);
+                }
+                "client_max_window_bits" => {
+                    let bits = parse_window_bits(key, value)?;
+                    if let Some(offered) = self.client_max_window_bits {
+                        if bits > offered {
+                            return Err(format!(
+                                "client_max_window_bits={} exceeds offered {}",
+                                bits, offered
+                            ));
+                        }
+                    }
+                    negotiated.client_max_window_bits = Some(bits);
+                }
+                _ => return Err(format!("unrequested parameter {}", key)),
+            }
+        }
+
+        if self.server_no_context_takeover && !negotiated.server_no_context_takeover {
+            return Err("server ignored server_no_context_takeover".to_string());
+        }
+        if let (Some(offered), Some(bits)) =
+            (self.server_max_window_bits, negotiated.server_max_window_bits)
+        {
+            if bits > offered {
+                return Err(format!(
+                    "server_max_window_bits={} exceeds offered {}",
+                    bits, offered
+                ));
+            }
+        }
+
+        Ok(negotiated)
+    }
+}
+
+/// Validate an accepted x-webkit-deflate extension.
+///
+/// Returns whether the server resets its compression context per message.
+pub fn negotiate_legacy(response: &Extension) -> Result<bool, String> {
+    if response.name != X_WEBKIT_DEFLATE {
+        return Err(format!("unexpected extension {}", response.name));
+    }
+
+    let mut no_context_takeover = false;
+    for (key, value) in &response.params {
+        match key.as_str() {
+            "no_context_takeover" => no_context_takeover = true,
+            "max_window_bits" => {
+                parse_window_bits(key, value)?;
+            }
+            _ => return Err(format!("unrequested parameter {}", key)),
+        }
+    }
+
+    Ok(no_context_takeover)
+}
+
+fn parse_window_bits(key: &str, value: &Option<String>) -> Result<u8, String> {
+    let value = value
+        .as_deref()
+        .ok_or_else(|| format!("{} requires a value in a response", key))?;
+    match value.parse::<u8>() {
+        Ok(bits) if (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&bits) => Ok(bits),
+        _ => Err(format!("invalid {}={}", key, value)),
+    }
+}
+
+/// Decompressor for server messages compressed with permessage-deflate
+pub struct Inflater {
+    decompress: Decompress,
+    reset_each_message: bool,
+    /// Compressed payload bytes received
+    pub compressed_bytes: u64,
+    /// Bytes they inflated to
+    pub inflated_bytes: u64,
+}
+
+impl Inflater {
+    /// Create an inflater; `reset_each_message` mirrors server_no_context_takeover
+    pub fn new(reset_each_message: bool) -> Self {
+        // A full 15-bit window inflates streams compressed with any smaller window
+        Inflater {
+            decompress: Decompress::new(false),
+            reset_each_message,
+            compressed_bytes: 0,
+            inflated_bytes: 0,
+        }
+    }
+
+    /// Inflate the payload of one complete compressed message
+    pub fn inflate(&mut self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let mut input = Vec::with_capacity(payload.len() + DEFLATE_TAIL.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&DEFLATE_TAIL);
+
+        let mut output = Vec::with_capacity(payload.len() * 2 + INFLATE_CHUNK);
+        let start_in = self.decompress.total_in();
+
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(INFLATE_CHUNK);
+            }
+
+            let consumed = (self.decompress.total_in() - start_in) as usize;
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            let consumed = (self.decompress.total_in() - start_in) as usize;
+            let output_full = output.len() == output.capacity();
+            match status {
+                Status::StreamEnd => break,
+                Status::Ok | Status::BufError if consumed >= input.len() && !output_full => break,
+                Status::BufError if !output_full => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Truncated compressed message",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        if self.reset_each_message {
+            self.decompress.reset(false);
+        }
+
+        self.compressed_bytes += payload.len() as u64;
+        self.inflated_bytes += output.len() as u64;
+        Ok(output)
+    }
+
+    /// Payload of one complete message: inflated if its first frame set RSV1, otherwise
+    /// passed through untouched, since servers may send any message uncompressed
+    pub fn decode<'a>(&mut self, rsv1: bool, payload: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        if rsv1 {
+            self.inflate(payload).map(Cow::Owned)
+        } else {
+            Ok(Cow::Borrowed(payload))
+        }
+    }
+
+    /// Inflated size over compressed size of everything inflated so far
+    pub fn ratio(&self) -> f64 {
+        if self.compressed_bytes == 0 {
+            return 0.0;
+        }
+        self.inflated_bytes as f64 / self.compressed_bytes as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_extensions;
+    use flate2::{Compress, Compression, FlushCompress};
+
+    /// Compress a message the way a permessage-deflate peer would
+    fn deflate_message(compress: &mut Compress, data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(data.len() + 64);
+        compress
+            .compress_vec(data, &mut output, FlushCompress::Sync)
+            .unwrap();
+        assert!(output.ends_with(&DEFLATE_TAIL));
+        output.truncate(output.len() - DEFLATE_TAIL.len());
+        output
+    }
+
+    #[test]
+    fn test_negotiate_server_response() {
+        let response = &parse_extensions(
+            "permessage-deflate; client_max_window_bits=10; server_no_context_takeover",
+        )[0];
+
+        let negotiated = DeflateParams::default().negotiate(response).unwrap();
+
+        assert_eq!(negotiated.client_max_window_bits, Some(10));
+        assert!(negotiated.server_no_context_takeover);
+        assert!(!negotiated.client_no_context_takeover);
+    }
+
+    #[test]
+    fn test_offer_and_describe_tuning_params() {
+        let params = DeflateParams {
+            client_max_window_bits: Some(9),
+            server_max_window_bits: Some(10),
+            client_no_context_takeover: true,
+            server_no_context_takeover: true,
+        };
+        assert_eq!(
+            params.offer(),
+            "permessage-deflate; client_max_window_bits=9; server_max_window_bits=10; \
+             client_no_context_takeover; server_no_context_takeover"
+        );
+
+        let response = &parse_extensions(
+            "permessage-deflate; server_max_window_bits=10; server_no_context_takeover",
+        )[0];
+        let negotiated = params.negotiate(response).unwrap();
+        assert_eq!(
+            negotiated.describe(),
+            "server_max_window_bits=10, server_no_context_takeover"
+        );
+        assert_eq!(
+            DeflateParams::default().describe(),
+            "defaults (15-bit windows, context takeover)"
+        );
+    }
+
+    #[test]
+    fn test_negotiate_rejects_unrequested_parameter() {
+        let response = &parse_extensions("permessage-deflate; mystery_param=1")[0];
+
+        assert!(DeflateParams::default().negotiate(response).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_rejects_invalid_window_bits() {
+        let response = &parse_extensions("permessage-deflate; server_max_window_bits=20")[0];
+
+        assert!(DeflateParams::default().negotiate(response).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_legacy() {
+        let response = &parse_extensions("x-webkit-deflate; no_context_takeover; max_window_bits=12")[0];
+        assert_eq!(negotiate_legacy(response), Ok(true));
+
+        let response = &parse_extensions("x-webkit-deflate; client_max_window_bits=12")[0];
+        assert!(negotiate_legacy(response).is_err());
+    }
+
+    #[test]
+    fn test_inflate_with_context_takeover() {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut inflater = Inflater::new(false);
+
+        for _ in 0..3 {
+            let compressed = deflate_message(&mut compress, b"PONG PONG PONG");
+            assert_eq!(inflater.inflate(&compressed).unwrap(), b"PONG PONG PONG");
+        }
+        assert_eq!(inflater.inflated_bytes, 3 * 14);
+        assert!(inflater.ratio() > 1.0);
+    }
+
+    #[test]
+    fn test_decode_passes_uncompressed_messages_through() {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut inflater = Inflater::new(false);
+
+        let compressed = deflate_message(&mut compress, b"PONG PONG PONG");
+        assert_eq!(&*inflater.decode(true, &compressed).unwrap(), b"PONG PONG PONG");
+
+        // A raw DEFLATE block would fail to inflate, so this only passes if RSV1 is honoured
+        let raw = b"\x00\x00\xff\xff PONG";
+        let decoded = inflater.decode(false, raw).unwrap();
+        assert!(matches!(decoded, Cow::Borrowed(_)));
+        assert_eq!(&*decoded, raw);
+
+        // The uncompressed message must not disturb the shared sliding window
+        let compressed = deflate_message(&mut compress, b"PONG PONG PONG");
+        assert_eq!(&*inflater.decode(true, &compressed).unwrap(), b"PONG PONG PONG");
+        assert_eq!(inflater.inflated_bytes, 2 * 14);
+    }
+}
@@ -0,0 +1,176 @@
+//! WebSocket over HTTP/2 (RFC 8441 extended CONNECT)
+
+use crate::utils::resolve_override;
+use crate::websocket::{check_declared_len, WebSocketFrame};
+use anyhow::{Context, Result};
+use bytes::{Bytes, BytesMut};
+use h2::client::SendRequest;
+use h2::ext::Protocol;
+use h2::{RecvStream, SendStream};
+use http::{Method, Request, StatusCode};
+use tokio::net::TcpStream;
+
+/// A WebSocket carried on a single HTTP/2 stream
+pub struct H2WebSocket {
+    send: SendStream<Bytes>,
+    recv: RecvStream,
+    buffer: BytesMut,
+    max_payload: u64,
+    // Keeps the connection's request handle alive for the stream's lifetime
+    _client: SendRequest<Bytes>,
+}
+
+impl H2WebSocket {
+    /// Negotiate h2 via ALPN and open a WebSocket stream with extended CONNECT; received
+    /// frames may carry at most `max_payload` bytes
+    pub async fn connect(host: &str, port: u16, path: &str, max_payload: u64) -> Result<Self> {
+        let tcp_stream = match resolve_override(host) {
+            Some(ip) => TcpStream::connect((ip, port)).await,
+            None => TcpStream::connect((host, port)).await,
+        }
+            .context("Failed to connect TCP")?;
+        tcp_stream.set_nodelay(true)?;
+
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .request_alpns(&["h2"])
+            .build()
+            .context("Failed to create TLS connector")?;
+        let tls_stream = tokio_native_tls::TlsConnector::from(connector)
+            .connect(host, tcp_stream)
+            .await
+            .context("Failed to connect TLS")?;
+
+        if tls_stream.get_ref().negotiated_alpn()?.as_deref() != Some(b"h2".as_slice()) {
+            anyhow::bail!("Server did not negotiate HTTP/2 via ALPN");
+        }
+
+        let (client, connection) = h2::client::handshake(tls_stream)
+            .await
+            .context("HTTP/2 handshake failed")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!(error = %e, "HTTP/2 connection error");
+            }
+        });
+
+        // SETTINGS_ENABLE_CONNECT_PROTOCOL arrives with the server's first SETTINGS
+        let mut client = client.ready().await.context("HTTP/2 connection not ready")?;
+        if !client.is_extended_connect_protocol_enabled() {
+            anyhow::bail!("Server does not support extended CONNECT (SETTINGS_ENABLE_CONNECT_PROTOCOL)");
+        }
+
+        let (response, send) = client
+            .send_request(connect_request(host, port, path)?, false)
+            .context("Failed to send extended CONNECT")?;
+        let response = response.await.context("Extended CONNECT failed")?;
+        check_connect_status(response.status())?;
+
+        Ok(H2WebSocket {
+            send,
+            recv: response.into_body(),
+            buffer: BytesMut::new(),
+            max_payload,
+            _client: client,
+        })
+    }
+
+    /// Send raw WebSocket frame bytes as DATA on the stream
+    pub fn send_frame(&mut self, frame: Vec<u8>) -> Result<()> {
+        self.send
+            .send_data(Bytes::from(frame), false)
+            .context("Failed to send DATA")
+    }
+
+    /// Receive one complete WebSocket frame, reading as many DATA frames as needed
+    pub async fn recv_frame(&mut self) -> Result<WebSocketFrame> {
+        loop {
+            if let Ok(frame) = WebSocketFrame::parse_frame(&self.buffer, self.max_payload) {
+                let consumed = self.buffer.len() - remaining_after(&frame, &self.buffer);
+                let _ = self.buffer.split_to(consumed);
+                return Ok(frame);
+            }
+            // Stop before buffering a payload that would be rejected anyway
+            check_declared_len(&self.buffer, self.max_payload)?;
+
+            let data = self
+                .recv
+                .data()
+                .await
+                .context("Stream closed")?
+                .context("Failed to receive DATA")?;
+            self.recv.flow_control().release_capacity(data.len())?;
+            self.buffer.extend_from_slice(&data);
+        }
+    }
+}
+
+/// Extended CONNECT request opening a WebSocket stream on `path`
+fn connect_request(host: &str, port: u16, path: &str) -> Result<Request<()>> {
+    Request::builder()
+        .method(Method::CONNECT)
+        .uri(format!("https://{}:{}{}", host, port, path))
+        .header("sec-websocket-version", "13")
+        .extension(Protocol::from_static("websocket"))
+        .body(())
+        .context("Invalid extended CONNECT request")
+}
+
+/// The server opens the stream with 200; any other status is a rejection
+fn check_connect_status(status: StatusCode) -> Result<()> {
+    if status != StatusCode::OK {
+        anyhow::bail!("Extended CONNECT rejected with status {}", status);
+    }
+    Ok(())
+}
+
+/// Bytes left in `buffer` after the parsed frame at its start
+fn remaining_after(frame: &WebSocketFrame, buffer: &[u8]) -> usize {
+    let extended_len = match buffer[1] & 0x7F {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask_len = if frame.masked { 4 } else { 0 };
+    let frame_len = 2 + extended_len + mask_len + frame.payload.len();
+    buffer.len() - frame_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_request_uses_extended_connect() {
+        let request = connect_request("example.com", 8443, "/ws?x=1").unwrap();
+        assert_eq!(request.method(), Method::CONNECT);
+        assert_eq!(request.uri().scheme_str(), Some("https"));
+        assert_eq!(request.uri().authority().unwrap().as_str(), "example.com:8443");
+        assert_eq!(request.uri().path_and_query().unwrap().as_str(), "/ws?x=1");
+        assert_eq!(request.headers()["sec-websocket-version"], "13");
+        assert_eq!(request.extensions().get::<Protocol>().unwrap().as_str(), "websocket");
+    }
+
+    #[test]
+    fn test_connect_request_rejects_invalid_uri() {
+        assert!(connect_request("bad host", 443, "/").is_err());
+    }
+
+    #[test]
+    fn test_connect_status() {
+        assert!(check_connect_status(StatusCode::OK).is_ok());
+        for status in [StatusCode::BAD_REQUEST, StatusCode::FORBIDDEN, StatusCode::NOT_IMPLEMENTED] {
+            let error = check_connect_status(status).unwrap_err().to_string();
+            assert!(error.contains(status.as_str()), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_remaining_after_frame() {
+        let mut buffer = WebSocketFrame::create_frame(0x2, b"hello");
+        buffer.extend_from_slice(&[0x81, 0x00]);
+        let frame = WebSocketFrame::parse_frame(&buffer, 1024).unwrap();
+        assert_eq!(remaining_after(&frame, &buffer), 2);
+    }
+}
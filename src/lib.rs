@@ -0,0 +1,8 @@
+//! Framing, statistics and output shared by the benchmark binary and the hot-path benches
+
+#[macro_use]
+pub mod output;
+
+pub mod stats;
+pub mod utils;
+pub mod websocket;
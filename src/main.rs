@@ -1,15 +1,30 @@
 //! WebSocket Ping-Pong Benchmark in Rust
 //! Rewrite of C benchmark with support for TLS
 
+#[macro_use]
+extern crate websocket_benchmark;
+
 mod benchmark;
 mod cli;
-mod stats;
-mod utils;
-mod websocket;
+mod compression;
+#[cfg(feature = "h2")]
+mod http2;
+mod mix;
+mod pacing;
+mod profile;
+mod report;
+#[cfg(all(feature = "shm", unix))]
+mod shm;
+mod trace;
+
+use websocket_benchmark::{output, stats, utils, websocket};
 
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use clap::Parser;
+use tracing_subscriber::EnvFilter;
 
 // Global configuration
 pub static QUIET_MODE: AtomicBool = AtomicBool::new(false);
@@ -19,11 +34,61 @@ use cli::Args;
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    // Set quiet mode globally
-    QUIET_MODE.store(args.quiet, Ordering::SeqCst);
+    // Logs go to stderr so they don't interleave with the report tables
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(&args.log_level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
 
     // Create async runtime
-    let rt = tokio::runtime::Runtime::new()?;
+    let mut builder = if args.current_thread {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    };
+    builder.enable_all();
+
+    if let Some(threads) = args.runtime_threads {
+        builder.worker_threads(threads as usize);
+    }
+
+    if !args.cpu_affinity.is_empty() {
+        let cores = args.cpu_affinity.clone();
+        // New threads inherit the affinity of the thread that spawns them, so keep the original
+        let unpinned = utils::Affinity::current().map_err(anyhow::Error::msg)?;
+
+        // Sync benchmarks (and the current-thread runtime) run on the main thread
+        utils::pin_current_thread(cores[0]).map_err(anyhow::Error::msg)?;
+
+        // One worker per listed core unless set explicitly, assigned in thread start order;
+        // the current-thread runtime has no workers and runs on the main thread
+        let workers = match args.runtime_threads {
+            _ if args.current_thread => 0,
+            Some(threads) => threads as usize,
+            None => {
+                builder.worker_threads(cores.len());
+                cores.len()
+            }
+        };
+        let started = Arc::new(AtomicUsize::new(0));
+        builder.on_thread_start(move || {
+            // The workers are started when the runtime is built; any later thread belongs to
+            // the blocking pool (spawn_blocking, file I/O) and gets the original affinity back
+            let index = started.fetch_add(1, Ordering::Relaxed);
+            if index < workers {
+                let core = cores[index % cores.len()];
+                if let Err(e) = utils::pin_current_thread(core) {
+                    tracing::warn!(core, error = %e, "failed to pin runtime worker");
+                }
+            } else if let Err(e) = unpinned.apply() {
+                tracing::warn!(error = %e, "failed to unpin blocking-pool thread");
+            }
+        });
+    }
+
+    let rt = builder.build()?;
 
     rt.block_on(async {
         cli::run_interactive_or_command(args).await
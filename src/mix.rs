@@ -0,0 +1,155 @@
+//! Weighted mixed-size payload workloads (--mix)
+
+use crate::pacing::make_rng;
+use crate::stats::RttStats;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use serde::Serialize;
+
+/// Filler repeated to build payloads of each size class
+const FILLER: &[u8] = b"PING";
+
+/// One size class of a --mix spec
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MixEntry {
+    pub size: usize,
+    pub weight: f64,
+}
+
+/// Parse a spec of comma-separated size:weight pairs, e.g. `64:70,1024:25,16384:5`.
+///
+/// Weights are relative (they need not sum to 100) but must be positive.
+pub fn parse_mix(spec: &str) -> Result<Vec<MixEntry>, String> {
+    let mut entries: Vec<MixEntry> = Vec::new();
+
+    for pair in spec.split(',').map(str::trim) {
+        let (size, weight) = pair
+            .split_once(':')
+            .ok_or_else(|| format!("Expected SIZE:WEIGHT, got \"{}\"", pair))?;
+        let size: usize = size
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid size \"{}\" in \"{}\"", size, pair))?;
+        let weight: f64 = weight
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid weight \"{}\" in \"{}\"", weight, pair))?;
+
+        if !weight.is_finite() || weight <= 0.0 {
+            return Err(format!("Weight must be positive in \"{}\"", pair));
+        }
+        if entries.iter().any(|entry| entry.size == size) {
+            return Err(format!("Size {} appears more than once", size));
+        }
+        entries.push(MixEntry { size, weight });
+    }
+
+    Ok(entries)
+}
+
+/// Picks a size class per iteration and keeps RTTs per class
+pub struct PayloadMix {
+    entries: Vec<MixEntry>,
+    payloads: Vec<Vec<u8>>,
+    index: WeightedIndex<f64>,
+    rng: StdRng,
+    rtts: Vec<Vec<f64>>,
+}
+
+impl PayloadMix {
+    /// Build the sampler, or None when no mix was configured
+    pub fn new(entries: &[MixEntry], seed: Option<u64>) -> Option<Self> {
+        if entries.is_empty() {
+            return None;
+        }
+
+        let index = WeightedIndex::new(entries.iter().map(|entry| entry.weight))
+            .expect("weights validated by parse_mix");
+        let payloads = entries
+            .iter()
+            .map(|entry| FILLER.iter().copied().cycle().take(entry.size).collect())
+            .collect();
+
+        // Offset the seed so sizes don't mirror the pacing RNG's sequence
+        Some(PayloadMix {
+            entries: entries.to_vec(),
+            payloads,
+            index,
+            rng: make_rng(seed.map(|seed| seed.wrapping_add(1))),
+            rtts: vec![Vec::new(); entries.len()],
+        })
+    }
+
+    /// Choose the size class for the next iteration
+    pub fn pick(&mut self) -> usize {
+        self.index.sample(&mut self.rng)
+    }
+
+    /// Payload of a size class
+    pub fn payload(&self, class: usize) -> &[u8] {
+        &self.payloads[class]
+    }
+
+    /// Record the RTT of an iteration that used `class`
+    pub fn record(&mut self, class: usize, rtt_ms: f64) {
+        self.rtts[class].push(rtt_ms);
+    }
+
+    /// Print RTT statistics per size class
+    pub fn print(&self) {
+        let total_weight: f64 = self.entries.iter().map(|entry| entry.weight).sum();
+
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ RTT by payload size:                                         │");
+        for (entry, rtts) in self.entries.iter().zip(&self.rtts) {
+            let stats = RttStats::new(rtts.clone());
+            outln!("│   {:>8} B ({:4.1}%) | n={:<7} | Avg: {:7.3} ms | P99: {:7.3} ms",
+                entry.size, entry.weight / total_weight * 100.0, stats.count,
+                stats.avg, stats.percentile(99.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mix() {
+        let entries = parse_mix("64:70, 1024:25,16384:5").unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], MixEntry { size: 1024, weight: 25.0 });
+    }
+
+    #[test]
+    fn test_parse_mix_rejects_malformed() {
+        assert!(parse_mix("64").is_err());
+        assert!(parse_mix("64:abc").is_err());
+        assert!(parse_mix("x:10").is_err());
+        assert!(parse_mix("64:0").is_err());
+        assert!(parse_mix("64:-5").is_err());
+        assert!(parse_mix("64:10,64:20").is_err());
+        assert!(parse_mix("").is_err());
+    }
+
+    #[test]
+    fn test_payload_mix_follows_weights() {
+        let entries = parse_mix("10:90,20:10").unwrap();
+        let mut mix = PayloadMix::new(&entries, Some(7)).unwrap();
+
+        let small = (0..10_000).filter(|_| mix.pick() == 0).count();
+        assert!((8_500..9_500).contains(&small), "small picked {} times", small);
+        assert_eq!(mix.payload(1).len(), 20);
+    }
+
+    #[test]
+    fn test_payload_mix_is_reproducible() {
+        let entries = parse_mix("1:1,2:1,3:1").unwrap();
+        let mut a = PayloadMix::new(&entries, Some(42)).unwrap();
+        let mut b = PayloadMix::new(&entries, Some(42)).unwrap();
+
+        let picks_a: Vec<usize> = (0..100).map(|_| a.pick()).collect();
+        let picks_b: Vec<usize> = (0..100).map(|_| b.pick()).collect();
+        assert_eq!(picks_a, picks_b);
+    }
+}
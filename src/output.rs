@@ -0,0 +1,182 @@
+//! Report output sink: stdout, or an in-memory buffer for --summary-only-on-failure,
+//! plus the interactive progress bar on stderr and the --report-interval status thread
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Buffered report output while buffering is enabled
+static BUFFER: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// Progress bar of the run in progress, if one is shown
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Iterations completed so far, read by the status thread
+static ITERATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Sum of the completed iterations' RTTs in microseconds
+static RTT_SUM_US: AtomicU64 = AtomicU64::new(0);
+
+const PROGRESS_TEMPLATE: &str =
+    "{spinner} [{bar:40}] {pos}/{len} iterations | {per_sec} | ETA {eta}";
+
+/// Print a line of report output (println! routed through the output sink)
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::output::write_line(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write_line(format_args!($($arg)*))
+    };
+}
+
+/// Capture report output in memory instead of printing it
+pub fn start_buffering() {
+    *BUFFER.lock().unwrap() = Some(Vec::new());
+}
+
+/// Stop buffering and return everything captured so far
+pub fn take_buffer() -> Option<Vec<u8>> {
+    BUFFER.lock().unwrap().take()
+}
+
+/// Write one line to the buffer if buffering, otherwise to stdout
+pub fn write_line(args: fmt::Arguments) {
+    let mut buffer = BUFFER.lock().unwrap();
+    match buffer.as_mut() {
+        Some(buffer) => {
+            let _ = writeln!(buffer, "{}", args);
+        }
+        None => match PROGRESS.lock().unwrap().as_ref() {
+            // Hide the bar while printing so lines don't land on top of it
+            Some(bar) => bar.suspend(|| {
+                let _ = writeln!(io::stdout().lock(), "{}", args);
+            }),
+            None => {
+                let _ = writeln!(io::stdout().lock(), "{}", args);
+            }
+        },
+    }
+}
+
+/// Show a progress bar on stderr for a run of `total` iterations.
+///
+/// Only for interactive use: skipped when stdout isn't a terminal or output is buffered.
+pub fn start_progress(total: u64) {
+    if !io::stdout().is_terminal() || BUFFER.lock().unwrap().is_some() {
+        return;
+    }
+
+    let bar = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr());
+    if let Ok(style) = ProgressStyle::with_template(PROGRESS_TEMPLATE) {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    *PROGRESS.lock().unwrap() = Some(bar);
+}
+
+/// Count one completed iteration
+pub fn advance_progress() {
+    if let Some(bar) = PROGRESS.lock().unwrap().as_ref() {
+        bar.inc(1);
+    }
+}
+
+/// Count a completed iteration for the status thread; two relaxed atomic adds, no formatting
+pub fn record_iteration(rtt_ms: f64) {
+    ITERATIONS.fetch_add(1, Ordering::Relaxed);
+    RTT_SUM_US.fetch_add((rtt_ms * 1000.0) as u64, Ordering::Relaxed);
+}
+
+/// Background thread printing a progress line from the iteration counters every interval.
+///
+/// Keeps formatting and printing out of the measurement loop; stops when dropped.
+pub struct StatusThread {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StatusThread {
+    /// Reset the counters and start reporting every `interval`
+    pub fn start(interval: Duration) -> Self {
+        ITERATIONS.store(0, Ordering::Relaxed);
+        RTT_SUM_US.store(0, Ordering::Relaxed);
+
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let (stopped, wake) = &*thread_stop;
+            let mut last = (Instant::now(), 0, 0);
+            let mut guard = stopped.lock().unwrap();
+            loop {
+                guard = wake.wait_timeout(guard, interval).unwrap().0;
+                if *guard {
+                    return;
+                }
+                last = print_status(last);
+            }
+        });
+
+        StatusThread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for StatusThread {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        wake.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Print progress since the previous `(time, iterations, rtt_sum_us)` sample and return the new one
+fn print_status(last: (Instant, u64, u64)) -> (Instant, u64, u64) {
+    let now = Instant::now();
+    let iterations = ITERATIONS.load(Ordering::Relaxed);
+    let rtt_sum_us = RTT_SUM_US.load(Ordering::Relaxed);
+
+    let delta = iterations - last.1;
+    let throughput = (delta * 2) as f64 / now.duration_since(last.0).as_secs_f64();
+    if delta == 0 {
+        outln!("[progress] {} iterations | no responses this interval", iterations);
+    } else {
+        let avg_rtt = (rtt_sum_us - last.2) as f64 / 1000.0 / delta as f64;
+        outln!(
+            "[progress] {} iterations | avg RTT {:.3} ms | {:.1} msg/sec",
+            iterations, avg_rtt, throughput
+        );
+    }
+    (now, iterations, rtt_sum_us)
+}
+
+/// Remove the progress bar, if one is shown
+pub fn finish_progress() {
+    if let Some(bar) = PROGRESS.lock().unwrap().take() {
+        bar.finish_and_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffered_output() {
+        start_buffering();
+        outln!("Avg RTT: {:.1} ms", 2.0);
+        outln!();
+
+        assert_eq!(take_buffer().unwrap(), b"Avg RTT: 2.0 ms\n\n");
+        assert!(take_buffer().is_none());
+    }
+}
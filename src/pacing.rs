@@ -0,0 +1,147 @@
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Inter-arrival distribution for paced sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arrival {
+    /// Fixed gap of --interval-ms
+    #[default]
+    Constant,
+    /// Exponentially distributed gaps with mean --interval-ms
+    Poisson,
+    /// Gaps uniformly distributed in [0, 2 × --interval-ms]
+    Uniform,
+}
+
+/// Build the benchmark RNG, seeded for reproducible runs when a seed is given
+pub fn make_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Schedules sends according to an arrival distribution
+pub struct Pacer {
+    arrival: Arrival,
+    mean_gap_ms: f64,
+    rng: StdRng,
+    next_send: Option<Instant>,
+    last_scheduled: Option<Instant>,
+    first_send: Option<Instant>,
+    last_send: Option<Instant>,
+    sends: u64,
+}
+
+impl Pacer {
+    pub fn new(arrival: Arrival, mean_gap_ms: f64, seed: Option<u64>) -> Self {
+        Pacer {
+            arrival,
+            mean_gap_ms: mean_gap_ms.max(0.0),
+            rng: make_rng(seed),
+            next_send: None,
+            last_scheduled: None,
+            first_send: None,
+            last_send: None,
+            sends: 0,
+        }
+    }
+
+    /// Sample the gap before the next send in milliseconds
+    fn sample_gap_ms(&mut self) -> f64 {
+        match self.arrival {
+            Arrival::Constant => self.mean_gap_ms,
+            Arrival::Poisson => {
+                // Inverse CDF of the exponential distribution; 1 - u is in (0, 1]
+                let u: f64 = self.rng.gen();
+                -(1.0 - u).ln() * self.mean_gap_ms
+            }
+            Arrival::Uniform => self.rng.gen_range(0.0..=2.0 * self.mean_gap_ms),
+        }
+    }
+
+    /// Time to wait before the next send; advances the schedule
+    pub fn next_delay(&mut self) -> Duration {
+        let now = Instant::now();
+        let scheduled = self.next_send.unwrap_or(now);
+
+        let gap = Duration::from_secs_f64(self.sample_gap_ms() / 1000.0);
+        self.next_send = Some(scheduled + gap);
+        self.last_scheduled = Some(scheduled);
+
+        self.first_send.get_or_insert(scheduled);
+        self.last_send = Some(scheduled.max(now));
+        self.sends += 1;
+
+        scheduled.saturating_duration_since(now)
+    }
+
+    /// Intended time of the latest send, however late it actually went out
+    pub fn scheduled(&self) -> Option<Instant> {
+        self.last_scheduled
+    }
+
+    /// Target send rate in messages per second
+    pub fn target_rate(&self) -> f64 {
+        if self.mean_gap_ms > 0.0 {
+            1000.0 / self.mean_gap_ms
+        } else {
+            0.0
+        }
+    }
+
+    /// Achieved send rate in messages per second
+    pub fn achieved_rate(&self) -> f64 {
+        match (self.first_send, self.last_send) {
+            (Some(first), Some(last)) if last > first => {
+                (self.sends - 1) as f64 / (last - first).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Print target vs achieved rate
+    pub fn print(&self) {
+        let arrival = format!("{:?}", self.arrival).to_lowercase();
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Pacing ({}):", arrival);
+        outln!("│   Target rate:  {:8.1} msg/sec                             │", self.target_rate());
+        outln!("│   Achieved:     {:8.1} msg/sec                             │", self.achieved_rate());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean_gap(arrival: Arrival, seed: u64) -> f64 {
+        let mut pacer = Pacer::new(arrival, 10.0, Some(seed));
+        let samples = 10_000;
+        (0..samples).map(|_| pacer.sample_gap_ms()).sum::<f64>() / samples as f64
+    }
+
+    #[test]
+    fn test_constant_gap() {
+        assert_eq!(mean_gap(Arrival::Constant, 1), 10.0);
+    }
+
+    #[test]
+    fn test_poisson_and_uniform_mean() {
+        assert!((mean_gap(Arrival::Poisson, 7) - 10.0).abs() < 0.5);
+        assert!((mean_gap(Arrival::Uniform, 7) - 10.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_seeded_schedule_is_reproducible() {
+        let mut a = Pacer::new(Arrival::Poisson, 5.0, Some(42));
+        let mut b = Pacer::new(Arrival::Poisson, 5.0, Some(42));
+
+        for _ in 0..100 {
+            assert_eq!(a.sample_gap_ms(), b.sample_gap_ms());
+        }
+    }
+}
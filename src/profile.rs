@@ -0,0 +1,77 @@
+//! Built-in option presets (--profile)
+
+use crate::benchmark::BenchmarkConfig;
+use clap::ValueEnum;
+
+/// A named starting configuration; explicit flags still override each setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// LAN latency: 1000 paced 64-byte pings every 10 ms, quiet
+    Latency,
+    /// Throughput: 100000 unpaced 16 KiB pings, 100 multi-connection clients, quiet
+    Throughput,
+    /// WAN soak: 1000000 pings every 100 ms with reconnect, memory tracking and progress every 60 s
+    Soak,
+}
+
+impl Profile {
+    /// Apply the preset to the base config that command-line flags are layered on
+    pub fn apply(self, config: &mut BenchmarkConfig) {
+        match self {
+            Profile::Latency => latency(config),
+            Profile::Throughput => throughput(config),
+            Profile::Soak => soak(config),
+        }
+    }
+}
+
+/// Small paced pings so queuing doesn't inflate the RTTs
+fn latency(config: &mut BenchmarkConfig) {
+    config.ping_pong_count = 1000;
+    config.payload_size = Some(64);
+    config.interval_ms = Some(10.0);
+    config.quiet = true;
+}
+
+/// Back-to-back large pings to saturate the link
+fn throughput(config: &mut BenchmarkConfig) {
+    config.ping_pong_count = 100_000;
+    config.payload_size = Some(16 * 1024);
+    config.interval_ms = None;
+    config.clients = 100;
+    config.quiet = true;
+}
+
+/// Long, slow run that survives drops and shows drift over time
+fn soak(config: &mut BenchmarkConfig) {
+    config.ping_pong_count = 1_000_000;
+    config.interval_ms = Some(100.0);
+    config.reconnect = true;
+    config.track_memory = true;
+    config.report_interval_secs = Some(60.0);
+    config.quiet = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_change_defaults() {
+        let default = BenchmarkConfig::default();
+
+        let mut latency = BenchmarkConfig::default();
+        Profile::Latency.apply(&mut latency);
+        assert_eq!(latency.interval_ms, Some(10.0));
+        assert_ne!(latency.ping_pong_count, default.ping_pong_count);
+
+        let mut throughput = BenchmarkConfig::default();
+        Profile::Throughput.apply(&mut throughput);
+        assert_eq!(throughput.interval_ms, None);
+        assert!(throughput.payload_size > latency.payload_size);
+
+        let mut soak = BenchmarkConfig::default();
+        Profile::Soak.apply(&mut soak);
+        assert!(soak.reconnect && soak.track_memory);
+    }
+}
@@ -0,0 +1,1100 @@
+use crate::benchmark::BenchmarkConfig;
+use crate::stats::{select_percentile, CpuTime, RttStats};
+use crate::utils::hostname;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the exported JSON layout; bump on any breaking change
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How the run summary is presented on stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Box-drawing tables as the run progresses
+    #[default]
+    Text,
+    /// Only a GitHub-flavored Markdown summary table, for issues and PRs
+    Markdown,
+}
+
+/// Encoder applied to an exported results file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCompression {
+    /// gzip (.gz)
+    Gzip,
+    /// Zstandard (.zst); requires building with the zstd feature
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Compression implied by a `.gz` / `.zst` extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(OutputCompression::Gzip),
+            Some("zst") => Some(OutputCompression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// An export file, optionally streamed through an encoder.
+///
+/// `finish` must be called to write the encoder's trailer; dropping the writer
+/// leaves a truncated archive.
+enum OutputWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputWriter {
+    fn new(file: File, compression: Option<OutputCompression>) -> Result<Self> {
+        let writer = BufWriter::new(file);
+        match compression {
+            None => Ok(OutputWriter::Plain(writer)),
+            Some(OutputCompression::Gzip) => {
+                Ok(OutputWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default())))
+            }
+            #[cfg(feature = "zstd")]
+            Some(OutputCompression::Zstd) => Ok(OutputWriter::Zstd(zstd::Encoder::new(writer, 0)?)),
+            #[cfg(not(feature = "zstd"))]
+            Some(OutputCompression::Zstd) => {
+                anyhow::bail!("zstd output requires building with --features zstd")
+            }
+        }
+    }
+
+    /// Finalize the encoder and flush everything to the file
+    fn finish(self) -> Result<()> {
+        let mut writer = match self {
+            OutputWriter::Plain(writer) => writer,
+            OutputWriter::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            OutputWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputWriter::Plain(writer) => writer.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            OutputWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::Plain(writer) => writer.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            OutputWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Summary statistics of a single benchmark run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSummary {
+    pub count: usize,
+    pub avg: f64,
+    /// Population standard deviation (absent in exports that predate it)
+    #[serde(default)]
+    pub stddev: Option<f64>,
+    /// Median RTT, null when the samples weren't kept (--no-percentiles)
+    #[serde(default)]
+    pub median: Option<f64>,
+    /// 99th percentile RTT, null without samples or in exports that predate it
+    #[serde(default)]
+    pub p99: Option<f64>,
+    pub min: f64,
+    pub max: f64,
+    pub wall_time: f64,
+    pub throughput: f64,
+}
+
+impl StatsSummary {
+    pub fn new(stats: &RttStats, wall_time: f64) -> Self {
+        let throughput = if wall_time > 0.0 {
+            (stats.count * 2) as f64 / wall_time // Each ping-pong is 2 messages
+        } else {
+            0.0
+        };
+
+        // The median and percentiles of a --no-percentiles run are unknown, not zero
+        let has_samples = stats.has_samples();
+        StatsSummary {
+            count: stats.count,
+            avg: stats.avg,
+            stddev: Some(stats.stddev),
+            median: has_samples.then_some(stats.median),
+            p99: has_samples.then(|| stats.percentile(99.0)),
+            min: stats.min,
+            max: stats.max,
+            wall_time,
+            throughput,
+        }
+    }
+}
+
+/// Data received by a download-style run (benchmark 14)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferSummary {
+    /// Bytes read off the connection, frame headers included
+    pub bytes: u64,
+    /// Size of the received message, after inflating
+    pub message_bytes: u64,
+    pub frames: usize,
+    pub first_frame_ms: f64,
+    /// Download throughput in bytes per second
+    pub bytes_per_sec: f64,
+}
+
+/// Exported result of one benchmark run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub benchmark: String,
+    pub host: String,
+    pub port: u16,
+    pub requested: u32,
+    pub summary: StatsSummary,
+    /// Raw RTT samples in milliseconds (absent in summary-only exports)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtts: Option<Vec<f64>>,
+    /// Bytes and download throughput (only for runs that measure a transfer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferSummary>,
+}
+
+impl BenchmarkReport {
+    pub fn new(name: &str, config: &BenchmarkConfig, stats: &RttStats, cpu: &CpuTime) -> Self {
+        BenchmarkReport {
+            benchmark: name.to_string(),
+            host: config.host.clone(),
+            port: config.port,
+            requested: config.ping_pong_count,
+            summary: StatsSummary::new(stats, cpu.wall_time),
+            rtts: Some(stats.rtts.clone()),
+            transfer: None,
+        }
+    }
+}
+
+/// Environment that produced a set of results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    pub crate_version: String,
+    pub rustc_version: String,
+    /// Tokio runtime flavor and worker count (empty in exports that predate it)
+    #[serde(default)]
+    pub runtime: String,
+    /// Fully resolved benchmark configuration
+    pub config: serde_json::Value,
+}
+
+impl Metadata {
+    pub fn collect(config: &BenchmarkConfig) -> Self {
+        Metadata {
+            hostname: hostname(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            rustc_version: env!("RUSTC_VERSION").to_string(),
+            runtime: config.runtime_description(),
+            config: serde_json::to_value(config).unwrap_or_default(),
+        }
+    }
+
+    /// Print the environment footer
+    pub fn print(&self) {
+        outln!("┌──────────────────────────────────────────────────────────────┐");
+        outln!("│ Environment                                                  │");
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Host: {} | {}/{} | {} CPUs", self.hostname, self.os, self.arch, self.cpus);
+        outln!("│ websocket_benchmark {} | {}", self.crate_version, self.rustc_version);
+        outln!("│ Runtime: {}", self.runtime);
+        outln!("│ Config: {}", self.config);
+        outln!("└──────────────────────────────────────────────────────────────┘");
+    }
+}
+
+/// Contents of an exported JSON results file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResultsFile {
+    /// Layout version; files written before versioning read as 0
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    pub runs: Vec<BenchmarkReport>,
+}
+
+/// JSON Schema describing the export layout of SCHEMA_VERSION
+pub fn json_schema() -> serde_json::Value {
+    let number = serde_json::json!({ "type": "number" });
+    let nullable_number = serde_json::json!({ "type": ["number", "null"] });
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "websocket_benchmark results",
+        "type": "object",
+        "required": ["schema_version", "runs"],
+        "properties": {
+            "schema_version": { "type": "integer", "const": SCHEMA_VERSION },
+            "metadata": {
+                "type": "object",
+                "properties": {
+                    "hostname": { "type": "string" },
+                    "os": { "type": "string" },
+                    "arch": { "type": "string" },
+                    "cpus": { "type": "integer", "minimum": 1 },
+                    "crate_version": { "type": "string" },
+                    "rustc_version": { "type": "string" },
+                    "runtime": { "type": "string" },
+                    "config": { "type": "object" }
+                }
+            },
+            "runs": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["benchmark", "host", "port", "requested", "summary"],
+                    "properties": {
+                        "benchmark": { "type": "string" },
+                        "host": { "type": "string" },
+                        "port": { "type": "integer", "minimum": 0, "maximum": 65535 },
+                        "requested": { "type": "integer", "minimum": 0 },
+                        "summary": {
+                            "type": "object",
+                            "required": ["count", "avg", "median", "min", "max", "wall_time", "throughput"],
+                            "properties": {
+                                "count": { "type": "integer", "minimum": 0 },
+                                "avg": number,
+                                "stddev": nullable_number,
+                                "median": nullable_number,
+                                "p99": nullable_number,
+                                "min": number,
+                                "max": number,
+                                "wall_time": number,
+                                "throughput": number
+                            }
+                        },
+                        "rtts": { "type": "array", "items": number },
+                        "transfer": {
+                            "type": "object",
+                            "required": ["bytes", "message_bytes", "frames", "first_frame_ms", "bytes_per_sec"],
+                            "properties": {
+                                "bytes": { "type": "integer", "minimum": 0 },
+                                "message_bytes": { "type": "integer", "minimum": 0 },
+                                "frames": { "type": "integer", "minimum": 0 },
+                                "first_frame_ms": number,
+                                "bytes_per_sec": number
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Write benchmark reports to a JSON file
+pub fn write_json(
+    path: &Path,
+    reports: &[BenchmarkReport],
+    metadata: &Metadata,
+    compression: Option<OutputCompression>,
+) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = OutputWriter::new(file, compression)?;
+
+    let results = ResultsFile {
+        schema_version: SCHEMA_VERSION,
+        metadata: Some(metadata.clone()),
+        runs: reports.to_vec(),
+    };
+    serde_json::to_writer_pretty(&mut writer, &results)?;
+    writer.write_all(b"\n")?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Column order of the CSV export
+const CSV_HEADER: &str =
+    "run_id,benchmark,host,port,requested,count,avg,stddev,median,p99,min,max,wall_time,throughput,bytes,bytes_per_sec";
+
+/// Identifies one invocation among rows accumulated with --append
+pub fn run_id() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    format!("{}-{}", secs, std::process::id())
+}
+
+/// Write results in the format implied by the extension: `.csv`, `.jsonl` or JSON.
+///
+/// With `append`, CSV and JSON-lines rows are added to an existing file and the
+/// CSV header is only written when the file is new or empty; appending to a CSV
+/// with different columns is an error.
+///
+/// A trailing `.gz` / `.zst` (or an explicit `compression`) streams the export
+/// through that encoder; the format comes from the extension before it. Appended
+/// runs become separate compressed members, which gzip and zstd decode as one stream.
+pub fn write_results(
+    path: &Path,
+    reports: &[BenchmarkReport],
+    metadata: &Metadata,
+    append: bool,
+    compression: Option<OutputCompression>,
+) -> Result<()> {
+    let inferred = OutputCompression::from_path(path);
+    let format_path = match inferred {
+        Some(_) => Path::new(path.file_stem().unwrap_or_default()),
+        None => path,
+    };
+    let compression = compression.or(inferred);
+
+    let extension = format_path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    match extension {
+        "csv" => write_csv(path, reports, &run_id(), append, compression),
+        "jsonl" => write_json_lines(path, reports, &run_id(), append, compression),
+        _ if append => anyhow::bail!("--append needs a .csv or .jsonl output, got {}", path.display()),
+        _ => write_json(path, reports, metadata, compression),
+    }
+}
+
+/// Open an export file for writing, truncating it unless appending
+fn open_output(path: &Path, append: bool) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Refuse to append to a CSV whose columns differ, e.g. one written by an older version
+fn check_csv_header(path: &Path, compression: Option<OutputCompression>) -> Result<()> {
+    let mut header = String::new();
+    BufReader::new(open_input(path, compression)?)
+        .read_line(&mut header)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let header = header.trim_end();
+    if header != CSV_HEADER {
+        anyhow::bail!(
+            "Can't append to {}: its columns differ from this version's\n  found:    {}\n  expected: {}",
+            path.display(),
+            header,
+            CSV_HEADER
+        );
+    }
+    Ok(())
+}
+
+/// Write one CSV row per report
+pub fn write_csv(
+    path: &Path,
+    reports: &[BenchmarkReport],
+    run_id: &str,
+    append: bool,
+    compression: Option<OutputCompression>,
+) -> Result<()> {
+    let file = open_output(path, append)?;
+    let is_empty = file.metadata()?.len() == 0;
+    if !is_empty {
+        check_csv_header(path, compression)?;
+    }
+    let mut writer = OutputWriter::new(file, compression)?;
+
+    if is_empty {
+        writeln!(writer, "{}", CSV_HEADER)?;
+    }
+    for report in reports {
+        let summary = &report.summary;
+        // Transfer columns stay empty for ping-pong runs
+        let (bytes, bytes_per_sec) = match &report.transfer {
+            Some(transfer) => (transfer.bytes.to_string(), transfer.bytes_per_sec.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(run_id),
+            csv_field(&report.benchmark),
+            csv_field(&report.host),
+            report.port,
+            report.requested,
+            summary.count,
+            summary.avg,
+            optional_field(summary.stddev),
+            optional_field(summary.median),
+            optional_field(summary.p99),
+            summary.min,
+            summary.max,
+            summary.wall_time,
+            summary.throughput,
+            bytes,
+            bytes_per_sec
+        )?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// A JSON-lines record: one report tagged with its run
+#[derive(Serialize)]
+struct ReportLine<'a> {
+    run_id: &'a str,
+    schema_version: u32,
+    #[serde(flatten)]
+    report: &'a BenchmarkReport,
+}
+
+/// Write one JSON object per line per report
+pub fn write_json_lines(
+    path: &Path,
+    reports: &[BenchmarkReport],
+    run_id: &str,
+    append: bool,
+    compression: Option<OutputCompression>,
+) -> Result<()> {
+    let mut writer = OutputWriter::new(open_output(path, append)?, compression)?;
+
+    for report in reports {
+        let line = ReportLine {
+            run_id,
+            schema_version: SCHEMA_VERSION,
+            report,
+        };
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// GitHub-flavored Markdown summary: a heading with the benchmarks and config, then one row per run
+pub fn markdown_summary(reports: &[BenchmarkReport], config: &BenchmarkConfig) -> String {
+    let names: Vec<&str> = reports.iter().map(|report| report.benchmark.as_str()).collect();
+    let mut markdown = format!("### {}\n\n", names.join(", "));
+    markdown.push_str(&format!(
+        "`{}` · {} ping-pong · {} B payload · {}\n\n",
+        config.ws_url(&config.host),
+        config.ping_pong_count,
+        config.ping_payload().len(),
+        config.runtime_description()
+    ));
+    markdown.push_str("| Benchmark | Count | Avg (ms) | Median (ms) | P95 (ms) | P99 (ms) | Throughput (msg/s) |\n");
+    markdown.push_str("|---|---:|---:|---:|---:|---:|---:|\n");
+
+    for report in reports {
+        let summary = &report.summary;
+        // P95 isn't part of the summary; derive it when the raw samples were kept
+        let p95 = match &report.rtts {
+            Some(rtts) => format!("{:.3}", select_percentile(&mut rtts.clone(), 95.0)),
+            None => "-".to_string(),
+        };
+        markdown.push_str(&format!(
+            "| {} | {}/{} | {:.3} | {} | {} | {} | {:.0} |\n",
+            report.benchmark.replace('|', "\\|"),
+            summary.count,
+            report.requested,
+            summary.avg,
+            optional_ms(summary.median),
+            p95,
+            optional_ms(summary.p99),
+            summary.throughput
+        ));
+    }
+    markdown
+}
+
+/// Open an export for reading, decoding it with `compression`
+fn open_input(path: &Path, compression: Option<OutputCompression>) -> Result<Box<dyn Read>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(match compression {
+        None => Box::new(file),
+        Some(OutputCompression::Gzip) => Box::new(MultiGzDecoder::new(file)),
+        #[cfg(feature = "zstd")]
+        Some(OutputCompression::Zstd) => Box::new(zstd::Decoder::new(file)?),
+        #[cfg(not(feature = "zstd"))]
+        Some(OutputCompression::Zstd) => {
+            anyhow::bail!("Reading {} requires building with --features zstd", path.display())
+        }
+    })
+}
+
+/// Read benchmark reports from a JSON file, decompressing `.gz` / `.zst` exports
+pub fn read_json(path: &Path) -> Result<ResultsFile> {
+    let reader = open_input(path, OutputCompression::from_path(path))?;
+    let results: ResultsFile = serde_json::from_reader(BufReader::new(reader))
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    if results.schema_version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "{} uses schema version {}, newer than supported version {}",
+            path.display(),
+            results.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+
+    Ok(results)
+}
+
+/// Combined statistics from several result files
+#[derive(Debug, Clone)]
+pub struct MergedReport {
+    pub sources: Vec<(String, StatsSummary)>,
+    pub combined: StatsSummary,
+    /// False when at least one run lacked raw samples
+    pub full_fidelity: bool,
+}
+
+/// Merge runs from several sources into one combined summary.
+///
+/// Raw samples are concatenated when every run has them; otherwise the
+/// summaries are combined (count-weighted mean, extreme min/max, and a
+/// count-weighted mean of medians as an approximation).
+pub fn merge_reports(sources: &[(String, Vec<BenchmarkReport>)]) -> MergedReport {
+    let runs: Vec<&BenchmarkReport> = sources.iter().flat_map(|(_, runs)| runs).collect();
+    let full_fidelity = runs.iter().all(|run| run.rtts.is_some());
+    let wall_time = runs
+        .iter()
+        .map(|run| run.summary.wall_time)
+        .fold(0.0, f64::max);
+
+    let combined = if full_fidelity {
+        let all_rtts: Vec<f64> = runs
+            .iter()
+            .flat_map(|run| run.rtts.iter().flatten().copied())
+            .collect();
+        StatsSummary::new(&RttStats::new(all_rtts), wall_time)
+    } else {
+        combine_summaries(runs.iter().map(|run| &run.summary), wall_time)
+    };
+
+    let sources = sources
+        .iter()
+        .map(|(name, runs)| {
+            let source_wall = runs
+                .iter()
+                .map(|run| run.summary.wall_time)
+                .fold(0.0, f64::max);
+            let summary = combine_summaries(runs.iter().map(|run| &run.summary), source_wall);
+            (name.clone(), summary)
+        })
+        .collect();
+
+    MergedReport {
+        sources,
+        combined,
+        full_fidelity,
+    }
+}
+
+/// Combine summaries without raw samples
+fn combine_summaries<'a>(
+    summaries: impl Iterator<Item = &'a StatsSummary>,
+    wall_time: f64,
+) -> StatsSummary {
+    let summaries: Vec<&StatsSummary> = summaries.filter(|s| s.count > 0).collect();
+    let count: usize = summaries.iter().map(|s| s.count).sum();
+
+    if count == 0 {
+        return StatsSummary::new(&RttStats::new(Vec::new()), wall_time);
+    }
+
+    let weighted = |value: fn(&StatsSummary) -> f64| {
+        summaries
+            .iter()
+            .map(|s| value(s) * s.count as f64)
+            .sum::<f64>()
+            / count as f64
+    };
+    let weighted_option = |value: fn(&StatsSummary) -> Option<f64>| {
+        summaries
+            .iter()
+            .map(|s| value(s).map(|v| v * s.count as f64))
+            .sum::<Option<f64>>()
+            .map(|sum| sum / count as f64)
+    };
+    let throughput = if wall_time > 0.0 {
+        (count * 2) as f64 / wall_time
+    } else {
+        0.0
+    };
+
+    StatsSummary {
+        count,
+        avg: weighted(|s| s.avg),
+        stddev: pooled_stddev(&summaries, count),
+        // Unknown as soon as one source lacks it
+        median: weighted_option(|s| s.median),
+        // Without samples the worst source's p99 is a conservative bound
+        p99: summaries
+            .iter()
+            .map(|s| s.p99)
+            .collect::<Option<Vec<f64>>>()
+            .map(|p99s| p99s.into_iter().fold(0.0, f64::max)),
+        min: summaries.iter().map(|s| s.min).fold(f64::INFINITY, f64::min),
+        max: summaries.iter().map(|s| s.max).fold(f64::NEG_INFINITY, f64::max),
+        wall_time,
+        throughput,
+    }
+}
+
+/// Standard deviation of the union of several runs, from each run's mean and stddev
+fn pooled_stddev(summaries: &[&StatsSummary], count: usize) -> Option<f64> {
+    let mean = summaries.iter().map(|s| s.avg * s.count as f64).sum::<f64>() / count as f64;
+    let squares = summaries
+        .iter()
+        .map(|s| s.stddev.map(|stddev| s.count as f64 * (stddev.powi(2) + (s.avg - mean).powi(2))))
+        .sum::<Option<f64>>()?;
+    Some((squares / count as f64).sqrt())
+}
+
+/// Format an optional value, "-" when it wasn't measured
+fn optional_ms(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{:.3}", value),
+        None => "-".to_string(),
+    }
+}
+
+/// CSV cell for an optional value; empty when it wasn't measured
+fn optional_field(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Change of one metric relative to a baseline run
+#[derive(Debug, Clone)]
+pub struct MetricDelta {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    /// Percentage change from the baseline
+    pub change_pct: f64,
+    /// Worse than the baseline by more than the threshold
+    pub regressed: bool,
+}
+
+/// Compare a run against a baseline; RTTs regress upward, throughput downward
+pub fn compare_to_baseline(
+    baseline: &StatsSummary,
+    current: &StatsSummary,
+    threshold_pct: f64,
+) -> Vec<MetricDelta> {
+    let metrics: [(&'static str, Option<f64>, Option<f64>, bool); 4] = [
+        ("Avg RTT", Some(baseline.avg), Some(current.avg), false),
+        ("Median RTT", baseline.median, current.median, false),
+        ("P99 RTT", baseline.p99, current.p99, false),
+        ("Throughput", Some(baseline.throughput), Some(current.throughput), true),
+    ];
+
+    // Metrics missing on either side (e.g. percentiles of a --no-percentiles run) are skipped
+    metrics
+        .into_iter()
+        .filter_map(|(metric, baseline, current, higher_is_better)| {
+            Some((metric, baseline?, current?, higher_is_better))
+        })
+        .filter(|(_, baseline, _, _)| *baseline > 0.0)
+        .map(|(metric, baseline, current, higher_is_better)| {
+            let change_pct = (current - baseline) / baseline * 100.0;
+            let worse_pct = if higher_is_better { -change_pct } else { change_pct };
+            MetricDelta {
+                metric,
+                baseline,
+                current,
+                change_pct,
+                regressed: worse_pct > threshold_pct,
+            }
+        })
+        .collect()
+}
+
+/// Compare runs against a saved baseline export; fails if any metric regressed
+pub fn check_baseline(path: &Path, reports: &[BenchmarkReport], threshold_pct: f64) -> Result<()> {
+    let baseline = read_json(path)?;
+    if baseline.runs.is_empty() {
+        anyhow::bail!("{} contains no runs", path.display());
+    }
+    let mut regressions = 0;
+
+    for report in reports {
+        // Comparing against a different benchmark would report meaningless deltas
+        let Some(base) = baseline.runs.iter().find(|run| run.benchmark == report.benchmark) else {
+            tracing::warn!(
+                benchmark = %report.benchmark,
+                baseline = %path.display(),
+                "no baseline run with this name, skipping the comparison"
+            );
+            continue;
+        };
+
+        let deltas = compare_to_baseline(&base.summary, &report.summary, threshold_pct);
+
+        outln!("┌──────────────────────────────────────────────────────────────┐");
+        outln!("│ Baseline Comparison: {:40}│", report.benchmark);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        for delta in &deltas {
+            outln!("│ {:<11} {:10.3} -> {:10.3} ({:+7.2}%){}",
+                delta.metric, delta.baseline, delta.current, delta.change_pct,
+                if delta.regressed { "  REGRESSION" } else { "" });
+        }
+        outln!("└──────────────────────────────────────────────────────────────┘");
+
+        regressions += deltas.iter().filter(|delta| delta.regressed).count();
+    }
+
+    if regressions > 0 {
+        anyhow::bail!(
+            "{} metric(s) regressed more than {}% against {}",
+            regressions,
+            threshold_pct,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Read result files and print the merged report
+pub fn merge_files(paths: &[PathBuf]) -> Result<()> {
+    let mut sources = Vec::with_capacity(paths.len());
+    for path in paths {
+        let results = read_json(path)?;
+        sources.push((path.display().to_string(), results.runs));
+    }
+
+    let merged = merge_reports(&sources);
+
+    outln!("┌──────────────────────────────────────────────────────────────┐");
+    outln!("│ Merged Results: {:3} sources                                  │", merged.sources.len());
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    for (name, summary) in &merged.sources {
+        outln!("│ {}", name);
+        outln!("│   Count: {:8} | Avg: {:7.3} ms | Median: {:>7} ms", summary.count, summary.avg, optional_ms(summary.median));
+        outln!("│   Min:   {:7.3} ms | Max: {:7.3} ms", summary.min, summary.max);
+    }
+    outln!("├──────────────────────────────────────────────────────────────┤");
+    outln!("│ Combined ({} samples):", merged.combined.count);
+    outln!("│   Avg: {:7.3} ms | Median: {:>7} ms                       │", merged.combined.avg, optional_ms(merged.combined.median));
+    outln!("│   Min: {:7.3} ms | Max:    {:7.3} ms                       │", merged.combined.min, merged.combined.max);
+    if !merged.full_fidelity {
+        outln!("│   Note: some inputs lack raw samples; merged from summaries  │");
+        outln!("│   (median is approximate)                                    │");
+    }
+    outln!("└──────────────────────────────────────────────────────────────┘");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::OnlineStats;
+
+    fn report_with(rtts: Vec<f64>, keep_samples: bool) -> BenchmarkReport {
+        let stats = RttStats::new(rtts);
+        BenchmarkReport {
+            benchmark: "test".to_string(),
+            host: "localhost".to_string(),
+            port: 8443,
+            requested: stats.count as u32,
+            summary: StatsSummary::new(&stats, 1.0),
+            rtts: keep_samples.then(|| stats.rtts.clone()),
+            transfer: None,
+        }
+    }
+
+    #[test]
+    fn test_markdown_summary() {
+        let config = BenchmarkConfig::default();
+        let markdown = markdown_summary(&[report_with(vec![1.0, 2.0, 3.0, 4.0], true)], &config);
+        let lines: Vec<&str> = markdown.lines().collect();
+
+        assert_eq!(lines[0], "### test");
+        assert!(lines[2].starts_with("`wss://10.25.96.5:8443/ws` · 30 ping-pong"));
+        assert!(lines[6].starts_with("| test | 4/4 | 2.500 | "));
+        assert!(lines[6].contains("| 4.000 | 4.000 |"));
+
+        let without_samples = markdown_summary(&[report_with(vec![1.0], false)], &config);
+        assert!(without_samples.lines().nth(6).unwrap().contains("| - |"));
+    }
+
+    #[test]
+    fn test_merge_with_raw_samples() {
+        let sources = vec![
+            ("a.json".to_string(), vec![report_with(vec![1.0, 2.0, 3.0], true)]),
+            ("b.json".to_string(), vec![report_with(vec![4.0, 5.0], true)]),
+        ];
+
+        let merged = merge_reports(&sources);
+
+        assert!(merged.full_fidelity);
+        assert_eq!(merged.sources.len(), 2);
+        assert_eq!(merged.combined.count, 5);
+        assert_eq!(merged.combined.avg, 3.0);
+        assert_eq!(merged.combined.median, Some(3.0));
+        assert!((merged.combined.stddev.unwrap() - 2.0f64.sqrt()).abs() < 1e-12);
+        assert_eq!(merged.combined.min, 1.0);
+        assert_eq!(merged.combined.max, 5.0);
+    }
+
+    #[test]
+    fn test_merge_summary_only() {
+        let sources = vec![
+            ("a.json".to_string(), vec![report_with(vec![1.0, 2.0, 3.0], true)]),
+            ("b.json".to_string(), vec![report_with(vec![10.0], false)]),
+        ];
+
+        let merged = merge_reports(&sources);
+
+        assert!(!merged.full_fidelity);
+        assert_eq!(merged.combined.count, 4);
+        assert_eq!(merged.combined.avg, 4.0); // (2 * 3 + 10) / 4
+        assert_eq!(merged.combined.min, 1.0);
+        assert_eq!(merged.combined.max, 10.0);
+        // Pooled from each source's mean and stddev: same as the stddev of [1, 2, 3, 10]
+        assert!((merged.combined.stddev.unwrap() - 12.5f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_summary_without_percentiles() {
+        let mut online = OnlineStats::default();
+        for rtt in [1.0, 2.0, 3.0] {
+            online.record(rtt);
+        }
+        let summary = StatsSummary::new(&RttStats::from_online(&online), 1.0);
+        assert_eq!(summary.median, None);
+        assert_eq!(summary.p99, None);
+        assert_eq!(summary.stddev, Some(online.stddev()));
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(json["median"].is_null());
+        assert!(json["p99"].is_null());
+
+        // A merge or baseline comparison can't invent the missing percentiles
+        let sources = vec![
+            ("a.json".to_string(), vec![report_with(vec![1.0, 2.0], false)]),
+            ("b.json".to_string(), vec![BenchmarkReport { summary: summary.clone(), ..report_with(vec![], false) }]),
+        ];
+        assert_eq!(merge_reports(&sources).combined.median, None);
+
+        let baseline = StatsSummary::new(&RttStats::new(vec![10.0; 3]), 1.0);
+        let metrics: Vec<&str> = compare_to_baseline(&baseline, &summary, 10.0)
+            .iter()
+            .map(|delta| delta.metric)
+            .collect();
+        assert_eq!(metrics, ["Avg RTT", "Throughput"]);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let report = report_with(vec![1.0, 2.0], true);
+        let results = ResultsFile {
+            schema_version: SCHEMA_VERSION,
+            metadata: Some(Metadata::collect(&BenchmarkConfig::default())),
+            runs: vec![report],
+        };
+
+        let json = serde_json::to_string(&results).unwrap();
+        let parsed: ResultsFile = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.schema_version, SCHEMA_VERSION);
+        assert_eq!(parsed.metadata.unwrap().config["port"], 8443);
+        assert_eq!(parsed.runs.len(), 1);
+        assert_eq!(parsed.runs[0].summary.count, 2);
+        assert_eq!(parsed.runs[0].rtts, Some(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_schema_matches_export() {
+        let results = ResultsFile {
+            schema_version: SCHEMA_VERSION,
+            metadata: None,
+            runs: vec![report_with(vec![1.0], true)],
+        };
+        let json = serde_json::to_value(&results).unwrap();
+        let schema = json_schema();
+
+        let required = |schema: &serde_json::Value, value: &serde_json::Value| {
+            for field in schema["required"].as_array().unwrap() {
+                assert!(value.get(field.as_str().unwrap()).is_some(), "missing {}", field);
+            }
+        };
+        let run_schema = &schema["properties"]["runs"]["items"];
+        required(&schema, &json);
+        required(run_schema, &json["runs"][0]);
+        required(&run_schema["properties"]["summary"], &json["runs"][0]["summary"]);
+
+        // Optional sections are left out unless the run measured them
+        assert!(json["runs"][0].get("transfer").is_none());
+        let mut report = report_with(vec![1.0], false);
+        report.transfer = Some(TransferSummary {
+            bytes: 1_000_006,
+            message_bytes: 1_000_000,
+            frames: 16,
+            first_frame_ms: 0.5,
+            bytes_per_sec: 2.0e6,
+        });
+        let json = serde_json::to_value(&report).unwrap();
+        required(&run_schema["properties"]["transfer"], &json["transfer"]);
+        let parsed: BenchmarkReport = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.transfer, report.transfer);
+    }
+
+    #[test]
+    fn test_compare_to_baseline() {
+        let baseline = StatsSummary::new(&RttStats::new(vec![10.0; 10]), 1.0);
+        let slower = StatsSummary::new(&RttStats::new(vec![12.0; 10]), 1.0);
+
+        let deltas = compare_to_baseline(&baseline, &slower, 10.0);
+        let avg = deltas.iter().find(|d| d.metric == "Avg RTT").unwrap();
+        assert!((avg.change_pct - 20.0).abs() < 1e-9);
+        assert!(avg.regressed);
+
+        // Same sample count in the same time: throughput unchanged
+        let throughput = deltas.iter().find(|d| d.metric == "Throughput").unwrap();
+        assert!(!throughput.regressed);
+
+        let within = compare_to_baseline(&baseline, &slower, 25.0);
+        assert!(within.iter().all(|d| !d.regressed));
+    }
+
+    #[test]
+    fn test_check_baseline_skips_unmatched_runs() {
+        let path = std::env::temp_dir().join(format!("ws_baseline_{}.json", std::process::id()));
+        let mut fast = report_with(vec![1.0; 10], false);
+        fast.benchmark = "other".to_string();
+        let metadata = Metadata::collect(&BenchmarkConfig::default());
+        write_json(&path, &[fast.clone()], &metadata, None).unwrap();
+
+        // 100x slower, but there is nothing with the same name to compare to
+        let slow = report_with(vec![100.0; 10], false);
+        let unmatched = check_baseline(&path, std::slice::from_ref(&slow), 5.0);
+
+        fast.benchmark = slow.benchmark.clone();
+        write_json(&path, &[fast], &metadata, None).unwrap();
+        let matched = check_baseline(&path, &[slow], 5.0);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(unmatched.is_ok());
+        assert!(matched.is_err());
+    }
+
+    #[test]
+    fn test_csv_append_writes_header_once() {
+        let path = std::env::temp_dir().join(format!("ws_results_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let reports = vec![report_with(vec![1.0, 2.0, 3.0], false)];
+
+        write_csv(&path, &reports, "run-a", true, None).unwrap();
+        write_csv(&path, &reports, "run-b", true, None).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("run-a,"));
+        assert!(lines[2].starts_with("run-b,"));
+    }
+
+    #[test]
+    fn test_csv_append_rejects_other_columns() {
+        let path = std::env::temp_dir().join(format!("ws_results_old_{}.csv", std::process::id()));
+        let old_header = "run_id,benchmark,host,port,requested,count,avg,median,min,max,wall_time,throughput";
+        std::fs::write(&path, format!("{}\nrun-a,x,h,1,1,1,1,1,1,1,1,1\n", old_header)).unwrap();
+        let reports = vec![report_with(vec![1.0, 2.0, 3.0], false)];
+
+        let result = write_csv(&path, &reports, "run-b", true, None);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("columns differ"));
+        assert_eq!(contents.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_gzip_csv_append_decodes_as_one_stream() {
+        let path = std::env::temp_dir().join(format!("ws_results_{}.csv.gz", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let reports = vec![report_with(vec![1.0, 2.0, 3.0], false)];
+        let metadata = Metadata::collect(&BenchmarkConfig::default());
+
+        write_results(&path, &reports, &metadata, true, None).unwrap();
+        write_results(&path, &reports, &metadata, true, None).unwrap();
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut contents = String::new();
+        MultiGzDecoder::new(compressed.as_slice()).read_to_string(&mut contents).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        let throughput_column = CSV_HEADER.split(',').position(|column| column == "throughput").unwrap();
+        let row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(row[throughput_column], reports[0].summary.throughput.to_string());
+    }
+
+    #[test]
+    fn test_json_lines_append() {
+        let path = std::env::temp_dir().join(format!("ws_results_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let reports = vec![report_with(vec![1.0, 2.0], false)];
+
+        write_json_lines(&path, &reports, "run-a", false, None).unwrap();
+        write_json_lines(&path, &reports, "run-b", true, None).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let run_ids: Vec<String> = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["run_id"].to_string())
+            .collect();
+        assert_eq!(run_ids, vec!["\"run-a\"", "\"run-b\""]);
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
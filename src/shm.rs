@@ -0,0 +1,224 @@
+//! Live RTT samples in a POSIX shared-memory ring (--shm)
+//!
+//! The segment is an array of 64-bit words, all accessed atomically:
+//!
+//! ```text
+//! [magic, slot count, samples written, (seq, rtt_ms as f64 bits) × slot count]
+//! ```
+//!
+//! Sample `n` (1-based) lives in slot `(n - 1) % slots` and carries `seq = n`. A slot's
+//! seq is zeroed while it is being rewritten, so a reader that sees the same non-zero
+//! seq before and after loading the RTT has a consistent sample; otherwise it was
+//! overwritten and the reader has fallen more than a ring behind.
+
+use std::ffi::CString;
+use std::io;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// "WSBRTT01", identifies the segment layout
+pub const SHM_MAGIC: u64 = u64::from_be_bytes(*b"WSBRTT01");
+/// Ring capacity used by --shm
+pub const SHM_SLOTS: usize = 4096;
+
+const HEADER_WORDS: usize = 3;
+
+static RING: OnceLock<ShmRing> = OnceLock::new();
+
+/// A mapped RTT ring
+pub struct ShmRing {
+    words: *mut AtomicU64,
+    len: usize,
+    slots: usize,
+}
+
+// The mapping is only touched through atomics and lives until the ring is dropped
+unsafe impl Send for ShmRing {}
+unsafe impl Sync for ShmRing {}
+
+impl ShmRing {
+    /// Create (or truncate and reuse) the segment `name` with room for `slots` samples
+    pub fn create(name: &str, slots: usize) -> io::Result<Self> {
+        if slots == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "ring needs at least one slot"));
+        }
+        let len = (HEADER_WORDS + 2 * slots) * std::mem::size_of::<u64>();
+        let mut ring = Self::map(name, libc::O_CREAT | libc::O_RDWR, Some(len))?;
+        ring.slots = slots;
+        ring.reset();
+        Ok(ring)
+    }
+
+    /// Map an existing segment written by `create`, for readers
+    #[allow(dead_code)] // used by examples/shm_reader.rs and the tests
+    pub fn open(name: &str) -> io::Result<Self> {
+        let mut ring = Self::map(name, libc::O_RDWR, None)?;
+        if ring.len < HEADER_WORDS * 8 || ring.word(0).load(Ordering::Acquire) != SHM_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not an RTT ring", name)));
+        }
+        ring.slots = ring.word(1).load(Ordering::Acquire) as usize;
+        if ring.slots == 0 || (HEADER_WORDS + 2 * ring.slots) * 8 > ring.len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is truncated", name)));
+        }
+        Ok(ring)
+    }
+
+    fn map(name: &str, flags: libc::c_int, create_len: Option<usize>) -> io::Result<Self> {
+        let path = if name.starts_with('/') { name.to_string() } else { format!("/{}", name) };
+        let path = CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let fd = unsafe { libc::shm_open(path.as_ptr(), flags, 0o644 as libc::c_uint) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let result = (|| {
+            let len = match create_len {
+                Some(len) => {
+                    if unsafe { libc::ftruncate(fd, len as libc::off_t) } != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    len
+                }
+                None => {
+                    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+                    if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    stat.st_size as usize
+                }
+            };
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "empty shared-memory segment"));
+            }
+
+            let addr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    0,
+                )
+            };
+            if addr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { words: addr as *mut AtomicU64, len, slots: 0 })
+        })();
+
+        // The mapping keeps the segment alive on its own
+        unsafe { libc::close(fd) };
+        result
+    }
+
+    fn word(&self, index: usize) -> &AtomicU64 {
+        debug_assert!((index + 1) * 8 <= self.len);
+        unsafe { &*self.words.add(index) }
+    }
+
+    fn reset(&self) {
+        // Invalidate the old contents before the new layout is advertised
+        self.word(0).store(0, Ordering::Release);
+        for index in 1..HEADER_WORDS + 2 * self.slots {
+            self.word(index).store(0, Ordering::Relaxed);
+        }
+        self.word(1).store(self.slots as u64, Ordering::Relaxed);
+        self.word(0).store(SHM_MAGIC, Ordering::Release);
+    }
+
+    /// Write the next sample; only one writer may publish to a ring
+    pub fn publish(&self, rtt_ms: f64) {
+        let seq = self.word(2).load(Ordering::Relaxed) + 1;
+        let slot = HEADER_WORDS + 2 * ((seq - 1) as usize % self.slots);
+
+        self.word(slot).store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.word(slot + 1).store(rtt_ms.to_bits(), Ordering::Relaxed);
+        self.word(slot).store(seq, Ordering::Release);
+        self.word(2).store(seq, Ordering::Release);
+    }
+
+    /// Number of samples published so far
+    #[allow(dead_code)] // used by examples/shm_reader.rs and the tests
+    pub fn written(&self) -> u64 {
+        self.word(2).load(Ordering::Acquire)
+    }
+
+    /// Ring capacity in samples
+    #[allow(dead_code)] // used by examples/shm_reader.rs and the tests
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Sample `seq` (1-based), or None if it hasn't been written yet or was overwritten
+    #[allow(dead_code)] // used by examples/shm_reader.rs and the tests
+    pub fn read(&self, seq: u64) -> Option<f64> {
+        if seq == 0 {
+            return None;
+        }
+        let slot = HEADER_WORDS + 2 * ((seq - 1) as usize % self.slots);
+
+        let before = self.word(slot).load(Ordering::Acquire);
+        let bits = self.word(slot + 1).load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        let after = self.word(slot).load(Ordering::Relaxed);
+
+        (before == seq && after == seq).then(|| f64::from_bits(bits))
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.words as *mut libc::c_void, self.len) };
+    }
+}
+
+/// Create the --shm ring; later calls are ignored
+pub fn init(name: &str) -> io::Result<()> {
+    if RING.get().is_none() {
+        let _ = RING.set(ShmRing::create(name, SHM_SLOTS)?);
+    }
+    Ok(())
+}
+
+/// Publish a sample to the --shm ring, if one was created
+pub fn publish(rtt_ms: f64) {
+    if let Some(ring) = RING.get() {
+        ring.publish(rtt_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_round_trip_and_overwrite() {
+        let name = format!("/wsb-test-{}", std::process::id());
+        let writer = ShmRing::create(&name, 4).unwrap();
+        let reader = ShmRing::open(&name).unwrap();
+        assert_eq!(reader.slots(), 4);
+        assert_eq!(reader.written(), 0);
+        assert_eq!(reader.read(1), None);
+
+        for rtt in [1.5, 2.5, 3.5] {
+            writer.publish(rtt);
+        }
+        assert_eq!(reader.written(), 3);
+        assert_eq!(reader.read(2), Some(2.5));
+
+        for rtt in [4.5, 5.5, 6.5] {
+            writer.publish(rtt);
+        }
+        // Samples 1 and 2 were overwritten by 5 and 6
+        assert_eq!(reader.read(1), None);
+        assert_eq!(reader.read(3), Some(3.5));
+        assert_eq!(reader.read(6), Some(6.5));
+        assert_eq!(reader.read(7), None);
+
+        let path = CString::new(name).unwrap();
+        unsafe { libc::shm_unlink(path.as_ptr()) };
+    }
+}
@@ -1,4 +1,7 @@
-use std::time::Instant;
+use rayon::slice::ParallelSliceMut;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
 /// CPU time measurement structure
 #[derive(Debug, Clone)]
@@ -7,6 +10,14 @@ pub struct CpuTime {
     pub system_time: f64, // System (kernel) CPU time in seconds
     pub wall_time: f64,   // Wall clock time in seconds
     pub start_time: Instant,
+    start_user: f64,
+    start_system: f64,
+}
+
+impl Default for CpuTime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CpuTime {
@@ -16,6 +27,8 @@ impl CpuTime {
             system_time: 0.0,
             wall_time: 0.0,
             start_time: Instant::now(),
+            start_user: 0.0,
+            start_system: 0.0,
         }
     }
 
@@ -24,10 +37,14 @@ impl CpuTime {
         self.user_time = 0.0;
         self.system_time = 0.0;
         self.wall_time = 0.0;
+        (self.start_user, self.start_system) = process_cpu_times();
     }
 
     pub fn stop(&mut self) {
         self.wall_time = self.start_time.elapsed().as_secs_f64();
+        let (user, system) = process_cpu_times();
+        self.user_time = user - self.start_user;
+        self.system_time = system - self.start_system;
     }
 
     pub fn cpu_total(&self) -> f64 {
@@ -42,6 +59,24 @@ impl CpuTime {
         }
     }
 
+    /// CPU microseconds spent per message (each ping-pong is 2 messages)
+    pub fn cpu_us_per_message(&self, ping_pong_count: usize) -> f64 {
+        if ping_pong_count > 0 {
+            self.cpu_total() / (ping_pong_count * 2) as f64 * 1e6
+        } else {
+            0.0
+        }
+    }
+
+    /// Wall-clock microseconds per message, for comparison with the CPU cost
+    pub fn wall_us_per_message(&self, ping_pong_count: usize) -> f64 {
+        if ping_pong_count > 0 {
+            self.wall_time / (ping_pong_count * 2) as f64 * 1e6
+        } else {
+            0.0
+        }
+    }
+
     /// Get memory usage in MB (Linux specific)
     #[cfg(target_os = "linux")]
     pub fn get_memory_mb() -> f64 {
@@ -50,15 +85,13 @@ impl CpuTime {
 
         if let Ok(file) = File::open("/proc/self/status") {
             let reader = BufReader::new(file);
-            for line in reader.lines() {
-                if let Ok(l) = line {
-                    if l.starts_with("VmRSS:") {
-                        // Parse "VmRSS:     12345 kB"
-                        let parts: Vec<&str> = l.split_whitespace().collect();
-                        if parts.len() >= 2 {
-                            if let Ok(kb) = parts[1].parse::<f64>() {
-                                return kb / 1024.0;
-                            }
+            for l in reader.lines().map_while(Result::ok) {
+                if l.starts_with("VmRSS:") {
+                    // Parse "VmRSS:     12345 kB"
+                    let parts: Vec<&str> = l.split_whitespace().collect();
+                    if parts.len() >= 2 {
+                        if let Ok(kb) = parts[1].parse::<f64>() {
+                            return kb / 1024.0;
                         }
                     }
                 }
@@ -67,19 +100,177 @@ impl CpuTime {
         0.0
     }
 
-    #[cfg(not(target_os = "linux"))]
+    /// Get memory usage in MB (macOS, via proc_pidinfo)
+    #[cfg(target_os = "macos")]
+    pub fn get_memory_mb() -> f64 {
+        let mut info: libc::proc_taskinfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+
+        let ret = unsafe {
+            libc::proc_pidinfo(
+                libc::getpid(),
+                libc::PROC_PIDTASKINFO,
+                0,
+                &mut info as *mut libc::proc_taskinfo as *mut libc::c_void,
+                size,
+            )
+        };
+
+        if ret == size {
+            info.pti_resident_size as f64 / (1024.0 * 1024.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Get memory usage in MB (Windows, working set via GetProcessMemoryInfo)
+    #[cfg(windows)]
+    pub fn get_memory_mb() -> f64 {
+        use windows_sys::Win32::System::ProcessStatus::{
+            GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+        };
+        use windows_sys::Win32::System::Threading::GetCurrentProcess;
+
+        let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+
+        let ok = unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, size) };
+        if ok != 0 {
+            counters.WorkingSetSize as f64 / (1024.0 * 1024.0)
+        } else {
+            0.0
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
     pub fn get_memory_mb() -> f64 {
         0.0
     }
 }
 
+/// Process (user, system) CPU time in seconds via getrusage
+#[cfg(unix)]
+fn process_cpu_times() -> (f64, f64) {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return (0.0, 0.0);
+    }
+
+    let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    (seconds(usage.ru_utime), seconds(usage.ru_stime))
+}
+
+/// Process (user, system) CPU time in seconds via GetProcessTimes
+#[cfg(windows)]
+fn process_cpu_times() -> (f64, f64) {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+    let zero = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut creation, mut exit, mut kernel, mut user) = (zero, zero, zero, zero);
+
+    let ok = unsafe {
+        GetProcessTimes(GetCurrentProcess(), &mut creation, &mut exit, &mut kernel, &mut user)
+    };
+    if ok == 0 {
+        return (0.0, 0.0);
+    }
+
+    // FILETIME counts 100-nanosecond intervals
+    let seconds = |ft: FILETIME| {
+        (((ft.dwHighDateTime as u64) << 32) | ft.dwLowDateTime as u64) as f64 / 10_000_000.0
+    };
+    (seconds(user), seconds(kernel))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_cpu_times() -> (f64, f64) {
+    (0.0, 0.0)
+}
+
+/// Linux's minimum delayed-ACK timer; Nagle waiting on a delayed ACK adds about this much
+const DELAYED_ACK_MS: f64 = 40.0;
+/// How close to the timer an RTT must be to count towards the cluster
+const DELAYED_ACK_TOLERANCE_MS: f64 = 5.0;
+/// Share of samples near the timer that's worth a hint
+const DELAYED_ACK_MIN_FRACTION: f64 = 0.02;
+
+/// Sample count from which sorting is spread across threads
+const PARALLEL_SORT_MIN: usize = 1 << 16;
+
+/// Index of the nearest-rank percentile `p` (0-100) among `count` sorted samples
+fn nearest_rank(p: f64, count: usize) -> usize {
+    let rank = ((p / 100.0) * count as f64).ceil() as usize;
+    rank.clamp(1, count) - 1
+}
+
+/// One nearest-rank percentile, partially reordering `samples` rather than sorting them
+pub fn select_percentile(samples: &mut [f64], p: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let index = nearest_rank(p, samples.len());
+    *samples.select_nth_unstable_by(index, f64::total_cmp).1
+}
+
+/// Running RTT moments (Welford's method), for runs that don't keep every sample
+#[derive(Debug, Clone, Default)]
+pub struct OnlineStats {
+    pub count: usize,
+    mean: f64,
+    m2: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl OnlineStats {
+    pub fn record(&mut self, rtt_ms: f64) {
+        if self.count == 0 {
+            self.min = rtt_ms;
+            self.max = rtt_ms;
+        } else {
+            self.min = self.min.min(rtt_ms);
+            self.max = self.max.max(rtt_ms);
+        }
+        self.count += 1;
+        let delta = rtt_ms - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (rtt_ms - self.mean);
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population standard deviation
+    pub fn stddev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+
+    pub fn print(&self) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ RTT Stddev: {:9.3} ms (running, percentiles not kept)     │", self.stddev());
+    }
+}
+
 /// RTT statistics
 #[derive(Debug, Clone)]
 pub struct RttStats {
     pub rtts: Vec<f64>,
+    /// The samples in ascending order, shared by the median and every percentile
+    pub sorted: Vec<f64>,
     pub count: usize,
     pub avg: f64,
     pub median: f64,
+    /// Population standard deviation
+    pub stddev: f64,
     pub min: f64,
     pub max: f64,
     pub total_time: f64,
@@ -88,37 +279,99 @@ pub struct RttStats {
 impl RttStats {
     pub fn new(rtts: Vec<f64>) -> Self {
         let count = rtts.len();
-        let (avg, median, min, max) = if count > 0 {
+        let mut sorted = rtts.clone();
+        if count >= PARALLEL_SORT_MIN {
+            sorted.par_sort_unstable_by(f64::total_cmp);
+        } else {
+            sorted.sort_unstable_by(f64::total_cmp);
+        }
+
+        let (avg, median, stddev, min, max) = if count > 0 {
             let sum: f64 = rtts.iter().sum();
             let avg = sum / count as f64;
-            let min = *rtts.iter().min_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
-            let max = *rtts.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
-
-            // Calculate median
-            let mut sorted = rtts.clone();
-            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-            let median = if count % 2 == 0 {
+            let median = if count.is_multiple_of(2) {
                 (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
             } else {
                 sorted[count / 2]
             };
+            let variance = rtts.iter().map(|rtt| (rtt - avg).powi(2)).sum::<f64>() / count as f64;
 
-            (avg, median, min, max)
+            (avg, median, variance.sqrt(), sorted[0], sorted[count - 1])
         } else {
-            (0.0, 0.0, 0.0, 0.0)
+            (0.0, 0.0, 0.0, 0.0, 0.0)
         };
 
         RttStats {
             rtts,
+            sorted,
             count,
             avg,
             median,
+            stddev,
             min,
             max,
             total_time: 0.0,
         }
     }
 
+    /// Avg, min and max from running moments; the median and percentiles read as 0
+    pub fn from_online(online: &OnlineStats) -> Self {
+        RttStats {
+            rtts: Vec::new(),
+            sorted: Vec::new(),
+            count: online.count,
+            avg: online.mean(),
+            median: 0.0,
+            stddev: online.stddev(),
+            min: online.min,
+            max: online.max,
+            total_time: 0.0,
+        }
+    }
+
+    /// Whether the samples were kept, so the median and percentiles are known
+    pub fn has_samples(&self) -> bool {
+        self.sorted.len() == self.count
+    }
+
+    /// Percentile (0-100) using the nearest-rank method
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+
+        self.sorted[nearest_rank(p, self.count)]
+    }
+
+    /// Indices of RTTs above the far-out Tukey fence (Q3 + 3 × IQR)
+    pub fn outliers(&self) -> Vec<usize> {
+        let q1 = self.percentile(25.0);
+        let q3 = self.percentile(75.0);
+        let fence = q3 + 3.0 * (q3 - q1);
+        self.rtts
+            .iter()
+            .enumerate()
+            .filter(|(_, &rtt)| rtt > fence)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Fraction of RTTs clustered near the delayed-ACK timer while typical RTTs are well below it,
+    /// the signature of Nagle on one side and delayed ACK on the other
+    pub fn delayed_ack_fraction(&self) -> Option<f64> {
+        if self.count == 0 || self.median >= DELAYED_ACK_MS - DELAYED_ACK_TOLERANCE_MS {
+            return None;
+        }
+
+        let near = self
+            .rtts
+            .iter()
+            .filter(|&&rtt| (rtt - DELAYED_ACK_MS).abs() <= DELAYED_ACK_TOLERANCE_MS)
+            .count();
+        let fraction = near as f64 / self.count as f64;
+        (fraction >= DELAYED_ACK_MIN_FRACTION).then_some(fraction)
+    }
+
     pub fn calculate_throughput(&self) -> f64 {
         if self.total_time > 0.0 {
             (self.count * 2) as f64 / self.total_time // Each ping-pong is 2 messages
@@ -138,29 +391,477 @@ impl RttStats {
         };
         let mem_mb = CpuTime::get_memory_mb();
 
-        println!("├──────────────────────────────────────────────────────────────┤");
-        println!("│ Throughput Statistics:                                       │");
-        println!("│   Total time:   {:8.3} sec                                 │", cpu.wall_time);
-        println!("│   Messages:     {:8} (ping+pong)                         │", ping_pong_count * 2);
-        println!("│   Throughput:   {:8.1} msg/sec                             │", msg_per_sec);
-        println!("├──────────────────────────────────────────────────────────────┤");
-        println!("│ CPU Time Statistics:                                         │");
-        println!("│   User time:    {:8.3} sec                                 │", cpu.user_time);
-        println!("│   System time:  {:8.3} sec                                 │", cpu.system_time);
-        println!("│   CPU total:    {:8.3} sec                                 │", cpu_total);
-        println!("│   Wall time:    {:8.3} sec                                 │", cpu.wall_time);
-        println!("│   CPU usage:    {:7.1}%                                    │", cpu_percent);
-        println!("├──────────────────────────────────────────────────────────────┤");
-        println!("│ Memory Statistics:                                           │");
-        println!("│   Memory (RSS): {:8.2} MB                                  │", mem_mb);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Throughput Statistics:                                       │");
+        outln!("│   Total time:   {:8.3} sec                                 │", cpu.wall_time);
+        outln!("│   Messages:     {:8} (ping+pong)                         │", ping_pong_count * 2);
+        outln!("│   Throughput:   {:8.1} msg/sec                             │", msg_per_sec);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ CPU Time Statistics:                                         │");
+        outln!("│   User time:    {:8.3} sec                                 │", cpu.user_time);
+        outln!("│   System time:  {:8.3} sec                                 │", cpu.system_time);
+        outln!("│   CPU total:    {:8.3} sec                                 │", cpu_total);
+        outln!("│   Wall time:    {:8.3} sec                                 │", cpu.wall_time);
+        outln!("│   CPU usage:    {:7.1}%                                    │", cpu_percent);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Per-message cost:                                            │");
+        outln!("│   CPU:          {:8.2} µs/msg                              │", cpu.cpu_us_per_message(ping_pong_count));
+        outln!("│   Wall:         {:8.2} µs/msg                              │", cpu.wall_us_per_message(ping_pong_count));
+        outln!("│   CPU/wall:     {:7.1}%                                    │", cpu_percent);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Memory Statistics:                                           │");
+        outln!("│   Memory (RSS): {:8.2} MB                                  │", mem_mb);
     }
 
     /// Print RTT statistics
     pub fn print_rtt_stats(&self) {
-        println!("├──────────────────────────────────────────────────────────────┤");
-        println!("│ RTT Statistics:                                              │");
-        println!("│   Avg: {:7.3} ms | Median: {:7.3} ms                       │", self.avg, self.median);
-        println!("│   Min: {:7.3} ms | Max:    {:7.3} ms                       │", self.min, self.max);
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ RTT Statistics:                                              │");
+        if self.has_samples() {
+            outln!("│   Avg: {:7.3} ms | Median: {:7.3} ms                       │", self.avg, self.median);
+        } else {
+            outln!("│   Avg: {:7.3} ms | Median:     n/a (--no-percentiles)       │", self.avg);
+        }
+        outln!("│   Min: {:7.3} ms | Max:    {:7.3} ms                       │", self.min, self.max);
+        if let Some(fraction) = self.delayed_ack_fraction() {
+            outln!(
+                "│ {:61}│",
+                format!("Hint: {:.1}% of RTTs cluster near {} ms, typical of Nagle", fraction * 100.0, DELAYED_ACK_MS)
+            );
+            outln!("│   waiting on a delayed ACK; check TCP_NODELAY on both ends   │");
+            outln!("│   and compare a run with --quickack                          │");
+        }
+    }
+
+    /// Print naive and coordinated-omission-corrected percentiles side by side
+    pub fn print_corrected(naive: &RttStats, corrected: &RttStats) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Latency: naive (from actual send), corrected (from schedule) │");
+        outln!("│   Percentile |   Naive (ms) | Corrected (ms)                 │");
+        for p in [50.0, 90.0, 99.0, 99.9] {
+            outln!("│   P{:<9} | {:>12.3} | {:>14.3}                 │",
+                p, naive.percentile(p), corrected.percentile(p));
+        }
+        outln!("│   Max        | {:>12.3} | {:>14.3}                 │", naive.max, corrected.max);
+    }
+}
+
+/// Coordinated-omission correction for a paced closed-loop run.
+///
+/// Each RTT is measured from the actual send, which is late whenever the previous
+/// response stalled; adding how far behind schedule the send went out measures it
+/// from its intended send time instead.
+pub fn co_corrected(rtts: &[f64], lateness_ms: &[f64]) -> Vec<f64> {
+    rtts.iter().zip(lateness_ms).map(|(rtt, late)| rtt + late).collect()
+}
+
+/// One end of a --measure-window: a fraction of the run or an offset in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowBound {
+    Fraction(f64),
+    Secs(f64),
+}
+
+impl WindowBound {
+    /// Offset in seconds into a run lasting `total_secs`
+    pub fn resolve(self, total_secs: f64) -> f64 {
+        match self {
+            WindowBound::Fraction(fraction) => fraction * total_secs,
+            WindowBound::Secs(secs) => secs,
+        }
+    }
+}
+
+/// Part of a run whose samples go into the final statistics, e.g. to drop ramp-up and drain
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MeasureWindow {
+    pub start: WindowBound,
+    pub end: WindowBound,
+}
+
+impl MeasureWindow {
+    /// Start and end offsets in seconds into a run lasting `total_secs`
+    pub fn resolve(&self, total_secs: f64) -> (f64, f64) {
+        (self.start.resolve(total_secs), self.end.resolve(total_secs))
+    }
+}
+
+/// Periodic RSS sampler for detecting client-side memory growth
+#[derive(Debug, Clone)]
+pub struct MemoryTracker {
+    pub start_mb: f64,
+    pub last_mb: f64,
+    pub peak_mb: f64,
+    pub samples: usize,
+    started: Instant,
+}
+
+impl MemoryTracker {
+    /// Whether RSS can be read on this platform
+    pub const SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "macos", windows));
+
+    /// Start tracking, taking the initial sample
+    pub fn start() -> Self {
+        let mut tracker = MemoryTracker {
+            start_mb: 0.0,
+            last_mb: 0.0,
+            peak_mb: 0.0,
+            samples: 0,
+            started: Instant::now(),
+        };
+        tracker.sample();
+        tracker
+    }
+
+    /// Take a new RSS sample
+    pub fn sample(&mut self) {
+        self.record(CpuTime::get_memory_mb());
+    }
+
+    /// Record an RSS reading; a zero reading means RSS could not be read and is not a sample
+    fn record(&mut self, mem_mb: f64) {
+        if mem_mb <= 0.0 {
+            return;
+        }
+        if self.samples == 0 {
+            self.start_mb = mem_mb;
+            self.peak_mb = mem_mb;
+            self.started = Instant::now();
+        }
+        self.last_mb = mem_mb;
+        self.peak_mb = self.peak_mb.max(mem_mb);
+        self.samples += 1;
+    }
+
+    /// Memory growth rate in KB per minute between the first and last sample
+    pub fn growth_kb_per_min(&self) -> f64 {
+        let minutes = self.started.elapsed().as_secs_f64() / 60.0;
+        if minutes > 0.0 {
+            (self.last_mb - self.start_mb) * 1024.0 / minutes
+        } else {
+            0.0
+        }
+    }
+
+    /// Print memory growth statistics
+    pub fn print(&self) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Memory Growth:                                               │");
+        if !Self::SUPPORTED {
+            outln!("│   Memory tracking is unavailable on this platform            │");
+            return;
+        }
+        if self.samples == 0 {
+            outln!("│   No samples: RSS could not be read                          │");
+            return;
+        }
+        outln!("│   Start RSS:    {:8.2} MB                                  │", self.start_mb);
+        outln!("│   End RSS:      {:8.2} MB                                  │", self.last_mb);
+        outln!("│   Peak RSS:     {:8.2} MB                                  │", self.peak_mb);
+        outln!("│   Growth:       {:8.1} KB/min                              │", self.growth_kb_per_min());
+        outln!("│   Samples:      {:8}                                     │", self.samples);
+    }
+}
+
+/// The kernel's view of a TCP connection (getsockopt TCP_INFO)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpInfo {
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    pub retransmits: u8,
+    pub total_retrans: u32,
+    pub lost: u32,
+    pub snd_cwnd: u32,
+    pub snd_mss: u32,
+}
+
+impl TcpInfo {
+    /// Read TCP_INFO for a connected socket
+    #[cfg(target_os = "linux")]
+    pub fn read<S: std::os::fd::AsRawFd>(socket: &S) -> std::io::Result<Self> {
+        let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_INFO,
+                &mut info as *mut libc::tcp_info as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(TcpInfo {
+            rtt_us: info.tcpi_rtt,
+            rttvar_us: info.tcpi_rttvar,
+            retransmits: info.tcpi_retransmits,
+            total_retrans: info.tcpi_total_retrans,
+            lost: info.tcpi_lost,
+            snd_cwnd: info.tcpi_snd_cwnd,
+            snd_mss: info.tcpi_snd_mss,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn read<S>(_socket: &S) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "TCP_INFO is only supported on Linux",
+        ))
+    }
+
+    /// Print the kernel's TCP statistics
+    pub fn print(&self) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Kernel TCP_INFO:                                             │");
+        outln!("│   RTT:           {:10.3} ms                               │", self.rtt_us as f64 / 1000.0);
+        outln!("│   RTT variance:  {:10.3} ms                               │", self.rttvar_us as f64 / 1000.0);
+        outln!("│   Retransmits:   {:10} (unrecovered now)                │", self.retransmits);
+        outln!("│   Total retrans: {:10}                                  │", self.total_retrans);
+        outln!("│   Lost:          {:10}                                  │", self.lost);
+        outln!("│ {:61}│", format!("  Send cwnd:     {:10} segments of {} B", self.snd_cwnd, self.snd_mss));
+    }
+
+    /// Print the result of `TcpInfo::read`, or note why it's unavailable
+    pub fn report(result: std::io::Result<TcpInfo>) {
+        match result {
+            Ok(info) => info.print(),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to read TCP_INFO");
+                outln!("├──────────────────────────────────────────────────────────────┤");
+                outln!("│ {:61}│", format!("Kernel TCP_INFO unavailable: {}", e));
+            }
+        }
+    }
+}
+
+/// Sequence tracking for detecting reordered, duplicated and lost responses
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryTracker {
+    pub expected: u64,
+    pub received: u64,
+    pub out_of_order: u64,
+    pub duplicates: u64,
+    /// Responses whose sequence isn't the one awaited (e.g. after a timeout); not counted as received
+    pub late: u64,
+    seen: HashSet<u64>,
+    highest: Option<u64>,
+}
+
+impl DeliveryTracker {
+    pub fn new(expected: u64) -> Self {
+        DeliveryTracker {
+            expected,
+            ..Default::default()
+        }
+    }
+
+    /// Record a response's sequence number; returns false for duplicates
+    pub fn record(&mut self, seq: u64) -> bool {
+        self.received += 1;
+
+        if !self.seen.insert(seq) {
+            self.duplicates += 1;
+            return false;
+        }
+
+        match self.highest {
+            Some(highest) if seq < highest => self.out_of_order += 1,
+            _ => self.highest = Some(seq),
+        }
+        true
+    }
+
+    /// Record a response outside the window of the request being awaited
+    pub fn record_late(&mut self) {
+        self.late += 1;
+    }
+
+    /// Sequences that never arrived
+    pub fn missing(&self) -> u64 {
+        self.expected.saturating_sub(self.seen.len() as u64)
+    }
+
+    /// Whether any reordering, duplication or loss was seen
+    pub fn has_anomalies(&self) -> bool {
+        self.out_of_order > 0 || self.duplicates > 0 || self.late > 0 || self.missing() > 0
+    }
+
+    /// Print delivery integrity statistics
+    pub fn print(&self) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        if self.has_anomalies() {
+            outln!("│ Delivery Integrity: ANOMALIES DETECTED                       │");
+        } else {
+            outln!("│ Delivery Integrity: OK                                       │");
+        }
+        outln!("│   Received:     {:8}                                     │", self.received);
+        outln!("│   Out of order: {:8}                                     │", self.out_of_order);
+        outln!("│   Duplicates:   {:8}                                     │", self.duplicates);
+        outln!("│   Late:         {:8}                                     │", self.late);
+        outln!("│   Missing:      {:8}                                     │", self.missing());
+    }
+}
+
+/// Gaps between consecutive response arrivals, i.e. how evenly the server answers
+#[derive(Debug, Clone, Default)]
+pub struct ResponseCadence {
+    last_arrival: Option<Instant>,
+    pub gaps: u64,
+    sum_ms: f64,
+    sum_sq_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+impl ResponseCadence {
+    /// Record a response arriving at `at`
+    pub fn record(&mut self, at: Instant) {
+        if let Some(last) = self.last_arrival.replace(at) {
+            self.record_gap(at.saturating_duration_since(last).as_secs_f64() * 1000.0);
+        }
+    }
+
+    fn record_gap(&mut self, gap_ms: f64) {
+        if self.gaps == 0 {
+            self.min_ms = gap_ms;
+            self.max_ms = gap_ms;
+        } else {
+            self.min_ms = self.min_ms.min(gap_ms);
+            self.max_ms = self.max_ms.max(gap_ms);
+        }
+        self.gaps += 1;
+        self.sum_ms += gap_ms;
+        self.sum_sq_ms += gap_ms * gap_ms;
+    }
+
+    pub fn avg_ms(&self) -> f64 {
+        if self.gaps == 0 {
+            return 0.0;
+        }
+        self.sum_ms / self.gaps as f64
+    }
+
+    /// Population standard deviation of the gaps
+    pub fn stddev_ms(&self) -> f64 {
+        if self.gaps == 0 {
+            return 0.0;
+        }
+        let avg = self.avg_ms();
+        (self.sum_sq_ms / self.gaps as f64 - avg * avg).max(0.0).sqrt()
+    }
+
+    /// Print the inter-arrival statistics, next to the send interval when pacing
+    pub fn print(&self, interval_ms: Option<f64>) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Response cadence ({:8} gaps):                            │", self.gaps);
+        outln!("│   Avg: {:9.3} ms | Stddev: {:9.3} ms                   │", self.avg_ms(), self.stddev_ms());
+        outln!("│   Min: {:9.3} ms | Max:    {:9.3} ms                   │", self.min_ms, self.max_ms);
+        if let Some(interval_ms) = interval_ms {
+            outln!("│   Send interval: {:9.3} ms                                │", interval_ms);
+        }
+    }
+}
+
+/// Reconnection and retry statistics for runs with --reconnect / --ping-retries
+#[derive(Debug, Clone, Default)]
+pub struct ReconnectStats {
+    pub reconnections: u32,
+    pub failed_iterations: u32,
+    pub downtime: Duration,
+    /// Failed ping attempts that were retried on the same connection
+    pub ping_retries: u32,
+}
+
+impl ReconnectStats {
+    /// Print reconnection statistics
+    pub fn print(&self) {
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Reconnect Statistics:                                        │");
+        outln!("│   Reconnects:   {:8}                                     │", self.reconnections);
+        outln!("│   Failed iters: {:8}                                     │", self.failed_iterations);
+        outln!("│   Downtime:     {:8.3} sec                                 │", self.downtime.as_secs_f64());
+        outln!("│   Ping retries: {:8}                                     │", self.ping_retries);
+    }
+}
+
+/// Phases of one manually framed ping-pong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Building and masking the frame
+    Frame,
+    /// write + flush
+    Write,
+    /// Blocking until the first response bytes arrive
+    Wait,
+    /// Reading the rest of the response frame
+    Read,
+    /// Parsing, validating and inflating the response
+    Parse,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [Phase::Frame, Phase::Write, Phase::Wait, Phase::Read, Phase::Parse];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Frame => "frame",
+            Phase::Write => "write",
+            Phase::Wait => "wait",
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+        }
+    }
+}
+
+/// Accumulated time per ping-pong phase
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimes {
+    totals: [Duration; 5],
+    iterations: u64,
+}
+
+impl PhaseTimes {
+    pub fn add(&mut self, phase: Phase, elapsed: Duration) {
+        self.totals[phase as usize] += elapsed;
+    }
+
+    pub fn finish_iteration(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Average time per iteration spent in a phase, in microseconds
+    pub fn avg_us(&self, phase: Phase) -> f64 {
+        if self.iterations == 0 {
+            return 0.0;
+        }
+        self.totals[phase as usize].as_secs_f64() * 1e6 / self.iterations as f64
+    }
+
+    /// Print the average time per phase
+    pub fn print(&self) {
+        let total: f64 = Phase::ALL.iter().map(|&phase| self.avg_us(phase)).sum();
+        outln!("├──────────────────────────────────────────────────────────────┤");
+        outln!("│ Phase Breakdown (avg per iteration):                         │");
+        for phase in Phase::ALL {
+            let avg = self.avg_us(phase);
+            let share = if total > 0.0 { avg / total * 100.0 } else { 0.0 };
+            outln!("│   {:<6} {:10.1} µs ({:5.1}%)                              │", phase.name(), avg, share);
+        }
+    }
+
+    /// Folded stacks (`root;phase microseconds`) for flamegraph tools such as inferno
+    pub fn folded(&self, root: &str) -> String {
+        Phase::ALL
+            .iter()
+            .map(|&phase| {
+                format!("{};{} {}\n", root, phase.name(), self.totals[phase as usize].as_micros())
+            })
+            .collect()
     }
 }
 
@@ -176,6 +877,35 @@ mod tests {
         assert_eq!(cpu.wall_time, 0.0);
     }
 
+    #[test]
+    fn test_cpu_time_measures_busy_loop() {
+        let mut cpu = CpuTime::new();
+        cpu.start();
+        let mut acc = 0u64;
+        for i in 0..5_000_000u64 {
+            acc = acc.wrapping_add(i * i);
+        }
+        std::hint::black_box(acc);
+        cpu.stop();
+
+        assert!(cpu.user_time >= 0.0);
+        assert!(cpu.system_time >= 0.0);
+        assert!(cpu.wall_time > 0.0);
+    }
+
+    #[test]
+    fn test_cpu_cost_per_message() {
+        let mut cpu = CpuTime::new();
+        cpu.user_time = 0.3;
+        cpu.system_time = 0.1;
+        cpu.wall_time = 2.0;
+
+        // 1000 ping-pongs = 2000 messages
+        assert!((cpu.cpu_us_per_message(1000) - 200.0).abs() < 1e-9);
+        assert!((cpu.wall_us_per_message(1000) - 1000.0).abs() < 1e-9);
+        assert_eq!(cpu.cpu_us_per_message(0), 0.0);
+    }
+
     #[test]
     fn test_rtt_stats_calculation() {
         let rtts = vec![10.0, 20.0, 30.0, 40.0, 50.0];
@@ -188,6 +918,64 @@ mod tests {
         assert_eq!(stats.median, 30.0);
     }
 
+    #[test]
+    fn test_delayed_ack_cluster() {
+        // 1 ms typical RTT with 5% of samples stalled on a 40 ms delayed ACK
+        let rtts: Vec<f64> = (0..100).map(|i| if i % 20 == 0 { 41.0 } else { 1.0 }).collect();
+        let fraction = RttStats::new(rtts).delayed_ack_fraction().unwrap();
+        assert!((fraction - 0.05).abs() < 1e-9);
+
+        // A handful of stragglers isn't a cluster
+        let rtts: Vec<f64> = (0..1000).map(|i| if i == 0 { 40.0 } else { 1.0 }).collect();
+        assert!(RttStats::new(rtts).delayed_ack_fraction().is_none());
+
+        // Nor is a path whose RTT is simply around 40 ms
+        let rtts: Vec<f64> = (0..100).map(|i| 38.0 + (i % 5) as f64).collect();
+        assert!(RttStats::new(rtts).delayed_ack_fraction().is_none());
+    }
+
+    #[test]
+    fn test_co_corrected_reflects_stall() {
+        // Closed loop paced every 10 ms with 1 ms responses, except one 1 s stall
+        let interval = 10.0;
+        let rtts: Vec<f64> = (0..100).map(|i| if i == 50 { 1000.0 } else { 1.0 }).collect();
+
+        let mut lateness = Vec::new();
+        let mut free_at = 0.0f64;
+        for (i, rtt) in rtts.iter().enumerate() {
+            let scheduled = i as f64 * interval;
+            let sent = scheduled.max(free_at);
+            lateness.push(sent - scheduled);
+            free_at = sent + rtt;
+        }
+
+        let raw = RttStats::new(rtts.clone());
+        let corrected = RttStats::new(co_corrected(&rtts, &lateness));
+
+        // The stall is a single raw sample, invisible at P99
+        assert_eq!(raw.percentile(99.0), 1.0);
+        // Every send queued behind it is late, so the corrected tail shows the stall
+        assert!(corrected.percentile(99.0) > 900.0);
+        assert_eq!(corrected.max, 1000.0);
+        assert_eq!(corrected.percentile(50.0), 1.0);
+    }
+
+    #[test]
+    fn test_response_cadence() {
+        let mut cadence = ResponseCadence::default();
+        let start = Instant::now();
+        cadence.record(start);
+        assert_eq!(cadence.gaps, 0);
+
+        cadence.record(start + Duration::from_millis(5));
+        cadence.record(start + Duration::from_millis(20));
+        assert_eq!(cadence.gaps, 2);
+        assert!((cadence.avg_ms() - 10.0).abs() < 1e-9);
+        assert!((cadence.stddev_ms() - 5.0).abs() < 1e-9);
+        assert!((cadence.min_ms - 5.0).abs() < 1e-9);
+        assert!((cadence.max_ms - 15.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_rtt_stats_even_count() {
         let rtts = vec![10.0, 20.0, 30.0, 40.0];
@@ -196,6 +984,75 @@ mod tests {
         assert_eq!(stats.median, 25.0); // (20 + 30) / 2
     }
 
+    #[test]
+    fn test_rtt_stats_outliers() {
+        let mut rtts: Vec<f64> = (0..100).map(|v| 10.0 + (v % 4) as f64).collect();
+        rtts[42] = 100.0;
+        let stats = RttStats::new(rtts);
+
+        assert_eq!(stats.outliers(), vec![42]);
+    }
+
+    #[test]
+    fn test_rtt_stats_percentile() {
+        let rtts: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        let stats = RttStats::new(rtts);
+
+        assert_eq!(stats.percentile(50.0), 50.0);
+        assert_eq!(stats.percentile(99.0), 99.0);
+        assert_eq!(stats.percentile(100.0), 100.0);
+        assert_eq!(stats.percentile(0.0), 1.0);
+    }
+
+    #[test]
+    fn test_percentiles_match_reference() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(5);
+        for count in [1, 2, 7, 100, 1001] {
+            let rtts: Vec<f64> = (0..count).map(|_| rng.gen_range(0.1..50.0)).collect();
+            let stats = RttStats::new(rtts.clone());
+
+            // Reference: the smallest sample with at least p% of samples at or below it
+            for p in [0.0, 1.0, 25.0, 50.0, 90.0, 95.0, 99.0, 99.9, 100.0] {
+                let expected = rtts
+                    .iter()
+                    .copied()
+                    .filter(|&x| rtts.iter().filter(|&&y| y <= x).count() as f64 >= p / 100.0 * count as f64)
+                    .fold(f64::INFINITY, f64::min);
+                assert_eq!(stats.percentile(p), expected, "count {} p{}", count, p);
+                assert_eq!(select_percentile(&mut rtts.clone(), p), expected, "count {} p{}", count, p);
+            }
+            assert_eq!(stats.min, rtts.iter().copied().fold(f64::INFINITY, f64::min));
+            assert_eq!(stats.max, rtts.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+        }
+        assert_eq!(select_percentile(&mut [], 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_online_stats_match_batch() {
+        let rtts = [3.5, 1.25, 9.0, 4.75, 2.0, 7.5, 1.25];
+        let mut online = OnlineStats::default();
+        for &rtt in &rtts {
+            online.record(rtt);
+        }
+
+        let batch = RttStats::new(rtts.to_vec());
+        let variance = rtts.iter().map(|r| (r - batch.avg).powi(2)).sum::<f64>() / rtts.len() as f64;
+        assert_eq!(online.count, rtts.len());
+        assert!((online.mean() - batch.avg).abs() < 1e-12);
+        assert!((online.stddev() - variance.sqrt()).abs() < 1e-12);
+        assert_eq!((online.min, online.max), (batch.min, batch.max));
+
+        assert!((batch.stddev - online.stddev()).abs() < 1e-12);
+
+        let summary = RttStats::from_online(&online);
+        assert_eq!(summary.stddev, online.stddev());
+        assert!(!summary.has_samples());
+        assert_eq!(summary.percentile(99.0), 0.0);
+        assert_eq!(OnlineStats::default().stddev(), 0.0);
+    }
+
     #[test]
     fn test_rtt_stats_empty() {
         let rtts: Vec<f64> = vec![];
@@ -205,4 +1062,83 @@ mod tests {
         assert_eq!(stats.avg, 0.0);
         assert_eq!(stats.median, 0.0);
     }
+
+    #[test]
+    fn test_delivery_tracker_in_order() {
+        let mut tracker = DeliveryTracker::new(3);
+        for seq in 0..3 {
+            assert!(tracker.record(seq));
+        }
+
+        assert!(!tracker.has_anomalies());
+        assert_eq!(tracker.missing(), 0);
+    }
+
+    #[test]
+    fn test_delivery_tracker_anomalies() {
+        let mut tracker = DeliveryTracker::new(5);
+        assert!(tracker.record(0));
+        assert!(tracker.record(2));
+        assert!(tracker.record(1)); // out of order
+        assert!(!tracker.record(2)); // duplicate
+
+        assert_eq!(tracker.received, 4);
+        assert_eq!(tracker.out_of_order, 1);
+        assert_eq!(tracker.duplicates, 1);
+        assert_eq!(tracker.missing(), 2); // 3 and 4 never arrived
+        assert!(tracker.has_anomalies());
+    }
+
+    #[test]
+    fn test_delivery_tracker_late_responses_are_not_deliveries() {
+        let mut tracker = DeliveryTracker::new(2);
+        assert!(tracker.record(0));
+        tracker.record_late();
+
+        assert_eq!(tracker.received, 1);
+        assert_eq!(tracker.late, 1);
+        assert_eq!(tracker.missing(), 1);
+        assert!(tracker.has_anomalies());
+    }
+
+    #[test]
+    fn test_memory_tracker_peak() {
+        let mut tracker = MemoryTracker::start();
+        tracker.sample();
+
+        assert_eq!(tracker.samples, 2);
+        assert!(tracker.peak_mb >= tracker.start_mb);
+        assert!(tracker.peak_mb >= tracker.last_mb);
+    }
+
+    #[test]
+    fn test_memory_tracker_skips_failed_reads() {
+        let mut tracker = MemoryTracker::start();
+        tracker.samples = 0;
+        tracker.record(0.0);
+        assert_eq!(tracker.samples, 0);
+
+        tracker.record(12.0);
+        tracker.record(0.0);
+        tracker.record(10.0);
+        assert_eq!(tracker.samples, 2);
+        assert_eq!(tracker.start_mb, 12.0);
+        assert_eq!(tracker.last_mb, 10.0);
+        assert_eq!(tracker.peak_mb, 12.0);
+    }
+
+    #[test]
+    fn test_phase_times() {
+        let mut phases = PhaseTimes::default();
+        for _ in 0..4 {
+            phases.add(Phase::Write, Duration::from_micros(10));
+            phases.add(Phase::Wait, Duration::from_micros(100));
+            phases.finish_iteration();
+        }
+
+        assert_eq!(phases.avg_us(Phase::Write), 10.0);
+        assert_eq!(phases.avg_us(Phase::Wait), 100.0);
+        assert_eq!(phases.avg_us(Phase::Parse), 0.0);
+        assert!(phases.folded("sync_tls").contains("sync_tls;wait 400\n"));
+    }
 }
@@ -0,0 +1,182 @@
+//! Replay of captured message traces (--trace, --trace-timing)
+//!
+//! A trace is a text file with one message per line: either `SIZE`, or
+//! `OFFSET_MS,SIZE` where the offset is milliseconds since the first message.
+//! Blank lines and lines starting with `#` are ignored.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Filler repeated to build payloads of the recorded sizes
+const FILLER: &[u8] = b"PING";
+
+/// One recorded message
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Time since the start of the trace, if the trace records timing
+    pub offset: Option<Duration>,
+    pub size: usize,
+}
+
+/// Parse trace text; every line must have an offset or none may
+pub fn parse_trace(text: &str) -> Result<Vec<TraceEntry>, String> {
+    let mut entries: Vec<TraceEntry> = Vec::new();
+
+    for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (offset, size) = match line.split_once(',') {
+            Some((offset, size)) => {
+                let offset_ms: f64 = offset
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("line {}: invalid offset \"{}\"", number, offset))?;
+                if !offset_ms.is_finite() || offset_ms < 0.0 {
+                    return Err(format!("line {}: offset must be non-negative", number));
+                }
+                (Some(Duration::from_secs_f64(offset_ms / 1000.0)), size)
+            }
+            None => (None, line),
+        };
+        let size = size
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid size \"{}\"", number, size))?;
+
+        if let Some(previous) = entries.last() {
+            if previous.offset.is_some() != offset.is_some() {
+                return Err(format!("line {}: either every line has an offset or none does", number));
+            }
+            if offset < previous.offset {
+                return Err(format!("line {}: offsets must not decrease", number));
+            }
+        }
+        entries.push(TraceEntry { offset, size });
+    }
+
+    if entries.is_empty() {
+        return Err("trace contains no messages".to_string());
+    }
+    Ok(entries)
+}
+
+/// Read and parse a trace file
+pub fn load_trace(path: &Path) -> Result<Vec<TraceEntry>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_trace(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Steps through a trace, optionally at its recorded timing
+pub struct TraceReplay {
+    entries: Vec<TraceEntry>,
+    timing: bool,
+    start: Option<Instant>,
+    /// How far behind schedule the latest message was sent
+    lag: Duration,
+    max_lag: Duration,
+}
+
+impl TraceReplay {
+    /// Build the replay, or None when no trace was configured
+    pub fn new(entries: &[TraceEntry], timing: bool) -> Option<Self> {
+        if entries.is_empty() {
+            return None;
+        }
+        Some(TraceReplay {
+            entries: entries.to_vec(),
+            timing,
+            start: None,
+            lag: Duration::ZERO,
+            max_lag: Duration::ZERO,
+        })
+    }
+
+    /// How long to wait before sending message `index` to honor the recorded gaps.
+    ///
+    /// Without --trace-timing messages go back to back. When the client is
+    /// already late the message is due immediately and the lag is recorded.
+    pub fn next_delay(&mut self, index: usize) -> Option<Duration> {
+        if !self.timing {
+            return None;
+        }
+        let offset = self.entries.get(index)?.offset?;
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let due = start + offset;
+        let now = Instant::now();
+
+        if now >= due {
+            self.lag = now - due;
+            self.max_lag = self.max_lag.max(self.lag);
+            None
+        } else {
+            self.lag = Duration::ZERO;
+            Some(due - now)
+        }
+    }
+
+    /// Payload of message `index`
+    pub fn payload(&self, index: usize) -> Vec<u8> {
+        let size = self.entries.get(index).map_or(0, |entry| entry.size);
+        FILLER.iter().copied().cycle().take(size).collect()
+    }
+
+    /// Print the replay summary, including scheduling lag in timing mode
+    pub fn print(&self) {
+        outln!("│ Trace: {:8} messages{}", self.entries.len(),
+            if self.timing { " at recorded timing" } else { "" });
+        if self.timing {
+            outln!("│   Scheduling lag: final {:9.3} ms | max {:9.3} ms",
+                self.lag.as_secs_f64() * 1000.0, self.max_lag.as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trace_sizes_only() {
+        let entries = parse_trace("# sizes\n64\n\n1024\n").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], TraceEntry { offset: None, size: 1024 });
+    }
+
+    #[test]
+    fn test_parse_trace_with_offsets() {
+        let entries = parse_trace("0,64\n12.5,128\n12.5,256\n").unwrap();
+        assert_eq!(entries[1].offset, Some(Duration::from_micros(12_500)));
+        assert_eq!(entries[2].size, 256);
+    }
+
+    #[test]
+    fn test_parse_trace_rejects_bad_input() {
+        assert!(parse_trace("").is_err());
+        assert!(parse_trace("abc").is_err());
+        assert!(parse_trace("0,64\n128").is_err());
+        assert!(parse_trace("10,64\n5,64").is_err());
+        assert!(parse_trace("-1,64").is_err());
+    }
+
+    #[test]
+    fn test_replay_without_timing_never_waits() {
+        let entries = parse_trace("0,4\n1000,8\n").unwrap();
+        let mut replay = TraceReplay::new(&entries, false).unwrap();
+
+        assert_eq!(replay.next_delay(1), None);
+        assert_eq!(replay.payload(1), b"PINGPING");
+    }
+
+    #[test]
+    fn test_replay_waits_for_recorded_offset() {
+        let entries = parse_trace("0,4\n1000,4\n").unwrap();
+        let mut replay = TraceReplay::new(&entries, true).unwrap();
+
+        assert_eq!(replay.next_delay(0), None);
+        let delay = replay.next_delay(1).unwrap();
+        assert!(delay > Duration::from_millis(900) && delay <= Duration::from_secs(1));
+    }
+}
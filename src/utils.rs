@@ -1,5 +1,23 @@
+use crate::stats::{MeasureWindow, WindowBound};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use rand::Rng;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+static CLOCK_EPOCH: OnceLock<Instant> = OnceLock::new();
+static RESOLVE_OVERRIDES: OnceLock<HashMap<String, IpAddr>> = OnceLock::new();
+
+/// Close reason bytes that fit a 125-byte control frame after the status code
+const MAX_CLOSE_REASON: usize = 123;
+/// Pause before retrying a read or write that returned WouldBlock
+const WOULD_BLOCK_BACKOFF: Duration = Duration::from_millis(1);
+/// Immediate WouldBlock retries before the error is surfaced anyway
+const MAX_WOULD_BLOCK_RETRIES: u32 = 100;
 
 /// Generate random WebSocket key (16 random bytes base64 encoded)
 pub fn generate_websocket_key() -> String {
@@ -8,6 +26,11 @@ pub fn generate_websocket_key() -> String {
     STANDARD.encode(random_bytes)
 }
 
+/// Monotonic nanoseconds since the first call in this process
+pub fn monotonic_nanos() -> u64 {
+    CLOCK_EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
 /// Base64 encode data
 pub fn base64_encode(data: &[u8]) -> String {
     STANDARD.encode(data)
@@ -18,10 +41,810 @@ pub fn base64_decode(data: &str) -> Result<Vec<u8>, base64::DecodeError> {
     STANDARD.decode(data)
 }
 
+/// A single extension from a Sec-WebSocket-Extensions header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extension {
+    pub name: String,
+    pub params: Vec<(String, Option<String>)>,
+}
+
+impl Extension {
+    /// Look up a parameter: None if absent, Some(None) if present without a value
+    pub fn param(&self, key: &str) -> Option<Option<&str>> {
+        self.params
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_deref())
+    }
+}
+
+/// Parse a Sec-WebSocket-Extensions header value into extensions with parameters
+pub fn parse_extensions(value: &str) -> Vec<Extension> {
+    value
+        .split(',')
+        .filter_map(|extension| {
+            let mut parts = extension.split(';').map(str::trim);
+            let name = parts.next().filter(|name| !name.is_empty())?;
+            let params = parts
+                .filter(|param| !param.is_empty())
+                .map(|param| match param.split_once('=') {
+                    Some((key, value)) => (
+                        key.trim().to_string(),
+                        Some(value.trim().trim_matches('"').to_string()),
+                    ),
+                    None => (param.to_string(), None),
+                })
+                .collect();
+
+            Some(Extension {
+                name: name.to_string(),
+                params,
+            })
+        })
+        .collect()
+}
+
+/// Find a header value in a raw HTTP response (header names are case-insensitive)
+pub fn find_header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then_some(value.trim())
+        })
+}
+
+/// Parse the headers of a raw HTTP response into a map keyed by lowercase name.
+/// Repeated headers are joined with ", ".
+pub fn parse_headers(response: &str) -> HashMap<String, String> {
+    let mut headers: HashMap<String, String> = HashMap::new();
+
+    for line in response
+        .lines()
+        .skip(1)
+        .map(|line| line.trim_end_matches('\r'))
+        .take_while(|line| !line.is_empty())
+    {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        headers
+            .entry(key.trim().to_ascii_lowercase())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value.trim());
+            })
+            .or_insert_with(|| value.trim().to_string());
+    }
+
+    headers
+}
+
+/// Check that a handshake response is a 101 with `Upgrade: websocket`
+/// and `Connection: Upgrade` (matched case-insensitively)
+pub fn validate_upgrade_response(response: &str) -> Result<(), String> {
+    let status_line = response.lines().next().unwrap_or_default().trim_end();
+    if response_status(response) != Some(101) {
+        return Err(format!("Expected 101 Switching Protocols, got \"{}\"", status_line));
+    }
+
+    let headers = parse_headers(response);
+
+    match headers.get("upgrade") {
+        Some(upgrade) if upgrade.to_ascii_lowercase().contains("websocket") => {}
+        Some(upgrade) => return Err(format!("Upgrade header is \"{}\", expected \"websocket\"", upgrade)),
+        None => return Err("Missing Upgrade header".to_string()),
+    }
+
+    // Connection is a comma-separated token list, e.g. "keep-alive, Upgrade"
+    match headers.get("connection") {
+        Some(connection)
+            if connection
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("upgrade")) => {}
+        Some(connection) => return Err(format!("Connection header is \"{}\", expected \"Upgrade\"", connection)),
+        None => return Err("Missing Connection header".to_string()),
+    }
+
+    Ok(())
+}
+
+/// Status code from an HTTP response's status line
+pub fn response_status(response: &str) -> Option<u16> {
+    response.lines().next()?.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Parse an --origin; it goes verbatim into a header line
+pub fn parse_origin(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.chars().any(|c| c.is_control()) {
+        return Err(format!("\"{}\" is not a valid Origin header value", value.escape_debug()));
+    }
+    Ok(value.to_string())
+}
+
+/// Check that Sec-WebSocket-Accept matches the key sent in the request (RFC 6455 §4.2.2)
+pub fn validate_accept_key(response: &str, key: &str) -> Result<(), String> {
+    let expected = derive_accept_key(key.as_bytes());
+    match parse_headers(response).get("sec-websocket-accept") {
+        Some(accept) if accept.trim() == expected => Ok(()),
+        Some(accept) => Err(format!("Sec-WebSocket-Accept is \"{}\", expected \"{}\"", accept, expected)),
+        None => Err("Missing Sec-WebSocket-Accept header".to_string()),
+    }
+}
+
+/// Create an unconnected socket for `addr` bound to a named network interface
+/// (SO_BINDTODEVICE). Requires CAP_NET_RAW or root.
+#[cfg(target_os = "linux")]
+pub fn interface_socket(
+    addr: SocketAddr,
+    interface: &str,
+    ty: socket2::Type,
+) -> std::io::Result<socket2::Socket> {
+    let socket = socket2::Socket::new(socket2::Domain::for_address(addr), ty, None)?;
+    socket.bind_device(Some(interface.as_bytes())).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            std::io::Error::new(
+                e.kind(),
+                format!("Binding to interface {} requires CAP_NET_RAW or root", interface),
+            )
+        } else {
+            std::io::Error::new(e.kind(), format!("Failed to bind to interface {}: {}", interface, e))
+        }
+    })?;
+    Ok(socket)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interface_socket(
+    _addr: SocketAddr,
+    _interface: &str,
+    _ty: socket2::Type,
+) -> std::io::Result<socket2::Socket> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "--interface is only supported on Linux",
+    ))
+}
+
+/// Mark traffic from `socket` with a DSCP value (IP_TOS for IPv4, IPV6_TCLASS for IPv6)
+pub fn set_dscp(socket: &socket2::Socket, addr: SocketAddr, dscp: u8) -> io::Result<()> {
+    // DSCP occupies the upper six bits of the TOS / traffic class byte
+    let tos = u32::from(dscp) << 2;
+    if addr.is_ipv4() {
+        socket.set_tos(tos)
+    } else {
+        set_tclass_v6(socket, tos)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_tclass_v6(socket: &socket2::Socket, tclass: u32) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let value = tclass as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_tclass_v6(_socket: &socket2::Socket, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPv6 traffic class is only supported on Linux",
+    ))
+}
+
+/// Ask the kernel to ACK incoming data immediately (TCP_QUICKACK).
+///
+/// Linux falls back to delayed ACKs on its own, so it has to be re-armed after every receive.
+#[cfg(target_os = "linux")]
+pub fn set_quickack<S: std::os::fd::AsFd>(socket: &S) -> io::Result<()> {
+    socket2::SockRef::from(socket).set_quickack(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_quickack<S>(_socket: &S) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP_QUICKACK is only supported on Linux",
+    ))
+}
+
+/// Take over an already-connected TCP socket handed in as file descriptor `fd` (--fd)
+#[cfg(unix)]
+pub fn stream_from_fd(fd: i32) -> io::Result<TcpStream> {
+    use std::os::fd::{BorrowedFd, FromRawFd};
+
+    // SAFETY: only borrowed for the checks, so a rejected fd is left open for its owner
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    let socket = socket2::SockRef::from(&borrowed);
+    let ty = socket
+        .r#type()
+        .map_err(|e| io::Error::new(e.kind(), format!("fd {} is not a socket: {}", fd, e)))?;
+    let is_inet = socket.local_addr()?.as_socket().is_some();
+    if ty != socket2::Type::STREAM || !is_inet {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("fd {} is not a TCP stream socket", fd),
+        ));
+    }
+    socket
+        .peer_addr()
+        .map_err(|e| io::Error::new(e.kind(), format!("fd {} is not connected: {}", fd, e)))?;
+
+    // SAFETY: checked above to be an open, connected TCP socket; we own it from here on
+    Ok(unsafe { TcpStream::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+pub fn stream_from_fd(_fd: i32) -> io::Result<TcpStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--fd is only supported on Unix",
+    ))
+}
+
+/// Name of the machine running the benchmark
+#[cfg(unix)]
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+pub fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Benchmark target parsed from a ws:// or wss:// URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsTarget {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    /// Request path including any query string
+    pub path: String,
+}
+
+/// Parse a --resolve entry of the form `host:ip` (IPv6 may be bracketed)
+pub fn parse_resolve(entry: &str) -> Result<(String, IpAddr), String> {
+    let (host, ip) = entry
+        .split_once(':')
+        .ok_or_else(|| format!("Expected HOST:IP, got \"{}\"", entry))?;
+    if host.is_empty() {
+        return Err(format!("Missing host in \"{}\"", entry));
+    }
+    let ip = ip.trim_start_matches('[').trim_end_matches(']');
+    let ip = ip.parse().map_err(|_| format!("Invalid IP address \"{}\"", ip))?;
+    Ok((host.to_ascii_lowercase(), ip))
+}
+
+/// Parse a --source-port-range of the form `START-END` (inclusive)
+pub fn parse_port_range(value: &str) -> Result<(u16, u16), String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("Expected START-END, got \"{}\"", value))?;
+    let port = |s: &str| match s.trim().parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!("Invalid port \"{}\"", s)),
+        Ok(port) => Ok(port),
+    };
+    let (start, end) = (port(start)?, port(end)?);
+    if start > end {
+        return Err(format!("Port range {}-{} is empty", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Parse a --close-code, accepting only codes RFC 6455 allows an endpoint to send
+pub fn parse_close_code(value: &str) -> Result<u16, String> {
+    let code: u16 = value
+        .parse()
+        .map_err(|_| format!("Invalid close code \"{}\"", value))?;
+    match code {
+        1000..=1003 | 1007..=1014 | 3000..=4999 => Ok(code),
+        // Reserved for reporting conditions locally, never sent on the wire
+        1004 | 1005 | 1006 | 1015 => Err(format!("Close code {} is reserved and must not be sent", code)),
+        _ => Err(format!(
+            "Close code {} is outside the permitted ranges (1000-1003, 1007-1014, 3000-4999)",
+            code
+        )),
+    }
+}
+
+/// Parse a --close-reason; it must fit a control frame next to the 2-byte code
+pub fn parse_close_reason(value: &str) -> Result<String, String> {
+    if value.len() > MAX_CLOSE_REASON {
+        return Err(format!(
+            "Close reason is {} bytes, at most {} fit in a close frame",
+            value.len(),
+            MAX_CLOSE_REASON
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse a non-negative, finite number of seconds
+pub fn parse_secs(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(secs),
+        _ => Err(format!("\"{}\" is not a non-negative number of seconds", value)),
+    }
+}
+
+/// Parse a positive, finite interval in the flag's unit; a zero interval would never let the clock advance
+pub fn parse_interval(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(interval) if interval.is_finite() && interval > 0.0 => Ok(interval),
+        _ => Err(format!("\"{}\" is not a positive, finite interval", value)),
+    }
+}
+
+/// Parse a --measure-window "START,END"; each bound is a fraction of the run
+/// (0.1) or an offset in seconds (10s)
+pub fn parse_measure_window(value: &str) -> Result<MeasureWindow, String> {
+    let bound = |part: &str| -> Result<WindowBound, String> {
+        let part = part.trim();
+        match part.strip_suffix('s') {
+            Some(secs) => parse_secs(secs).map(WindowBound::Secs),
+            None => match part.parse::<f64>() {
+                Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(WindowBound::Fraction(fraction)),
+                _ => Err(format!("\"{}\" is neither a fraction in 0..1 nor seconds like 10s", part)),
+            },
+        }
+    };
+
+    let (start, end) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Expected START,END, got \"{}\"", value))?;
+    let window = MeasureWindow {
+        start: bound(start)?,
+        end: bound(end)?,
+    };
+
+    // Mixed bounds can only be ordered once the run length is known
+    let ordered = match (window.start, window.end) {
+        (WindowBound::Fraction(start), WindowBound::Fraction(end))
+        | (WindowBound::Secs(start), WindowBound::Secs(end)) => start < end,
+        _ => true,
+    };
+    if !ordered {
+        return Err(format!("Measure window \"{}\" must start before it ends", value));
+    }
+    Ok(window)
+}
+
+/// Install the --resolve overrides; later calls are ignored
+pub fn set_resolve_overrides(overrides: HashMap<String, IpAddr>) {
+    let _ = RESOLVE_OVERRIDES.set(overrides);
+}
+
+/// The --resolve address for `host`, consulted before DNS
+pub fn resolve_override(host: &str) -> Option<IpAddr> {
+    RESOLVE_OVERRIDES.get()?.get(&host.to_ascii_lowercase()).copied()
+}
+
+/// Parse a WebSocket URL such as `wss://host:8443/ws`
+pub fn parse_ws_url(input: &str) -> Result<WsTarget, String> {
+    let url = url::Url::parse(input).map_err(|e| format!("Invalid URL {}: {}", input, e))?;
+
+    let tls = match url.scheme() {
+        "wss" => true,
+        "ws" => false,
+        scheme => return Err(format!("Unsupported scheme {}, expected ws or wss", scheme)),
+    };
+
+    // IPv6 literals are stored without brackets so they can be used for connecting
+    let host = match url.host() {
+        Some(url::Host::Ipv6(addr)) => addr.to_string(),
+        Some(host) => host.to_string(),
+        None => return Err(format!("URL {} has no host", input)),
+    };
+
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    Ok(WsTarget {
+        tls,
+        host,
+        port: url.port_or_known_default().unwrap_or(if tls { 443 } else { 80 }),
+        path,
+    })
+}
+
+/// The same URL with a ws:// scheme, for the --auto-scheme fallback.
+///
+/// Host, path and query are kept as they are; a port the wss:// URL left implicit
+/// is written out so the fallback doesn't silently switch to port 80.
+pub fn plaintext_url(input: &str) -> Result<String, String> {
+    let mut url = url::Url::parse(input).map_err(|e| format!("Invalid URL {}: {}", input, e))?;
+    let port = url.port_or_known_default();
+    url.set_scheme("ws")
+        .map_err(|()| format!("Can't switch {} to ws://", input))?;
+    url.set_port(port)
+        .map_err(|()| format!("Can't keep the port of {}", input))?;
+    Ok(url.into())
+}
+
+/// Endpoint URLs of a --targets file: one per line, blank lines and # comments skipped.
+///
+/// URLs aren't validated here; a bad one shows up as a failed endpoint in the survey.
+pub fn parse_targets(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read the endpoint list of a --targets file
+pub fn load_targets(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let targets = parse_targets(&text);
+    if targets.is_empty() {
+        return Err(format!("{}: no endpoints listed", path.display()));
+    }
+    Ok(targets)
+}
+
+/// Make sure the open-file soft limit allows `required` descriptors,
+/// raising it toward the hard limit when possible
+#[cfg(unix)]
+pub fn ensure_fd_limit(required: u64) -> Result<(), String> {
+    let required = required as libc::rlim_t;
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(format!(
+            "Failed to read RLIMIT_NOFILE: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    if limit.rlim_cur >= required {
+        return Ok(());
+    }
+
+    if limit.rlim_max >= required {
+        let raised = libc::rlimit {
+            rlim_cur: required,
+            rlim_max: limit.rlim_max,
+        };
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &raised) } == 0 {
+            tracing::info!(from = limit.rlim_cur, to = required, "raised open-file limit");
+            return Ok(());
+        }
+    }
+
+    Err(format!(
+        "Need about {} file descriptors but the open-file limit is {} (hard limit {}); \
+         raise it with `ulimit -n {}` or reduce --clients",
+        required, limit.rlim_cur, limit.rlim_max, required
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn ensure_fd_limit(_required: u64) -> Result<(), String> {
+    Ok(())
+}
+
+/// Pin the calling thread to a single CPU core
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> Result<(), String> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(format!("CPU core {} is out of range", core));
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format!(
+                "Failed to pin thread to core {}: {}",
+                core,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_core: usize) -> Result<(), String> {
+    Err("CPU affinity is only supported on Linux".to_string())
+}
+
+/// A thread's CPU affinity mask, captured so it can be handed back to other threads
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy)]
+pub struct Affinity(libc::cpu_set_t);
+
+#[cfg(target_os = "linux")]
+impl Affinity {
+    /// Affinity of the calling thread
+    pub fn current() -> Result<Self, String> {
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Err(format!("Failed to read CPU affinity: {}", std::io::Error::last_os_error()));
+            }
+            Ok(Affinity(set))
+        }
+    }
+
+    /// Give the calling thread this affinity
+    pub fn apply(&self) -> Result<(), String> {
+        if unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &self.0) } != 0 {
+            return Err(format!("Failed to set CPU affinity: {}", std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[derive(Clone, Copy)]
+pub struct Affinity;
+
+#[cfg(not(target_os = "linux"))]
+impl Affinity {
+    pub fn current() -> Result<Self, String> {
+        Err("CPU affinity is only supported on Linux".to_string())
+    }
+
+    pub fn apply(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// CPU core the calling thread is currently running on
+#[cfg(target_os = "linux")]
+pub fn current_cpu() -> Option<usize> {
+    let cpu = unsafe { libc::sched_getcpu() };
+    (cpu >= 0).then_some(cpu as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_cpu() -> Option<usize> {
+    None
+}
+
+const TLS_RECORD_HEADER_LEN: usize = 5;
+const TLS_CHANGE_CIPHER_SPEC: u8 = 20;
+const TLS_HANDSHAKE: u8 = 22;
+
+/// Access to the TCP socket under a (possibly wrapped) blocking stream
+pub trait AsTcpStream {
+    fn tcp(&self) -> &TcpStream;
+}
+
+impl AsTcpStream for TcpStream {
+    fn tcp(&self) -> &TcpStream {
+        self
+    }
+}
+
+/// Run one I/O call, retrying on EINTR and backing off briefly on WouldBlock.
+///
+/// A blocking socket with a read timeout reports the timeout as WouldBlock too, but
+/// only after waiting it out; retrying those would multiply the timeout, so only a
+/// WouldBlock that came back immediately (a non-blocking socket) is retried.
+pub fn retry_transient<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut would_block = 0;
+    loop {
+        let call_start = Instant::now();
+        match op() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    && would_block < MAX_WOULD_BLOCK_RETRIES
+                    && call_start.elapsed() < WOULD_BLOCK_BACKOFF =>
+            {
+                would_block += 1;
+                std::thread::sleep(WOULD_BLOCK_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Stream wrapper whose reads, writes and flushes go through `retry_transient`.
+///
+/// Retrying each call is safe: one that fails transiently transferred nothing,
+/// unlike `write_all`, which may have written part of the buffer.
+pub struct RetryIo<S>(pub S);
+
+impl<S: Read> Read for RetryIo<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        retry_transient(|| self.0.read(buf))
+    }
+}
+
+impl<S: Write> Write for RetryIo<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retry_transient(|| self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        retry_transient(|| self.0.flush())
+    }
+}
+
+/// Passes bytes through while following the TLS record headers the server sends.
+///
+/// Once armed (after the initial handshake), handshake and ChangeCipherSpec
+/// records signal a TLS 1.2 renegotiation. TLS 1.3 KeyUpdate and
+/// NewSessionTicket messages travel as encrypted application data and can't
+/// be told apart here.
+#[derive(Debug)]
+pub struct TlsRecordWatcher<S> {
+    inner: S,
+    header: Vec<u8>,
+    remaining: usize,
+    armed: bool,
+    control_records: u64,
+}
+
+impl<S> TlsRecordWatcher<S> {
+    pub fn new(inner: S) -> Self {
+        TlsRecordWatcher {
+            inner,
+            header: Vec::with_capacity(TLS_RECORD_HEADER_LEN),
+            remaining: 0,
+            armed: false,
+            control_records: 0,
+        }
+    }
+
+    /// Start counting control records; call once the initial handshake is done
+    pub fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    /// Handshake/ChangeCipherSpec records received since `arm`
+    pub fn control_records(&self) -> u64 {
+        self.control_records
+    }
+
+    fn observe(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let take = self.remaining.min(data.len());
+                self.remaining -= take;
+                data = &data[take..];
+                continue;
+            }
+
+            // A record belongs to the phase in which its first byte (the content type) arrived
+            let control = matches!(data[0], TLS_HANDSHAKE | TLS_CHANGE_CIPHER_SPEC);
+            if self.header.is_empty() && self.armed && control {
+                self.control_records += 1;
+            }
+            let take = (TLS_RECORD_HEADER_LEN - self.header.len()).min(data.len());
+            self.header.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.header.len() == TLS_RECORD_HEADER_LEN {
+                self.remaining = u16::from_be_bytes([self.header[3], self.header[4]]) as usize;
+                self.header.clear();
+            }
+        }
+    }
+}
+
+impl<S: Read> Read for TlsRecordWatcher<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.observe(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for TlsRecordWatcher<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl AsTcpStream for TlsRecordWatcher<TcpStream> {
+    fn tcp(&self) -> &TcpStream {
+        &self.inner
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Fails with each queued error kind in turn, then reads `data`
+    struct FlakyReader {
+        errors: Vec<io::ErrorKind>,
+        data: &'static [u8],
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.errors.is_empty() {
+                return Err(self.errors.remove(0).into());
+            }
+            let n = self.data.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_retry_io_rides_out_transient_errors() {
+        let mut buf = [0u8; 8];
+        let mut reader = RetryIo(FlakyReader {
+            errors: vec![io::ErrorKind::Interrupted],
+            data: b"PONG",
+        });
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"PONG");
+
+        let mut reader = RetryIo(FlakyReader {
+            errors: vec![io::ErrorKind::WouldBlock, io::ErrorKind::Interrupted],
+            data: b"PONG",
+        });
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+
+        // Genuine errors still surface
+        let mut reader = RetryIo(FlakyReader {
+            errors: vec![io::ErrorKind::ConnectionReset],
+            data: b"PONG",
+        });
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    /// Reports WouldBlock the way SO_RCVTIMEO does: after waiting out the timeout
+    struct TimingOutReader {
+        calls: u32,
+    }
+
+    impl Read for TimingOutReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            std::thread::sleep(Duration::from_millis(5));
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[test]
+    fn test_retry_io_surfaces_read_timeouts_at_once() {
+        let mut reader = RetryIo(TimingOutReader { calls: 0 });
+        let err = reader.read(&mut [0u8; 8]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(reader.0.calls, 1);
+    }
+
     #[test]
     fn test_websocket_key_generation() {
         let key1 = generate_websocket_key();
@@ -43,4 +866,282 @@ mod tests {
 
         assert_eq!(original.to_vec(), decoded);
     }
+
+    #[test]
+    fn test_parse_permessage_deflate_extension() {
+        let extensions = parse_extensions(
+            "permessage-deflate; client_max_window_bits=10; server_no_context_takeover",
+        );
+
+        assert_eq!(extensions.len(), 1);
+        let ext = &extensions[0];
+        assert_eq!(ext.name, "permessage-deflate");
+        assert_eq!(ext.param("client_max_window_bits"), Some(Some("10")));
+        assert_eq!(ext.param("server_no_context_takeover"), Some(None));
+        assert_eq!(ext.param("client_no_context_takeover"), None);
+    }
+
+    #[test]
+    fn test_parse_multiple_extensions() {
+        let extensions = parse_extensions("permessage-deflate, x-webkit-deflate; max_window_bits=\"12\"");
+
+        assert_eq!(extensions.len(), 2);
+        assert!(extensions[0].params.is_empty());
+        assert_eq!(extensions[1].param("max_window_bits"), Some(Some("12")));
+    }
+
+    #[test]
+    fn test_find_header() {
+        let response = "HTTP/1.1 101 Switching Protocols\r\n\
+                        upgrade: websocket\r\n\
+                        Sec-WebSocket-Extensions: permessage-deflate\r\n\
+                        \r\n";
+
+        assert_eq!(find_header(response, "Upgrade"), Some("websocket"));
+        assert_eq!(
+            find_header(response, "sec-websocket-extensions"),
+            Some("permessage-deflate")
+        );
+        assert_eq!(find_header(response, "Connection"), None);
+    }
+
+    #[test]
+    fn test_fd_limit_small_requirement() {
+        assert!(ensure_fd_limit(16).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pin_current_thread() {
+        let core = current_cpu().unwrap();
+        std::thread::spawn(move || {
+            pin_current_thread(core).unwrap();
+            assert_eq!(current_cpu(), Some(core));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_validate_upgrade_response_mixed_case() {
+        let response = "HTTP/1.1 101 Switching Protocols\r\n\
+                        uPgRaDe: WebSocket\r\n\
+                        CONNECTION: keep-alive, upgrade\r\n\
+                        \r\n";
+
+        assert!(validate_upgrade_response(response).is_ok());
+        assert_eq!(parse_headers(response).get("upgrade").unwrap(), "WebSocket");
+    }
+
+    #[test]
+    fn test_response_status_and_origin() {
+        assert_eq!(response_status("HTTP/1.1 403 Forbidden\r\n\r\n"), Some(403));
+        assert_eq!(response_status("garbage"), None);
+        assert_eq!(response_status(""), None);
+
+        assert_eq!(parse_origin("https://example.com"), Ok("https://example.com".to_string()));
+        assert!(parse_origin("https://a\r\nX-Injected: 1").is_err());
+        assert!(parse_origin("").is_err());
+    }
+
+    #[test]
+    fn test_validate_upgrade_response_missing_header() {
+        let response = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+        let err = validate_upgrade_response(response).unwrap_err();
+
+        assert!(err.contains("Connection"));
+    }
+
+    #[test]
+    fn test_validate_upgrade_response_wrong_upgrade() {
+        let response = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: h2c\r\nConnection: Upgrade\r\n\r\n";
+        let err = validate_upgrade_response(response).unwrap_err();
+
+        assert!(err.contains("Upgrade"));
+    }
+
+    #[test]
+    fn test_validate_accept_key() {
+        // Sample handshake from RFC 6455 §1.3
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let response = "HTTP/1.1 101 Switching Protocols\r\n\
+                        Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                        \r\n";
+
+        assert!(validate_accept_key(response, key).is_ok());
+        assert!(validate_accept_key(response, &generate_websocket_key()).is_err());
+        assert!(validate_accept_key("HTTP/1.1 101 Switching Protocols\r\n\r\n", key).is_err());
+    }
+
+    #[test]
+    fn test_validate_upgrade_response_rejects_non_101() {
+        let response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 101\r\n\r\n";
+
+        assert!(validate_upgrade_response(response).is_err());
+    }
+
+    #[test]
+    fn test_tls_record_watcher_counts_post_handshake_records() {
+        // Handshake record (split across reads), then application data, then a renegotiation
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&[TLS_HANDSHAKE, 3, 3, 0, 2, 0xAA, 0xBB]);
+        stream.extend_from_slice(&[23, 3, 3, 0, 3, 1, 2, 3]);
+        stream.extend_from_slice(&[TLS_HANDSHAKE, 3, 3, 0, 1, 0]);
+
+        let mut watcher = TlsRecordWatcher::new(&stream[..]);
+        let mut buf = [0u8; 3];
+        watcher.read_exact(&mut buf).unwrap();
+        watcher.arm();
+        io::copy(&mut watcher, &mut io::sink()).unwrap();
+
+        // The first record started before arming, so only the renegotiation counts
+        assert_eq!(watcher.control_records(), 1);
+    }
+
+    #[test]
+    fn test_set_dscp_ipv4() {
+        let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let socket =
+            socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+
+        set_dscp(&socket, addr, 46).unwrap();
+        assert_eq!(socket.tos().unwrap(), 46 << 2);
+    }
+
+    #[test]
+    fn test_parse_close_code() {
+        assert_eq!(parse_close_code("1000"), Ok(1000));
+        assert_eq!(parse_close_code("1011"), Ok(1011));
+        assert_eq!(parse_close_code("4999"), Ok(4999));
+        for code in ["999", "1005", "1006", "1015", "1016", "2999", "5000", "abc"] {
+            assert!(parse_close_code(code).is_err(), "{} should be rejected", code);
+        }
+
+        assert!(parse_close_reason(&"x".repeat(123)).is_ok());
+        assert!(parse_close_reason(&"x".repeat(124)).is_err());
+    }
+
+    #[test]
+    fn test_parse_measure_window() {
+        let window = parse_measure_window("0.1,0.9").unwrap();
+        assert_eq!(window.resolve(100.0), (10.0, 90.0));
+
+        let window = parse_measure_window("10s, 0.5").unwrap();
+        assert_eq!(window.start, WindowBound::Secs(10.0));
+        assert_eq!(window.resolve(60.0), (10.0, 30.0));
+
+        for value in ["0.9,0.1", "20s,10s", "0.1", "0.1,1.5", "-1s,2s", "a,b"] {
+            assert!(parse_measure_window(value).is_err(), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_secs() {
+        assert_eq!(parse_secs("0"), Ok(0.0));
+        assert_eq!(parse_secs("2.5"), Ok(2.5));
+        for value in ["-1", "inf", "NaN", "abc"] {
+            assert!(parse_secs(value).is_err(), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn test_plaintext_url_only_changes_the_scheme() {
+        assert_eq!(
+            plaintext_url("wss://example.com:8443/ws?room=1").unwrap(),
+            "ws://example.com:8443/ws?room=1"
+        );
+        assert_eq!(plaintext_url("wss://[::1]:8443/ws").unwrap(), "ws://[::1]:8443/ws");
+        assert_eq!(plaintext_url("wss://example.com/ws").unwrap(), "ws://example.com:443/ws");
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("0.5"), Ok(0.5));
+        for value in ["0", "-0", "-1", "inf", "NaN", ""] {
+            assert!(parse_interval(value).is_err(), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_resolve() {
+        assert_eq!(
+            parse_resolve("Backend.example:10.0.0.7").unwrap(),
+            ("backend.example".to_string(), "10.0.0.7".parse().unwrap())
+        );
+        assert_eq!(
+            parse_resolve("example.com:[::1]").unwrap(),
+            ("example.com".to_string(), "::1".parse().unwrap())
+        );
+        assert!(parse_resolve("example.com").is_err());
+        assert!(parse_resolve("example.com:not-an-ip").is_err());
+        assert!(parse_resolve(":10.0.0.7").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stream_from_fd_checks_socket() {
+        use std::os::fd::{AsRawFd, IntoRawFd};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let peer = client.peer_addr().unwrap();
+        let stream = stream_from_fd(client.into_raw_fd()).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), peer);
+
+        // A listening socket isn't connected, and a UDP socket isn't a stream
+        assert!(stream_from_fd(listener.as_raw_fd()).is_err());
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(stream_from_fd(udp.as_raw_fd()).is_err());
+        let file = std::fs::File::open("Cargo.toml").unwrap();
+        assert!(stream_from_fd(file.as_raw_fd()).is_err());
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(parse_port_range("40000-40099").unwrap(), (40000, 40099));
+        assert_eq!(parse_port_range("5000-5000").unwrap(), (5000, 5000));
+        assert!(parse_port_range("40000").is_err());
+        assert!(parse_port_range("0-10").is_err());
+        assert!(parse_port_range("50000-40000").is_err());
+        assert!(parse_port_range("1-70000").is_err());
+    }
+
+    #[test]
+    fn test_parse_ws_url() {
+        let target = parse_ws_url("wss://10.25.96.5:8443/ws").unwrap();
+        assert_eq!(
+            target,
+            WsTarget {
+                tls: true,
+                host: "10.25.96.5".to_string(),
+                port: 8443,
+                path: "/ws".to_string(),
+            }
+        );
+
+        let target = parse_ws_url("ws://example.com/chat?room=1").unwrap();
+        assert!(!target.tls);
+        assert_eq!(target.port, 80);
+        assert_eq!(target.path, "/chat?room=1");
+
+        let target = parse_ws_url("wss://[::1]/").unwrap();
+        assert_eq!(target.host, "::1");
+        assert_eq!(target.port, 443);
+    }
+
+    #[test]
+    fn test_parse_ws_url_rejects_other_schemes() {
+        assert!(parse_ws_url("https://example.com/ws").is_err());
+        assert!(parse_ws_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let text = "# fleet\nwss://a.example/ws\n\n  ws://10.0.0.2:8080/ws  \n";
+
+        assert_eq!(
+            parse_targets(text),
+            vec!["wss://a.example/ws".to_string(), "ws://10.0.0.2:8080/ws".to_string()]
+        );
+    }
 }
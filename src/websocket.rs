@@ -1,7 +1,111 @@
-use rand::Rng;
-use std::io;
+use crate::utils::monotonic_nanos;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::{self, Read};
 
 const PING_MESSAGE: &[u8] = b"PING";
+const MAX_CONTROL_PAYLOAD: u64 = 125;
+const READ_CHUNK: usize = 4096;
+/// Largest payload `create_small_frame` builds on the stack
+pub const SMALL_PAYLOAD_MAX: usize = 64;
+/// 2 header bytes + 4 mask bytes; small payloads never need an extended length
+const SMALL_FRAME_HEADER: usize = 6;
+pub const PAYLOAD_HEADER_LEN: usize = 16;
+pub const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+/// Default --max-frame-size
+pub const DEFAULT_MAX_PAYLOAD: u64 = 64 * 1024 * 1024;
+
+/// Reject a payload length over `max_payload` before anything is allocated for it
+fn check_payload_len(payload_len: u64, max_payload: u64) -> Result<(), io::Error> {
+    if payload_len > max_payload {
+        return Err(protocol_error(
+            &format!("{}-byte payload is over the {}-byte limit", payload_len, max_payload),
+            CLOSE_MESSAGE_TOO_BIG,
+        ));
+    }
+    Ok(())
+}
+
+/// Source of the 4-byte masking keys of client frames
+pub trait MaskSource {
+    fn next_mask(&mut self) -> [u8; 4];
+}
+
+/// Fresh random masks from the thread-local RNG (the default)
+pub struct ThreadRngSource;
+
+impl MaskSource for ThreadRngSource {
+    fn next_mask(&mut self) -> [u8; 4] {
+        let mut mask = [0u8; 4];
+        rand::thread_rng().fill(&mut mask);
+        mask
+    }
+}
+
+/// Reproducible masks from a seeded RNG (--seed)
+pub struct SeededSource {
+    rng: StdRng,
+}
+
+impl SeededSource {
+    pub fn new(seed: u64) -> Self {
+        SeededSource {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl MaskSource for SeededSource {
+    fn next_mask(&mut self) -> [u8; 4] {
+        self.rng.gen()
+    }
+}
+
+/// XOR `data` with the repeating 4-byte `mask` (RFC 6455 §5.3); masking and unmasking are the same.
+///
+/// Works a 64-bit word at a time over the aligned middle of the buffer, with the
+/// unaligned head and tail done byte by byte.
+pub fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    // SAFETY: every bit pattern is a valid u64
+    let (head, words, tail) = unsafe { data.align_to_mut::<u64>() };
+    for (i, byte) in head.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    // The words start head.len() bytes into the key stream
+    let phase = head.len() % 4;
+    let mut key = [0u8; 8];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = mask[(phase + i) % 4];
+    }
+    let key = u64::from_ne_bytes(key);
+    for word in words.iter_mut() {
+        *word ^= key;
+    }
+
+    // Whole words don't shift the phase, so the tail picks up where the head left off
+    for (i, byte) in tail.iter_mut().enumerate() {
+        *byte ^= mask[(phase + i) % 4];
+    }
+}
+
+/// A masked frame with a payload of at most SMALL_PAYLOAD_MAX bytes, held in a stack buffer
+#[derive(Clone, Copy)]
+pub struct SmallFrame {
+    buf: [u8; SMALL_FRAME_HEADER + SMALL_PAYLOAD_MAX],
+    len: usize,
+}
+
+impl std::ops::Deref for SmallFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
 
 /// WebSocket frame structure
 #[derive(Debug, Clone)]
@@ -23,10 +127,58 @@ impl WebSocketFrame {
         Self::create_frame(0x81, payload) // FIN + Text frame
     }
 
+    /// Create a binary frame with the given payload
+    pub fn create_binary_frame(payload: &[u8]) -> Vec<u8> {
+        Self::create_frame(0x82, payload) // FIN + Binary frame
+    }
+
     /// Create a WebSocket frame with the given opcode and payload
     pub fn create_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        Self::create_frame_with(opcode, payload, &mut ThreadRngSource)
+    }
+
+    /// Create a WebSocket frame masked with a key from `masks`
+    pub fn create_frame_with<M: MaskSource + ?Sized>(opcode: u8, payload: &[u8], masks: &mut M) -> Vec<u8> {
         let mut frame = Vec::new();
+        Self::create_frame_into(&mut frame, opcode, payload, masks);
+        frame
+    }
+
+    /// Build a frame without touching the heap; None if the payload exceeds SMALL_PAYLOAD_MAX
+    pub fn create_small_frame<M: MaskSource + ?Sized>(
+        opcode: u8,
+        payload: &[u8],
+        masks: &mut M,
+    ) -> Option<SmallFrame> {
         let len = payload.len();
+        if len > SMALL_PAYLOAD_MAX {
+            return None;
+        }
+
+        let mask = masks.next_mask();
+        let mut buf = [0u8; SMALL_FRAME_HEADER + SMALL_PAYLOAD_MAX];
+        buf[0] = opcode;
+        buf[1] = 0x80 | len as u8; // Masked + length
+        buf[2..SMALL_FRAME_HEADER].copy_from_slice(&mask);
+
+        let frame_len = SMALL_FRAME_HEADER + len;
+        buf[SMALL_FRAME_HEADER..frame_len].copy_from_slice(payload);
+        apply_mask(&mut buf[SMALL_FRAME_HEADER..frame_len], mask);
+
+        Some(SmallFrame { buf, len: frame_len })
+    }
+
+    /// Build a frame into `frame`, clearing it first so one send buffer can be reused across pings
+    pub fn create_frame_into<M: MaskSource + ?Sized>(
+        frame: &mut Vec<u8>,
+        opcode: u8,
+        payload: &[u8],
+        masks: &mut M,
+    ) {
+        let len = payload.len();
+        frame.clear();
+        // Header is at most 2 + 8 length bytes + 4 mask bytes
+        frame.reserve(14 + len);
 
         frame.push(opcode);
 
@@ -42,24 +194,164 @@ impl WebSocketFrame {
             frame.extend_from_slice(&(len as u64).to_be_bytes());
         }
 
-        // Generate and add masking key
-        let mut mask = [0u8; 4];
-        rand::thread_rng().fill(&mut mask);
+        // Add masking key
+        let mask = masks.next_mask();
         frame.extend_from_slice(&mask);
 
-        // Add masked payload
-        let masked_payload: Vec<u8> = payload
-            .iter()
-            .enumerate()
-            .map(|(i, &b)| b ^ mask[i % 4])
-            .collect();
-        frame.extend(masked_payload);
+        // Copy the payload in and mask it where it lies
+        let start = frame.len();
+        frame.extend_from_slice(payload);
+        apply_mask(&mut frame[start..], mask);
+    }
 
-        frame
+    /// Parse a WebSocket frame from bytes, rejecting payloads over `max_payload`
+    pub fn parse_frame(data: &[u8], max_payload: u64) -> Result<Self, io::Error> {
+        let header = FrameHeader::parse(data, max_payload)?;
+        let mut payload = data[header.payload_range()].to_vec();
+
+        // Unmask if needed
+        if let Some(mask) = header.masking_key {
+            apply_mask(&mut payload, mask);
+        }
+
+        Ok(header.with_payload(payload))
+    }
+
+    /// Borrow this frame as a view, e.g. to validate it
+    pub fn view(&self) -> FrameView<'_> {
+        FrameView {
+            fin: self.fin,
+            rsv1: self.rsv1,
+            rsv2: self.rsv2,
+            rsv3: self.rsv3,
+            opcode: self.opcode,
+            masked: self.masked,
+            payload_len: self.payload_len,
+            masking_key: self.masking_key,
+            payload: &self.payload,
+        }
+    }
+
+    /// Whether this is a control frame (close, ping, pong)
+    pub fn is_control(&self) -> bool {
+        self.view().is_control()
+    }
+
+    /// Validate the frame against RFC 6455 framing rules
+    pub fn validate(&self) -> Result<(), io::Error> {
+        self.validate_with(false)
+    }
+
+    /// Validate the frame, permitting RSV1 when permessage-deflate was negotiated
+    pub fn validate_with(&self, rsv1_allowed: bool) -> Result<(), io::Error> {
+        self.view().validate_with(rsv1_allowed)
+    }
+}
+
+/// A parsed frame whose payload borrows from the buffer it was parsed from
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView<'a> {
+    pub fin: bool,
+    pub rsv1: bool,
+    pub rsv2: bool,
+    pub rsv3: bool,
+    pub opcode: u8,
+    pub masked: bool,
+    pub payload_len: u64,
+    pub masking_key: Option<[u8; 4]>,
+    pub payload: &'a [u8],
+}
+
+/// Parse a frame without copying its payload.
+///
+/// A masked payload is unmasked in place, so `data` no longer holds the wire bytes afterwards.
+pub fn parse_frame_borrowed(data: &mut [u8], max_payload: u64) -> Result<FrameView<'_>, io::Error> {
+    let header = FrameHeader::parse(data, max_payload)?;
+    let payload = &mut data[header.payload_range()];
+    if let Some(mask) = header.masking_key {
+        apply_mask(payload, mask);
+    }
+
+    Ok(FrameView {
+        fin: header.fin,
+        rsv1: header.rsv1,
+        rsv2: header.rsv2,
+        rsv3: header.rsv3,
+        opcode: header.opcode,
+        masked: header.masked,
+        payload_len: header.payload_len,
+        masking_key: header.masking_key,
+        payload,
+    })
+}
+
+impl FrameView<'_> {
+    /// Whether this is a control frame (close, ping, pong)
+    pub fn is_control(&self) -> bool {
+        (self.opcode & 0x08) != 0
     }
 
-    /// Parse a WebSocket frame from bytes
-    pub fn parse_frame(data: &[u8]) -> Result<Self, io::Error> {
+    /// Validate the frame, permitting RSV1 when permessage-deflate was negotiated
+    pub fn validate_with(&self, rsv1_allowed: bool) -> Result<(), io::Error> {
+        if (self.rsv1 && !rsv1_allowed) || self.rsv2 || self.rsv3 {
+            return Err(protocol_error(
+                "RSV bits set without a negotiated extension",
+                CLOSE_PROTOCOL_ERROR,
+            ));
+        }
+
+        match self.opcode {
+            0x0 | 0x1 | 0x2 | 0x8 | 0x9 | 0xA => {}
+            opcode => {
+                return Err(protocol_error(
+                    &format!("Reserved opcode 0x{:X}", opcode),
+                    CLOSE_PROTOCOL_ERROR,
+                ))
+            }
+        }
+
+        if self.is_control() {
+            if self.payload_len > MAX_CONTROL_PAYLOAD {
+                return Err(protocol_error(
+                    "Control frame payload exceeds 125 bytes",
+                    CLOSE_PROTOCOL_ERROR,
+                ));
+            }
+            if !self.fin {
+                return Err(protocol_error(
+                    "Fragmented control frame",
+                    CLOSE_PROTOCOL_ERROR,
+                ));
+            }
+        }
+
+        // A fragment may split a code point and compressed payloads are
+        // checked by the caller after inflating (check_text_payload), so only
+        // unfragmented uncompressed text frames are checked here
+        if self.fin && !self.rsv1 {
+            check_text_payload(self.opcode, self.payload)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fixed fields of a frame and where its payload starts
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    rsv2: bool,
+    rsv3: bool,
+    opcode: u8,
+    masked: bool,
+    payload_len: u64,
+    masking_key: Option<[u8; 4]>,
+    offset: usize,
+}
+
+impl FrameHeader {
+    /// Parse the header, checking that `data` holds the whole payload
+    fn parse(data: &[u8], max_payload: u64) -> Result<Self, io::Error> {
         if data.len() < 2 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -102,6 +394,7 @@ impl WebSocketFrame {
         } else {
             (payload_len, 2)
         };
+        check_payload_len(payload_len, max_payload)?;
 
         let mut offset = offset;
         let masking_key = if masked {
@@ -126,17 +419,7 @@ impl WebSocketFrame {
             ));
         }
 
-        let mut payload = vec![0u8; payload_len as usize];
-        payload.copy_from_slice(&data[offset..offset + payload_len as usize]);
-
-        // Unmask if needed
-        if let Some(mask) = masking_key {
-            for (i, byte) in payload.iter_mut().enumerate() {
-                *byte ^= mask[i % 4];
-            }
-        }
-
-        Ok(WebSocketFrame {
+        Ok(FrameHeader {
             fin,
             rsv1,
             rsv2,
@@ -145,9 +428,317 @@ impl WebSocketFrame {
             masked,
             payload_len,
             masking_key,
+            offset,
+        })
+    }
+
+    fn payload_range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.payload_len as usize
+    }
+
+    fn with_payload(self, payload: Vec<u8>) -> WebSocketFrame {
+        WebSocketFrame {
+            fin: self.fin,
+            rsv1: self.rsv1,
+            rsv2: self.rsv2,
+            rsv3: self.rsv3,
+            opcode: self.opcode,
+            masked: self.masked,
+            payload_len: self.payload_len,
+            masking_key: self.masking_key,
             payload,
+        }
+    }
+}
+
+/// Text payloads must be valid UTF-8; for inflated or reassembled messages,
+/// which frame validation can't check
+pub fn check_text_payload(opcode: u8, payload: &[u8]) -> io::Result<()> {
+    if opcode == 0x1 && std::str::from_utf8(payload).is_err() {
+        return Err(protocol_error(
+            "Text frame payload is not valid UTF-8",
+            CLOSE_INVALID_PAYLOAD,
+        ));
+    }
+    Ok(())
+}
+
+/// Build an InvalidData error carrying the suggested close code
+fn protocol_error(reason: &str, close_code: u16) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} (close code {})", reason, close_code),
+    )
+}
+
+/// Sequence number and send time embedded at the start of benchmark payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadHeader {
+    pub seq: u64,
+    /// Send time in monotonic nanoseconds (see utils::monotonic_nanos)
+    pub sent_nanos: u64,
+}
+
+impl PayloadHeader {
+    /// Header for a payload sent now
+    pub fn now(seq: u64) -> Self {
+        PayloadHeader {
+            seq,
+            sent_nanos: monotonic_nanos(),
+        }
+    }
+
+    /// Build a payload: 8-byte big-endian sequence, 8-byte send time, then filler
+    pub fn encode(&self, filler: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(PAYLOAD_HEADER_LEN + filler.len());
+        payload.extend_from_slice(&self.seq.to_be_bytes());
+        payload.extend_from_slice(&self.sent_nanos.to_be_bytes());
+        payload.extend_from_slice(filler);
+        payload
+    }
+
+    /// Extract the header from an echoed payload
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let seq: [u8; 8] = payload.get(0..8)?.try_into().ok()?;
+        let sent_nanos: [u8; 8] = payload.get(8..PAYLOAD_HEADER_LEN)?.try_into().ok()?;
+        Some(PayloadHeader {
+            seq: u64::from_be_bytes(seq),
+            sent_nanos: u64::from_be_bytes(sent_nanos),
         })
     }
+
+    /// Milliseconds elapsed since the embedded send time
+    pub fn elapsed_ms(&self) -> f64 {
+        monotonic_nanos().saturating_sub(self.sent_nanos) as f64 / 1_000_000.0
+    }
+}
+
+/// Read exactly one complete frame from a byte stream.
+///
+/// Large payloads span many reads; the header is read first so the full
+/// frame length is known before reading the rest.
+pub fn read_frame<R: Read>(reader: &mut R, max_payload: u64) -> io::Result<Vec<u8>> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+    read_frame_rest(reader, head, max_payload)
+}
+
+/// Read the rest of a frame whose first two bytes were already read
+pub fn read_frame_rest<R: Read>(reader: &mut R, head: [u8; 2], max_payload: u64) -> io::Result<Vec<u8>> {
+    let mut frame = head.to_vec();
+
+    let extended_len = match frame[1] & 0x7F {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask_len = if frame[1] & 0x80 != 0 { 4 } else { 0 };
+
+    frame.resize(2 + extended_len, 0);
+    reader.read_exact(&mut frame[2..])?;
+
+    let payload_len = match extended_len {
+        2 => u16::from_be_bytes([frame[2], frame[3]]) as u64,
+        8 => u64::from_be_bytes(frame[2..10].try_into().unwrap()),
+        _ => (frame[1] & 0x7F) as u64,
+    };
+    check_payload_len(payload_len, max_payload)?;
+    let payload_len = usize::try_from(payload_len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Frame too large"))?;
+
+    let header_len = frame.len() + mask_len;
+    frame.resize(header_len + payload_len, 0);
+    reader.read_exact(&mut frame[2 + extended_len..])?;
+
+    Ok(frame)
+}
+
+/// Total length of the frame at the start of `data`, or None until enough of
+/// the header has arrived to know it
+pub fn frame_len(data: &[u8]) -> Option<u64> {
+    let (payload_len, header_len) = declared_len(data)?;
+    Some(payload_len.saturating_add(header_len as u64))
+}
+
+/// Fail once the header at the start of `data` declares a payload over `max_payload`,
+/// so a reader can stop buffering before the payload arrives
+pub fn check_declared_len(data: &[u8], max_payload: u64) -> Result<(), io::Error> {
+    match declared_len(data) {
+        Some((payload_len, _)) => check_payload_len(payload_len, max_payload),
+        None => Ok(()),
+    }
+}
+
+/// Declared payload length and header length of the frame at the start of `data`
+fn declared_len(data: &[u8]) -> Option<(u64, usize)> {
+    let second = *data.get(1)?;
+    let extended_len = match second & 0x7F {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask_len = if second & 0x80 != 0 { 4 } else { 0 };
+    let extended = data.get(2..2 + extended_len)?;
+
+    let payload_len = match extended_len {
+        2 => u16::from_be_bytes([extended[0], extended[1]]) as u64,
+        8 => u64::from_be_bytes(extended.try_into().unwrap()),
+        _ => (second & 0x7F) as u64,
+    };
+    Some((payload_len, 2 + extended_len + mask_len))
+}
+
+/// Split the complete frames off the front of `data`.
+///
+/// Returns the raw frames and the number of bytes they cover; anything past
+/// that is the start of a frame that hasn't fully arrived yet.
+pub fn parse_frames(data: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut frames = Vec::new();
+    let mut consumed = 0;
+
+    while let Some(len) = frame_len(&data[consumed..]) {
+        match usize::try_from(len) {
+            Ok(len) if len <= data.len() - consumed => {
+                frames.push(&data[consumed..consumed + len]);
+                consumed += len;
+            }
+            _ => break,
+        }
+    }
+
+    (frames, consumed)
+}
+
+/// Accumulating receive buffer for a blocking stream.
+///
+/// Bytes past the end of one frame (a coalesced or unexpected extra frame)
+/// stay buffered for the next read instead of being dropped.
+#[derive(Debug)]
+pub struct FrameBuffer {
+    buf: Vec<u8>,
+    max_payload: u64,
+}
+
+impl FrameBuffer {
+    /// A buffer that rejects frames declaring payloads over `max_payload`
+    pub fn new(max_payload: u64) -> Self {
+        FrameBuffer {
+            buf: Vec::new(),
+            max_payload,
+        }
+    }
+
+    /// Bytes received but not yet returned as a frame
+    pub fn pending(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Queue bytes that were read from the stream elsewhere (e.g. behind a handshake response)
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Do one read from the stream into the buffer
+    pub fn fill<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let mut chunk = [0u8; READ_CHUNK];
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed"));
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(n)
+    }
+
+    /// Return the next complete frame, reading from the stream only as needed
+    pub fn read_frame<R: Read>(&mut self, reader: &mut R) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some((payload_len, header_len)) = declared_len(&self.buf) {
+                check_payload_len(payload_len, self.max_payload)?;
+                let len = usize::try_from(payload_len)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Frame too large"))?
+                    + header_len;
+                if self.buf.len() >= len {
+                    return Ok(self.buf.drain(..len).collect());
+                }
+                self.buf.reserve(len - self.buf.len());
+            }
+            self.fill(reader)?;
+        }
+    }
+}
+
+/// A data message put back together from its fragments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembledMessage {
+    pub opcode: u8,
+    /// RSV1 of the first fragment: the whole message is compressed
+    pub rsv1: bool,
+    pub payload: Vec<u8>,
+    /// Frames the message arrived in
+    pub frames: usize,
+}
+
+/// Reassembles fragmented data messages (RFC 6455 section 5.4).
+///
+/// Control frames may be interleaved between fragments and are left to the caller.
+#[derive(Debug)]
+pub struct MessageAssembler {
+    current: Option<AssembledMessage>,
+    max_payload: u64,
+}
+
+impl MessageAssembler {
+    /// An assembler that rejects messages growing past `max_payload` bytes
+    pub fn new(max_payload: u64) -> Self {
+        MessageAssembler {
+            current: None,
+            max_payload,
+        }
+    }
+
+    /// Payload bytes buffered for the message in progress
+    pub fn buffered(&self) -> usize {
+        self.current.as_ref().map_or(0, |message| message.payload.len())
+    }
+
+    /// Add a data frame; returns the message once its final fragment arrives
+    pub fn push(&mut self, frame: &FrameView) -> Result<Option<AssembledMessage>, io::Error> {
+        // Every fragment may be within the frame limit while the message grows without end
+        let total = (self.buffered() as u64).saturating_add(frame.payload.len() as u64);
+        if let Err(e) = check_payload_len(total, self.max_payload) {
+            self.current = None;
+            return Err(e);
+        }
+
+        match (frame.opcode, self.current.as_mut()) {
+            (0x0, Some(message)) => {
+                message.payload.extend_from_slice(frame.payload);
+                message.frames += 1;
+            }
+            (0x0, None) => {
+                return Err(protocol_error(
+                    "Continuation frame without a message to continue",
+                    CLOSE_PROTOCOL_ERROR,
+                ))
+            }
+            (_, Some(_)) => {
+                return Err(protocol_error(
+                    "New data frame before the fragmented message finished",
+                    CLOSE_PROTOCOL_ERROR,
+                ))
+            }
+            (opcode, None) => {
+                self.current = Some(AssembledMessage {
+                    opcode,
+                    rsv1: frame.rsv1,
+                    payload: frame.payload.to_vec(),
+                    frames: 1,
+                })
+            }
+        }
+
+        Ok(if frame.fin { self.current.take() } else { None })
+    }
 }
 
 /// Helper to create PING message as WebSocket frame
@@ -173,6 +764,148 @@ mod tests {
         assert!(frame[1] & 0x80 != 0);
     }
 
+    /// Hands out one fixed mask
+    struct FixedMask([u8; 4]);
+
+    impl MaskSource for FixedMask {
+        fn next_mask(&mut self) -> [u8; 4] {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_create_frame_with_mask_source() {
+        let frame = WebSocketFrame::create_frame_with(0x81, b"PING", &mut FixedMask([1, 2, 3, 4]));
+        assert_eq!(frame, [0x81, 0x84, 1, 2, 3, 4, b'P' ^ 1, b'I' ^ 2, b'N' ^ 3, b'G' ^ 4]);
+
+        // The same seed gives the same sequence of masks
+        let mut a = SeededSource::new(42);
+        let mut b = SeededSource::new(42);
+        for _ in 0..3 {
+            assert_eq!(
+                WebSocketFrame::create_frame_with(0x82, b"PING", &mut a),
+                WebSocketFrame::create_frame_with(0x82, b"PING", &mut b)
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_frame_matches_reference_layout() {
+        // The header and masked payload laid out byte by byte, as create_frame used to build them
+        fn reference(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+            let len = payload.len();
+            let mut frame = vec![opcode];
+            if len < 126 {
+                frame.push(0x80 | len as u8);
+            } else if len < 65536 {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            } else {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, &b)| b ^ mask[i % 4]));
+            frame
+        }
+
+        let mut rng = StdRng::seed_from_u64(11);
+        for &len in &[0, 1, 7, 125, 126, 127, 1000, 65535, 65536, 70_001] {
+            let mut payload = vec![0u8; len];
+            rng.fill(payload.as_mut_slice());
+            let mask: [u8; 4] = rng.gen();
+            assert_eq!(
+                WebSocketFrame::create_frame_with(0x82, &payload, &mut FixedMask(mask)),
+                reference(0x82, &payload, mask),
+                "len {}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_small_frame_matches_general_path() {
+        let payload: Vec<u8> = (0..=SMALL_PAYLOAD_MAX as u8).collect();
+        for len in 0..=SMALL_PAYLOAD_MAX {
+            let small = WebSocketFrame::create_small_frame(0x81, &payload[..len], &mut FixedMask([5, 6, 7, 8])).unwrap();
+            let general = WebSocketFrame::create_frame_with(0x81, &payload[..len], &mut FixedMask([5, 6, 7, 8]));
+            assert_eq!(&small[..], general.as_slice(), "len {}", len);
+        }
+
+        assert!(WebSocketFrame::create_small_frame(0x81, &payload, &mut FixedMask([5, 6, 7, 8])).is_none());
+    }
+
+    #[test]
+    fn test_create_frame_into_reuses_buffer() {
+        let mut frame = Vec::new();
+        WebSocketFrame::create_frame_into(&mut frame, 0x82, &[9u8; 1000], &mut FixedMask([1, 2, 3, 4]));
+        let capacity = frame.capacity();
+
+        // A smaller frame overwrites the previous one without reallocating
+        WebSocketFrame::create_frame_into(&mut frame, 0x81, b"PING", &mut FixedMask([1, 2, 3, 4]));
+        assert_eq!(frame, WebSocketFrame::create_frame_with(0x81, b"PING", &mut FixedMask([1, 2, 3, 4])));
+        assert_eq!(frame.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_parse_frame_borrowed_matches_owned() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for &len in &[0, 5, 125, 126, 1000, 70_000] {
+            let mut payload = vec![0u8; len];
+            rng.fill(payload.as_mut_slice());
+
+            // Masked as a client sends it, and unmasked as a server sends it
+            let masked = WebSocketFrame::create_binary_frame(&payload);
+            let mut unmasked = masked.clone();
+            unmasked[1] &= 0x7F;
+            let mask_at = unmasked.len() - len - 4;
+            unmasked.drain(mask_at..mask_at + 4);
+            unmasked[mask_at..].copy_from_slice(&payload);
+
+            for data in [masked, unmasked] {
+                let owned = WebSocketFrame::parse_frame(&data, DEFAULT_MAX_PAYLOAD).unwrap();
+                let mut input = data.clone();
+                let view = parse_frame_borrowed(&mut input, DEFAULT_MAX_PAYLOAD).unwrap();
+                assert_eq!(view.payload, payload.as_slice());
+                assert_eq!(view.payload, owned.payload.as_slice());
+                assert_eq!((view.opcode, view.masked, view.masking_key), (owned.opcode, owned.masked, owned.masking_key));
+            }
+        }
+
+        assert!(parse_frame_borrowed(&mut [0x82, 0x05, 1, 2], DEFAULT_MAX_PAYLOAD).is_err());
+    }
+
+    #[test]
+    fn test_apply_mask_matches_bytewise_xor() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut buffer = vec![0u8; 1024];
+
+        for _ in 0..500 {
+            rng.fill(buffer.as_mut_slice());
+            let mask: [u8; 4] = rng.gen();
+            // Random start offsets move the slice across u64 alignments
+            let start = rng.gen_range(0..16);
+            let len = rng.gen_range(0..buffer.len() - start);
+
+            let data = &mut buffer[start..start + len];
+            let expected: Vec<u8> = data.iter().enumerate().map(|(i, &b)| b ^ mask[i % 4]).collect();
+            apply_mask(data, mask);
+            assert_eq!(data, expected.as_slice(), "start {} len {}", start, len);
+        }
+    }
+
+    #[test]
+    fn test_create_binary_frame() {
+        let frame = WebSocketFrame::create_binary_frame(&[0xFF, 0x00, 0x80]);
+
+        // First byte should be 0x82 (FIN + Binary frame)
+        assert_eq!(frame[0], 0x82);
+
+        let parsed = WebSocketFrame::parse_frame(&frame, DEFAULT_MAX_PAYLOAD).unwrap();
+        assert_eq!(parsed.opcode, 2);
+        assert_eq!(parsed.payload, vec![0xFF, 0x00, 0x80]);
+    }
+
     #[test]
     fn test_ping_frame() {
         let frame = create_ping_frame();
@@ -187,7 +920,7 @@ mod tests {
         let original = b"Test message";
         let frame_data = WebSocketFrame::create_text_frame(original);
 
-        let frame = WebSocketFrame::parse_frame(&frame_data).unwrap();
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
 
         assert_eq!(frame.fin, true);
         assert_eq!(frame.opcode, 1); // Text frame
@@ -199,9 +932,249 @@ mod tests {
         let large_payload = vec![0x42u8; 1000];
         let frame_data = WebSocketFrame::create_text_frame(&large_payload);
 
-        let frame = WebSocketFrame::parse_frame(&frame_data).unwrap();
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
 
         assert_eq!(frame.payload.len(), 1000);
         assert_eq!(frame.payload, large_payload);
     }
+
+    #[test]
+    fn test_read_frame_from_file_payload() {
+        // Large enough for the 64-bit extended length
+        let payload: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("ws_payload_{}.bin", std::process::id()));
+        std::fs::write(&path, &payload).unwrap();
+        let loaded = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut stream = io::Cursor::new(WebSocketFrame::create_text_frame(&loaded));
+        let frame_data = read_frame(&mut stream, DEFAULT_MAX_PAYLOAD).unwrap();
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert_eq!(frame.payload_len, payload.len() as u64);
+        assert_eq!(frame.payload, payload);
+        assert_eq!(stream.position() as usize, frame_data.len());
+    }
+
+    #[test]
+    fn test_validate_accepts_text_frame() {
+        let frame_data = WebSocketFrame::create_text_frame(b"PONG");
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_reserved_opcode() {
+        let frame = WebSocketFrame::parse_frame(&[0x83, 0x00], DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_rsv_bits() {
+        let frame = WebSocketFrame::parse_frame(&[0xC1, 0x00], DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_oversized_control_frame() {
+        let frame_data = WebSocketFrame::create_frame(0x89, &[0u8; 126]);
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.is_control());
+        assert!(frame.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_fragmented_control_frame() {
+        let frame = WebSocketFrame::parse_frame(&[0x09, 0x00], DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_utf8_text() {
+        let frame_data = WebSocketFrame::create_text_frame(&[0xC3, 0x28]);
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        let err = frame.validate().unwrap_err();
+        assert!(err.to_string().contains("1007"));
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_utf8_text() {
+        let frame_data = WebSocketFrame::create_text_frame("Привет, мир".as_bytes());
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_ok());
+    }
+
+    #[test]
+    fn test_compressed_text_is_checked_after_inflating() {
+        // RSV1 text carries DEFLATE bytes, which needn't be UTF-8 until inflated
+        let frame_data = WebSocketFrame::create_frame(0xC1, &[0xC3, 0x28]);
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+        assert!(frame.validate_with(true).is_ok());
+
+        let err = check_text_payload(0x1, &[0xC3, 0x28]).unwrap_err();
+        assert!(err.to_string().contains("1007"));
+        assert!(check_text_payload(0x1, "Привет".as_bytes()).is_ok());
+        assert!(check_text_payload(0x2, &[0xC3, 0x28]).is_ok());
+    }
+
+    #[test]
+    fn test_validate_skips_utf8_for_binary() {
+        let frame_data = WebSocketFrame::create_frame(0x82, &[0xC3, 0x28]);
+        let frame = WebSocketFrame::parse_frame(&frame_data, DEFAULT_MAX_PAYLOAD).unwrap();
+
+        assert!(frame.validate().is_ok());
+    }
+
+    #[test]
+    fn test_payload_header_roundtrip() {
+        let header = PayloadHeader {
+            seq: 0x0102_0304_0506_0708,
+            sent_nanos: 42_000_000,
+        };
+        let payload = header.encode(PING_MESSAGE);
+
+        assert_eq!(payload.len(), PAYLOAD_HEADER_LEN + PING_MESSAGE.len());
+        assert_eq!(&payload[PAYLOAD_HEADER_LEN..], PING_MESSAGE);
+        assert_eq!(PayloadHeader::decode(&payload), Some(header));
+    }
+
+    #[test]
+    fn test_payload_header_too_short() {
+        assert_eq!(PayloadHeader::decode(b"short"), None);
+        assert_eq!(PayloadHeader::decode(&[0u8; PAYLOAD_HEADER_LEN - 1]), None);
+    }
+
+    #[test]
+    fn test_payload_header_elapsed() {
+        let header = PayloadHeader::now(1);
+
+        assert!(header.elapsed_ms() >= 0.0);
+    }
+
+    #[test]
+    fn test_parse_frames_coalesced_and_partial() {
+        let first = WebSocketFrame::create_text_frame(b"PONG");
+        let second = WebSocketFrame::create_binary_frame(&[7u8; 300]);
+        let mut data = [first.clone(), second.clone()].concat();
+        data.extend_from_slice(&second[..3]);
+
+        let (frames, consumed) = parse_frames(&data);
+
+        assert_eq!(frames, vec![first.as_slice(), second.as_slice()]);
+        assert_eq!(consumed, first.len() + second.len());
+        assert!(parse_frames(&data[..1]).0.is_empty());
+    }
+
+    #[test]
+    fn test_frame_buffer_carries_leftover_bytes() {
+        let first = WebSocketFrame::create_text_frame(b"PONG");
+        let second = WebSocketFrame::create_text_frame(b"EXTRA");
+        let mut stream = std::io::Cursor::new([first.clone(), second.clone()].concat());
+        let mut buffer = FrameBuffer::new(DEFAULT_MAX_PAYLOAD);
+
+        // A single read picks up both frames; the second stays buffered
+        assert_eq!(buffer.read_frame(&mut stream).unwrap(), first);
+        assert_eq!(buffer.pending(), second.as_slice());
+        assert_eq!(buffer.read_frame(&mut stream).unwrap(), second);
+        assert!(buffer.pending().is_empty());
+        assert!(buffer.read_frame(&mut stream).is_err());
+    }
+
+    #[test]
+    fn test_frame_buffer_large_frame_across_reads() {
+        let frame = WebSocketFrame::create_binary_frame(&vec![1u8; 100_000]);
+        let mut stream = std::io::Cursor::new(frame.clone());
+        let mut buffer = FrameBuffer::new(DEFAULT_MAX_PAYLOAD);
+
+        assert_eq!(buffer.read_frame(&mut stream).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_message_assembler_reassembles_fragments() {
+        let stream = [
+            WebSocketFrame::create_frame(0x02, b"snap"),
+            WebSocketFrame::create_frame(0x89, b"keepalive"),
+            WebSocketFrame::create_frame(0x00, b"sh"),
+            WebSocketFrame::create_frame(0x80, b"ot"),
+            WebSocketFrame::create_frame(0x81, b"next"),
+        ]
+        .concat();
+        let mut frames = parse_frames(&stream).0.into_iter().map(<[u8]>::to_vec).collect::<Vec<_>>();
+
+        let mut assembler = MessageAssembler::new(DEFAULT_MAX_PAYLOAD);
+        let mut messages = Vec::new();
+        for data in frames.iter_mut() {
+            let frame = parse_frame_borrowed(data, DEFAULT_MAX_PAYLOAD).unwrap();
+            // The interleaved ping is the caller's business
+            if frame.is_control() {
+                continue;
+            }
+            messages.extend(assembler.push(&frame).unwrap());
+        }
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].opcode, 0x2);
+        assert_eq!(messages[0].payload, b"snapshot");
+        assert_eq!(messages[0].frames, 3);
+        assert_eq!(messages[1].payload, b"next");
+        assert_eq!(assembler.buffered(), 0);
+
+        // A continuation needs a message to continue, and a message must finish first
+        let mut push = |opcode: u8, payload: &[u8]| {
+            let mut data = WebSocketFrame::create_frame(opcode, payload);
+            assembler.push(&parse_frame_borrowed(&mut data, DEFAULT_MAX_PAYLOAD).unwrap())
+        };
+        assert!(push(0x80, b"x").is_err());
+        push(0x01, b"a").unwrap();
+        assert!(push(0x81, b"b").is_err());
+    }
+
+    #[test]
+    fn test_message_assembler_caps_message_size() {
+        let mut assembler = MessageAssembler::new(10);
+        let mut push = |opcode: u8, payload: &[u8]| {
+            let mut data = WebSocketFrame::create_frame(opcode, payload);
+            assembler.push(&parse_frame_borrowed(&mut data, DEFAULT_MAX_PAYLOAD).unwrap())
+        };
+
+        // Each fragment is small, but together they pass the limit
+        push(0x02, &[0u8; 4]).unwrap();
+        push(0x00, &[0u8; 4]).unwrap();
+        let err = push(0x00, &[0u8; 4]).unwrap_err();
+        assert!(err.to_string().contains("1009"), "{}", err);
+
+        // The rejected message is dropped rather than kept growing
+        assert_eq!(push(0x82, &[0u8; 10]).unwrap().unwrap().payload.len(), 10);
+    }
+
+    #[test]
+    fn test_oversized_payload_rejected_before_allocating() {
+        // Header alone, claiming a 4 GiB unmasked binary payload
+        let mut header = vec![0x82, 0x7F];
+        header.extend_from_slice(&(4u64 << 30).to_be_bytes());
+
+        let err = WebSocketFrame::parse_frame(&header, DEFAULT_MAX_PAYLOAD).unwrap_err();
+        assert!(err.to_string().contains("1009"), "{}", err);
+        let err = read_frame(&mut header.as_slice(), DEFAULT_MAX_PAYLOAD).unwrap_err();
+        assert!(err.to_string().contains("1009"), "{}", err);
+
+        // The limit is hit as soon as the header is buffered, before reserving for the payload
+        let mut buffer = FrameBuffer::new(DEFAULT_MAX_PAYLOAD);
+        let err = buffer.read_frame(&mut header.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("1009"), "{}", err);
+        assert_eq!(buffer.pending(), header.as_slice());
+
+        // The limit is the caller's, not a fixed cap
+        let frame = WebSocketFrame::create_binary_frame(&[0u8; 200]);
+        assert!(WebSocketFrame::parse_frame(&frame, 199).is_err());
+        assert!(WebSocketFrame::parse_frame(&frame, 200).is_ok());
+        assert!(FrameBuffer::new(100).read_frame(&mut frame.as_slice()).is_err());
+    }
 }